@@ -1,9 +1,19 @@
+pub mod autoreply;
+pub mod custom;
+pub mod dad;
+pub mod infractions;
 pub mod meta;
+pub mod moderation;
 pub mod music;
 pub mod openai;
 pub mod register;
+pub mod reminders;
+pub mod responders;
 pub mod userdir;
 // Public RAG-related commands disabled; admins use /userdir only
 pub mod userdir_admin;
 pub mod profile_admin;
+pub mod persona;
+pub mod prompt;
 pub mod roleplay;
+pub mod soundboard;
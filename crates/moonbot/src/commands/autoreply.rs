@@ -0,0 +1,226 @@
+use crate::{handlers::auto_reply, utils::send_err_msg, Context, Error};
+use moonbot_db::{AutoReplyOverrides, AutoReplyTrigger};
+use poise::serenity_prelude as serenity;
+
+fn scope(channel: Option<&serenity::GuildChannel>) -> String {
+    match channel {
+        Some(channel) => format!("<#{}>", channel.id),
+        None => "the whole server".to_string(),
+    }
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum AutoReplyAction {
+    Show,
+    Set,
+    Reset,
+}
+
+/// Configure random replies for the server or a channel
+#[poise::command(
+    slash_command,
+    rename = "auto-reply",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn auto_reply(
+    ctx: Context<'_>,
+    #[description = "Action"] action: AutoReplyAction,
+    #[description = "Channel to configure, leave empty for the whole server"]
+    #[channel_types("Text", "News")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Chance to reply in percent (for set)"]
+    #[min = 0.0]
+    #[max = 100.0]
+    chance: Option<f64>,
+    #[description = "Seconds between replies in a channel (for set)"] cooldown: Option<u32>,
+    #[description = "Whether replies may fire in threads (for set)"] threads: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let channel_key = channel.as_ref().map(|c| c.id.get() as i64).unwrap_or(0);
+    let mut overrides = moonbot_db::get_auto_reply_settings(db, guild_id.get() as i64, channel_key).await;
+
+    match action {
+        AutoReplyAction::Show => {}
+        AutoReplyAction::Set => {
+            if chance.is_none() && cooldown.is_none() && threads.is_none() {
+                send_err_msg(ctx, "Error", "Give at least one setting to change.").await;
+                return Ok(());
+            }
+            if let Some(v) = chance {
+                overrides.chance = Some(v / 100.0);
+            }
+            if let Some(v) = cooldown {
+                overrides.cooldown_secs = Some(v as i64);
+            }
+            if let Some(v) = threads {
+                overrides.threads = Some(v);
+            }
+            moonbot_db::set_auto_reply_settings(db, guild_id.get() as i64, channel_key, overrides).await?;
+        }
+        AutoReplyAction::Reset => {
+            moonbot_db::set_auto_reply_settings(db, guild_id.get() as i64, channel_key, AutoReplyOverrides::default())
+                .await?;
+        }
+    }
+
+    let (chance, cooldown, threads) =
+        auto_reply::settings(ctx.framework(), guild_id, channel.as_ref().map(|c| c.id)).await;
+    let embed = serenity::CreateEmbed::new()
+        .title("Random Replies")
+        .color(0x2ECC71)
+        .description(format!("Settings for {}", scope(channel.as_ref())))
+        .field("Chance", format!("{:.1}%", chance * 100.0), true)
+        .field(
+            "Cooldown",
+            humantime::format_duration(std::time::Duration::from_secs(cooldown)).to_string(),
+            true,
+        )
+        .field("In Threads", if threads { "Allowed" } else { "Never" }, true);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum TriggersAction {
+    List,
+    Add,
+    Remove,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum TriggerEffect {
+    #[name = "Force (always reply)"]
+    Force,
+    #[name = "Suppress (never reply)"]
+    Suppress,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum TriggerKind {
+    #[name = "Keyword (whole word, any case)"]
+    Keyword,
+    #[name = "Regex"]
+    Regex,
+}
+
+/// Words or patterns that force or suppress random replies
+#[poise::command(
+    slash_command,
+    rename = "auto-reply-triggers",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn auto_reply_triggers(
+    ctx: Context<'_>,
+    #[description = "Action"] action: TriggersAction,
+    #[description = "What a match does (for add/remove)"] effect: Option<TriggerEffect>,
+    #[description = "How to match (for add/remove)"] kind: Option<TriggerKind>,
+    #[description = "Keyword or regular expression (for add/remove)"] value: Option<String>,
+    #[description = "Only in this channel, leave empty for the whole server (for add/remove)"]
+    #[channel_types("Text", "News")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+
+    if !matches!(action, TriggersAction::List) {
+        let (Some(effect), Some(kind), Some(value)) =
+            (effect, kind, value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()))
+        else {
+            send_err_msg(ctx, "Error", "Give an effect, a kind and a value.").await;
+            return Ok(());
+        };
+        let trigger = AutoReplyTrigger {
+            channel_id: channel.as_ref().map(|c| c.id.get() as i64).unwrap_or(0),
+            effect: match effect {
+                TriggerEffect::Force => "force",
+                TriggerEffect::Suppress => "suppress",
+            }
+            .to_string(),
+            kind: match kind {
+                TriggerKind::Keyword => "keyword",
+                TriggerKind::Regex => "regex",
+            }
+            .to_string(),
+            value,
+            added_by: Some(ctx.author().id.get() as i64),
+        };
+        // Removing still works for triggers that no longer compile
+        if let TriggersAction::Add = action {
+            if let Err(e) = auto_reply::compile_trigger(&trigger.kind, &trigger.value) {
+                send_err_msg(ctx, "Error", format!("That isn't a valid {}: {}", trigger.kind, e).as_str()).await;
+                return Ok(());
+            }
+        }
+        let (changed, title) = match action {
+            TriggersAction::Add => (
+                moonbot_db::add_auto_reply_trigger(db, guild_id.get() as i64, &trigger).await?,
+                "Trigger added",
+            ),
+            _ => (
+                moonbot_db::remove_auto_reply_trigger(
+                    db,
+                    guild_id.get() as i64,
+                    trigger.channel_id,
+                    &trigger.effect,
+                    &trigger.kind,
+                    &trigger.value,
+                )
+                .await?,
+                "Trigger removed",
+            ),
+        };
+        if !changed {
+            send_err_msg(
+                ctx,
+                "Error",
+                format!(
+                    "That trigger {} for {}.",
+                    if title == "Trigger added" { "already exists" } else { "doesn't exist" },
+                    scope(channel.as_ref())
+                )
+                .as_str(),
+            )
+            .await;
+            return Ok(());
+        }
+        auto_reply::invalidate_triggers(guild_id);
+        let embed = serenity::CreateEmbed::new()
+            .title(title)
+            .color(0x2ECC71)
+            .description(format!(
+                "{} {} `{}` in {}",
+                trigger.effect,
+                trigger.kind,
+                trigger.value,
+                scope(channel.as_ref())
+            ));
+        ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let triggers = moonbot_db::auto_reply_triggers(db, guild_id.get() as i64).await?;
+    let list = |effect: &str| {
+        let items = triggers
+            .iter()
+            .filter(|t| t.effect == effect)
+            .map(|t| {
+                let place = if t.channel_id == 0 { String::new() } else { format!(" in <#{}>", t.channel_id) };
+                format!("{} `{}`{}", t.kind, t.value, place)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if items.is_empty() { "None".to_string() } else { items }
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title("Random Reply Triggers")
+        .color(0x2ECC71)
+        .field("Force", list("force"), false)
+        .field("Suppress", list("suppress"), false);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
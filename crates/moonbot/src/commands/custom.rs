@@ -0,0 +1,325 @@
+use crate::{
+    custom_commands::{self, MAX_CONTENT, MAX_EMBED_CONTENT},
+    utils::send_err_msg,
+    Context, Error,
+};
+use moonbot_db::CustomCommand;
+use poise::serenity_prelude as serenity;
+
+async fn autocomplete_command(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    moonbot_db::custom_commands(ctx.data().db, guild_id.get() as i64)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|command| command.name)
+        .filter(|name| name.contains(&partial))
+        .take(25)
+        .collect()
+}
+
+/// Split a comma separated list of aliases.
+fn parse_aliases(aliases: &str) -> Vec<String> {
+    let mut list = Vec::new();
+    for alias in aliases.split(',').map(|a| a.trim().to_lowercase()) {
+        if !alias.is_empty() && !list.contains(&alias) {
+            list.push(alias);
+        }
+    }
+    list
+}
+
+/// Check that names are free and well formed, or say why not.
+async fn check_names(ctx: Context<'_>, names: &[String], except: Option<i64>) -> Result<Option<String>, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let existing = moonbot_db::custom_commands(ctx.data().db, guild_id.get() as i64).await?;
+    for name in names {
+        if !custom_commands::valid_name(name) {
+            return Ok(Some(format!("`{}` isn't a valid name, use up to 32 characters of a-z, 0-9, - and _.", name)));
+        }
+        let built_in = ctx
+            .framework()
+            .options()
+            .commands
+            .iter()
+            .any(|c| c.name == *name || c.aliases.contains(name));
+        if built_in {
+            return Ok(Some(format!("`{}` is a built-in command.", name)));
+        }
+        let taken = existing
+            .iter()
+            .filter(|c| Some(c.id) != except)
+            .any(|c| c.name == *name || c.aliases.contains(name));
+        if taken {
+            return Ok(Some(format!("`{}` is already taken.", name)));
+        }
+    }
+    Ok(None)
+}
+
+/// Check the length of content for how it will be sent.
+fn check_content(content: &str, embed: bool) -> Option<String> {
+    let max = if embed { MAX_EMBED_CONTENT } else { MAX_CONTENT };
+    if content.trim().is_empty() {
+        Some("The content can't be empty.".to_string())
+    } else if content.chars().count() > max {
+        Some(format!("The content can be at most {} characters.", max))
+    } else {
+        None
+    }
+}
+
+fn describe(command: &CustomCommand) -> serenity::CreateEmbed {
+    let aliases = if command.aliases.is_empty() {
+        "None".to_string()
+    } else {
+        command.aliases.iter().map(|a| format!("`{}`", a)).collect::<Vec<_>>().join(", ")
+    };
+    serenity::CreateEmbed::new()
+        .title(format!("~{}", command.name))
+        .color(0x2ECC71)
+        .description(format!("```\n{}\n```", command.content.replace("```", "'''")))
+        .field("Aliases", aliases, true)
+        .field("Embed", if command.embed { "Yes" } else { "No" }, true)
+        .field("Uses", command.uses.to_string(), true)
+        .field(
+            "Created",
+            format!(
+                "<t:{}:R>{}",
+                command.created_at.timestamp(),
+                command.created_by.map(|u| format!(" by <@{}>", u)).unwrap_or_default()
+            ),
+            true,
+        )
+}
+
+/// Canned responses for this server, run with ~name
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("add", "edit", "delete", "list", "info", "run"),
+    subcommand_required
+)]
+pub async fn cc(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Add a custom command
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name to run it by (a-z, 0-9, - and _)"] name: String,
+    #[description = "Response, with {user}, {user.name}, {channel}, {server}, {args}, {uses} and \\n for new lines"]
+    content: String,
+    #[description = "Send the response as an embed"] embed: Option<bool>,
+    #[description = "Other names, separated by commas"] aliases: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let content = content.replace("\\n", "\n");
+    let embed = embed.unwrap_or(false);
+    let aliases = aliases.as_deref().map(parse_aliases).unwrap_or_default();
+
+    let mut names = vec![name.clone()];
+    names.extend(aliases.iter().filter(|a| **a != name).cloned());
+    let problem = match check_names(ctx, &names, None).await? {
+        Some(problem) => Some(problem),
+        None => check_content(&content, embed),
+    };
+    if let Some(problem) = problem {
+        send_err_msg(ctx, "Error", problem.as_str()).await;
+        return Ok(());
+    }
+
+    let mut command = CustomCommand {
+        id: 0,
+        guild_id: guild_id.get() as i64,
+        name,
+        content,
+        embed,
+        aliases: names.split_off(1),
+        uses: 0,
+        created_by: Some(ctx.author().id.get() as i64),
+        created_at: chrono::Utc::now(),
+    };
+    command.id = moonbot_db::add_custom_command(ctx.data().db, &command).await?;
+
+    ctx.send(poise::CreateReply::default().embed(describe(&command).title(format!("Added ~{}", command.name))))
+        .await?;
+    Ok(())
+}
+
+/// Change a custom command's response, embed setting or aliases
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[description = "Command name"]
+    #[autocomplete = "autocomplete_command"]
+    name: String,
+    #[description = "New response, with {user}, {user.name}, {channel}, {server}, {args}, {uses} and \\n"]
+    content: Option<String>,
+    #[description = "Send the response as an embed"] embed: Option<bool>,
+    #[description = "Other names, separated by commas, \"-\" for none"] aliases: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let Some(mut command) = moonbot_db::find_custom_command(ctx.data().db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no custom command called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+    if content.is_none() && embed.is_none() && aliases.is_none() {
+        send_err_msg(ctx, "Error", "Give at least one thing to change.").await;
+        return Ok(());
+    }
+
+    if let Some(content) = content {
+        command.content = content.replace("\\n", "\n");
+    }
+    if let Some(embed) = embed {
+        command.embed = embed;
+    }
+    if let Some(aliases) = aliases {
+        command.aliases = parse_aliases(&aliases);
+        command.aliases.retain(|a| a != "-" && *a != command.name);
+        if let Some(problem) = check_names(ctx, &command.aliases, Some(command.id)).await? {
+            send_err_msg(ctx, "Error", problem.as_str()).await;
+            return Ok(());
+        }
+    }
+    if let Some(problem) = check_content(&command.content, command.embed) {
+        send_err_msg(ctx, "Error", problem.as_str()).await;
+        return Ok(());
+    }
+    moonbot_db::update_custom_command(ctx.data().db, &command).await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(describe(&command).title(format!("Edited ~{}", command.name)))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Delete a custom command
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Command name"]
+    #[autocomplete = "autocomplete_command"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let db = ctx.data().db;
+    let Some(command) = moonbot_db::find_custom_command(db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no custom command called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+    moonbot_db::delete_custom_command(db, command.id).await?;
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Custom command deleted")
+        .color(0x2ECC71)
+        .description(format!("`~{}`", command.name));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// List this server's custom commands
+#[poise::command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let commands = moonbot_db::custom_commands(ctx.data().db, guild_id.get() as i64).await?;
+
+    let mut description = String::new();
+    for (idx, command) in commands.iter().enumerate() {
+        let aliases = if command.aliases.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", command.aliases.join(", "))
+        };
+        let line = format!("`~{}`{} | {} uses\n", command.name, aliases, command.uses);
+        if description.len() + line.len() > 4000 {
+            description.push_str(&format!("...and {} more", commands.len() - idx));
+            break;
+        }
+        description.push_str(&line);
+    }
+    if description.is_empty() {
+        description = "No custom commands yet, admins add them with /cc add.".to_string();
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Custom Commands")
+        .color(0x2ECC71)
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show a custom command's template and usage
+#[poise::command(slash_command, guild_only)]
+pub async fn info(
+    ctx: Context<'_>,
+    #[description = "Command name"]
+    #[autocomplete = "autocomplete_command"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let Some(command) = moonbot_db::find_custom_command(ctx.data().db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no custom command called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+    ctx.send(poise::CreateReply::default().embed(describe(&command)).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Run a custom command
+#[poise::command(slash_command, guild_only)]
+pub async fn run(
+    ctx: Context<'_>,
+    #[description = "Command name"]
+    #[autocomplete = "autocomplete_command"]
+    name: String,
+    #[description = "Arguments, for {args}"] args: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let db = ctx.data().db;
+    let Some(command) = moonbot_db::find_custom_command(db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no custom command called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+
+    let server = guild_id.name(ctx.cache()).unwrap_or_default();
+    let text = custom_commands::render(
+        &command,
+        ctx.author(),
+        ctx.channel_id(),
+        &server,
+        args.as_deref().unwrap_or_default(),
+    );
+    ctx.send(custom_commands::build_reply(&command, text)).await?;
+    moonbot_db::record_custom_command_use(db, command.id).await?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::{handlers::responders::dad, utils::send_err_msg, Context, Error};
+use poise::serenity_prelude as serenity;
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum DadAction {
+    Show,
+    Enable,
+    Disable,
+    Set,
+    Reset,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum NicknameMode {
+    Off,
+    Temporary,
+    Permanent,
+}
+
+impl NicknameMode {
+    fn key(&self) -> &'static str {
+        match self {
+            NicknameMode::Off => "off",
+            NicknameMode::Temporary => "temporary",
+            NicknameMode::Permanent => "permanent",
+        }
+    }
+}
+
+/// Configure "Hi X, I'm Moonbot" jokes for the server
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", guild_only)]
+pub async fn dad(
+    ctx: Context<'_>,
+    #[description = "Action"] action: DadAction,
+    #[description = "Chance to answer in percent (for set)"]
+    #[min = 0.0]
+    #[max = 100.0]
+    chance: Option<f64>,
+    #[description = "Seconds between jokes in the server (for set)"] cooldown: Option<u32>,
+    #[description = "Rename the author to the joke's name (for set)"] nickname_mode: Option<NicknameMode>,
+    #[description = "How long a temporary nickname lasts, in minutes (for set)"]
+    #[min = 1]
+    nickname_minutes: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let mut overrides = moonbot_db::get_dad_settings(db, guild_id.get() as i64).await;
+
+    match action {
+        DadAction::Show => {}
+        DadAction::Enable | DadAction::Disable => {
+            overrides.enabled = Some(matches!(action, DadAction::Enable));
+            moonbot_db::set_dad_settings(db, guild_id.get() as i64, overrides).await?;
+        }
+        DadAction::Set => {
+            if chance.is_none() && cooldown.is_none() && nickname_mode.is_none() && nickname_minutes.is_none() {
+                send_err_msg(ctx, "Error", "Give at least one setting to change.").await;
+                return Ok(());
+            }
+            if let Some(v) = chance {
+                overrides.chance = Some(v / 100.0);
+            }
+            if let Some(v) = cooldown {
+                overrides.cooldown_secs = Some(v as i64);
+            }
+            if let Some(v) = &nickname_mode {
+                overrides.nickname_mode = Some(v.key().to_string());
+            }
+            if let Some(v) = nickname_minutes {
+                overrides.nickname_minutes = Some(v as i64);
+            }
+            moonbot_db::set_dad_settings(db, guild_id.get() as i64, overrides).await?;
+        }
+        DadAction::Reset => {
+            moonbot_db::set_dad_settings(db, guild_id.get() as i64, Default::default()).await?;
+        }
+    }
+
+    let settings = dad::settings(Some(guild_id)).await;
+    let nickname = match settings.nickname_mode.as_str() {
+        "temporary" => format!("Temporary ({} min)", settings.nickname_minutes),
+        "permanent" => "Permanent".to_string(),
+        _ => "Off".to_string(),
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title("Dad Jokes")
+        .color(0x2ECC71)
+        .field("Status", if settings.enabled { "Enabled" } else { "Disabled" }, true)
+        .field("Chance", format!("{:.1}%", settings.chance * 100.0), true)
+        .field(
+            "Cooldown",
+            humantime::format_duration(std::time::Duration::from_secs(settings.cooldown)).to_string(),
+            true,
+        )
+        .field("Nickname", nickname, true);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Stop (or start again) getting dad jokes and nicknames
+#[poise::command(slash_command, rename = "dad-optout")]
+pub async fn dad_optout(
+    ctx: Context<'_>,
+    #[description = "Whether to opt out, true by default"] opt_out: Option<bool>,
+) -> Result<(), Error> {
+    let opt_out = opt_out.unwrap_or(true);
+    moonbot_db::set_dad_opt_out(ctx.data().db, ctx.author().id.get() as i64, opt_out).await?;
+
+    let text = if opt_out {
+        "I won't answer your \"I'm ...\" messages anymore."
+    } else {
+        "I'll answer your \"I'm ...\" messages again."
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title("Dad Jokes")
+        .color(0x2ECC71)
+        .description(text);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use crate::{
+    infractions::{self, Applied, Kind, MAX_TIMEOUT},
+    utils::send_err_msg,
+    Context, Error,
+};
+use poise::serenity_prelude as serenity;
+
+/// Whether the author's highest role is above the target's. The owner outranks everyone.
+async fn outranks(ctx: Context<'_>, target: serenity::UserId) -> bool {
+    let guild_id = ctx.guild_id().unwrap();
+    let Ok(target) = guild_id.member(ctx, target).await else {
+        // Not a member, e.g. banning someone who already left
+        return true;
+    };
+    let Some(author) = ctx.author_member().await else {
+        return false;
+    };
+    let Some(guild) = ctx.guild() else {
+        return false;
+    };
+    if guild.owner_id == author.user.id {
+        return true;
+    }
+    let top = |member: &serenity::Member| guild.member_highest_role(member).map_or(0, |r| r.position);
+    guild.owner_id != target.user.id && top(&target) < top(&author)
+}
+
+/// Apply a punishment and report what happened, including escalations.
+async fn punish(ctx: Context<'_>, user: &serenity::User, kind: Kind, reason: Option<String>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if user.id == ctx.author().id || user.bot {
+        send_err_msg(ctx, "Error", "You can't do that to that user.").await;
+        return Ok(());
+    }
+    if !outranks(ctx, user.id).await {
+        send_err_msg(ctx, "Error", "Their highest role isn't below yours.").await;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+
+    let punished = match infractions::punish(
+        ctx.serenity_context(),
+        guild_id,
+        user.id,
+        Some(ctx.author().id),
+        kind,
+        reason.as_deref(),
+    )
+    .await
+    {
+        Ok(punished) => punished,
+        Err(e) => {
+            send_err_msg(ctx, "Error", format!("Couldn't {} {}: {}", kind.name(), user.name, e).as_str()).await;
+            return Ok(());
+        }
+    };
+
+    let mut embed = serenity::CreateEmbed::new()
+        .title("Infraction recorded")
+        .color(0x2ECC71)
+        .description(format!(
+            "<@{}>\n{}",
+            user.id,
+            punished.applied.iter().map(Applied::describe).collect::<Vec<_>>().join("\n")
+        ))
+        .field("Reason", reason.unwrap_or_else(|| "None given".to_string()), false);
+    if let Some(e) = punished.escalation_error {
+        embed = embed.field("Escalation failed", e.to_string(), false);
+    }
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Warn a member
+#[poise::command(slash_command, default_member_permissions = "MODERATE_MEMBERS", guild_only)]
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "Member to warn"] user: serenity::User,
+    #[description = "Why"] reason: Option<String>,
+) -> Result<(), Error> {
+    punish(ctx, &user, Kind::Warn, reason).await
+}
+
+/// Time out a member
+#[poise::command(slash_command, default_member_permissions = "MODERATE_MEMBERS", guild_only)]
+pub async fn timeout(
+    ctx: Context<'_>,
+    #[description = "Member to time out"] user: serenity::User,
+    #[description = "How long, e.g. \"10m\", \"1h\" or \"2d\" (at most 28 days)"] duration: String,
+    #[description = "Why"] reason: Option<String>,
+) -> Result<(), Error> {
+    let duration = match humantime::parse_duration(duration.trim()) {
+        Ok(d) if !d.is_zero() && d <= MAX_TIMEOUT => d,
+        Ok(_) => {
+            send_err_msg(ctx, "Error", "Timeouts last between a second and 28 days.").await;
+            return Ok(());
+        }
+        Err(_) => {
+            send_err_msg(ctx, "Error", format!("I don't understand `{}`, try `10m` or `1h`.", duration).as_str()).await;
+            return Ok(());
+        }
+    };
+    punish(ctx, &user, Kind::Timeout(duration), reason).await
+}
+
+/// Kick a member
+#[poise::command(slash_command, default_member_permissions = "KICK_MEMBERS", guild_only)]
+pub async fn kick(
+    ctx: Context<'_>,
+    #[description = "Member to kick"] user: serenity::User,
+    #[description = "Why"] reason: Option<String>,
+) -> Result<(), Error> {
+    punish(ctx, &user, Kind::Kick, reason).await
+}
+
+/// Ban a user
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS", guild_only)]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "User to ban"] user: serenity::User,
+    #[description = "Why"] reason: Option<String>,
+    #[description = "Delete their messages from the last days (0-7)"]
+    #[min = 0]
+    #[max = 7]
+    delete_days: Option<u8>,
+) -> Result<(), Error> {
+    punish(ctx, &user, Kind::Ban(delete_days.unwrap_or(0)), reason).await
+}
+
+/// Show a member's infractions
+#[poise::command(slash_command, default_member_permissions = "MODERATE_MEMBERS", guild_only)]
+pub async fn infractions(
+    ctx: Context<'_>,
+    #[description = "Member"] user: serenity::User,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let list = moonbot_db::user_infractions(ctx.data().db, guild_id.get() as i64, user.id.get() as i64).await?;
+
+    let active = list.iter().filter(|i| i.active).count();
+    let mut description = list
+        .iter()
+        .take(20)
+        .map(|i| {
+            let by = i.moderator_id.map(|m| format!("<@{}>", m)).unwrap_or_else(|| "escalation".to_string());
+            let duration = i
+                .duration_secs
+                .map(|s| format!(" {}", humantime::format_duration(std::time::Duration::from_secs(s as u64))))
+                .unwrap_or_default();
+            let line = format!(
+                "**#{}** {}{} <t:{}:R> by {}{}",
+                i.id,
+                i.kind,
+                duration,
+                i.created_at.timestamp(),
+                by,
+                i.reason.as_deref().map(|r| format!(": {}", r)).unwrap_or_default()
+            );
+            if i.active { line } else { format!("~~{}~~ (pardoned)", line) }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if description.is_empty() {
+        description = "No infractions.".to_string();
+    }
+    let embed = serenity::CreateEmbed::new()
+        .title(format!("Infractions of {}", user.name))
+        .color(0x2ECC71)
+        .description(description)
+        .footer(serenity::CreateEmbedFooter::new(format!("{} active, {} total", active, list.len())));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Pardon an infraction, lifting a running timeout or ban
+#[poise::command(slash_command, default_member_permissions = "MODERATE_MEMBERS", guild_only)]
+pub async fn pardon(
+    ctx: Context<'_>,
+    #[description = "Infraction number, see /infractions"] id: i64,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let infraction = moonbot_db::get_infraction(ctx.data().db, id).await?;
+    let Some(infraction) = infraction.filter(|i| i.guild_id == guild_id.get() as i64) else {
+        send_err_msg(ctx, "Error", format!("There's no infraction #{} here.", id).as_str()).await;
+        return Ok(());
+    };
+    let (needed, permission) = match infraction.kind.as_str() {
+        "ban" => (serenity::Permissions::BAN_MEMBERS, "Ban Members"),
+        _ => (serenity::Permissions::MODERATE_MEMBERS, "Timeout Members"),
+    };
+    let allowed = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.contains(needed));
+    if !allowed {
+        send_err_msg(ctx, "Error", format!("You need the {} permission to pardon that.", permission).as_str()).await;
+        return Ok(());
+    }
+    match infractions::pardon(ctx.serenity_context(), &infraction, ctx.author().id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            send_err_msg(ctx, "Error", format!("#{} was already pardoned.", id).as_str()).await;
+            return Ok(());
+        }
+        Err(e) => {
+            let text = format!("Couldn't lift the {} of #{}: {}", infraction.kind, id, e);
+            send_err_msg(ctx, "Error", text.as_str()).await;
+            return Ok(());
+        }
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Infraction pardoned")
+        .color(0x2ECC71)
+        .description(format!("**#{}** {} of <@{}>", infraction.id, infraction.kind, infraction.user_id));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use crate::{
+    handlers::moderation::{threshold, CATEGORIES},
+    utils::send_err_msg,
+    Context, Error,
+};
+use poise::serenity_prelude as serenity;
+
+async fn autocomplete_category(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    std::iter::once("default")
+        .chain(CATEGORIES.iter().copied())
+        .filter(|c| c.contains(&partial.to_lowercase()))
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ModerationAction {
+    Show,
+    Enable,
+    Disable,
+    Threshold,
+}
+
+/// Flag harmful messages to a mod-log channel
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn moderation(
+    ctx: Context<'_>,
+    #[description = "Action"] action: ModerationAction,
+    #[description = "Mod-log channel for flagged messages (for enable)"]
+    #[channel_types("Text")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Category, or \"default\" for all others (for threshold)"]
+    #[autocomplete = "autocomplete_category"]
+    category: Option<String>,
+    #[description = "Score in percent needed to flag, empty to reset (for threshold)"]
+    #[min = 1.0]
+    #[max = 100.0]
+    threshold_percent: Option<f64>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let mut settings = moonbot_db::get_moderation_settings(db, guild_id.get() as i64).await;
+
+    match action {
+        ModerationAction::Show => {}
+        ModerationAction::Enable => {
+            if let Some(channel) = &channel {
+                settings.log_channel_id = Some(channel.id.get() as i64);
+            }
+            if settings.log_channel_id.is_none() {
+                send_err_msg(ctx, "Error", "Give a channel for the mod-log.").await;
+                return Ok(());
+            }
+            if !crate::handlers::moderation::is_available() {
+                send_err_msg(ctx, "Error", "No OpenAI key or local classifier is configured.").await;
+                return Ok(());
+            }
+            settings.enabled = true;
+            moonbot_db::set_moderation_settings(db, guild_id.get() as i64, &settings).await?;
+        }
+        ModerationAction::Disable => {
+            settings.enabled = false;
+            moonbot_db::set_moderation_settings(db, guild_id.get() as i64, &settings).await?;
+        }
+        ModerationAction::Threshold => {
+            let category = category.unwrap_or_else(|| "default".to_string()).trim().to_lowercase();
+            if category != "default" && !CATEGORIES.contains(&category.as_str()) {
+                send_err_msg(ctx, "Error", format!("`{}` isn't a moderation category.", category).as_str()).await;
+                return Ok(());
+            }
+            match threshold_percent {
+                Some(v) => settings.thresholds.insert(category, v / 100.0),
+                None => settings.thresholds.remove(&category),
+            };
+            moonbot_db::set_moderation_settings(db, guild_id.get() as i64, &settings).await?;
+        }
+    }
+
+    let thresholds = CATEGORIES
+        .iter()
+        .map(|c| {
+            let marker = if settings.thresholds.contains_key(*c) { "" } else { " (default)" };
+            format!("{} **{:.0}%**{}", c, threshold(&settings, c) * 100.0, marker)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = serenity::CreateEmbed::new()
+        .title("Moderation")
+        .color(0x2ECC71)
+        .field("Status", if settings.enabled { "Enabled" } else { "Disabled" }, true)
+        .field(
+            "Mod-log",
+            settings
+                .log_channel_id
+                .map(|c| format!("<#{}>", c))
+                .unwrap_or_else(|| "Not set".to_string()),
+            true,
+        )
+        .field("Thresholds", thresholds, false);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Show recent moderation actions
+#[poise::command(
+    slash_command,
+    rename = "moderation-log",
+    default_member_permissions = "MODERATE_MEMBERS",
+    guild_only
+)]
+pub async fn moderation_log(
+    ctx: Context<'_>,
+    #[description = "Only actions against this user"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let actions = moonbot_db::moderation_actions(
+        ctx.data().db,
+        guild_id.get() as i64,
+        user.as_ref().map(|u| u.id.get() as i64),
+        15,
+    )
+    .await?;
+
+    let mut description = actions
+        .iter()
+        .map(|a| {
+            let by = a.moderator_id.map(|m| format!("<@{}>", m)).unwrap_or_else(|| "the bot".to_string());
+            let reason = a.reason.as_deref().map(|r| format!(": {}", r)).unwrap_or_default();
+            format!(
+                "**#{}** <t:{}:R> {} <@{}> by {}{}",
+                a.id,
+                a.created_at.timestamp(),
+                a.action,
+                a.target_user_id,
+                by,
+                reason
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if description.is_empty() {
+        description = "No actions yet.".to_string();
+    }
+    let embed = serenity::CreateEmbed::new()
+        .title("Moderation Log")
+        .color(0x2ECC71)
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
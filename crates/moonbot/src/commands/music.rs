@@ -1,27 +1,82 @@
-use crate::{utils::send_err_msg, Context, Error};
-use futures::{future, StreamExt};
+use crate::{
+    music_backend::{MusicBackend, NowPlaying, Resolved},
+    queue_policy::{describe_rejections, QueuePolicy},
+    utils::{send_err_msg, voice_listeners},
+    Context, Error,
+};
 use humantime::format_duration;
-use lavalink_rs::prelude::*;
+use moonbot_db::GuildMusicSettings;
+use once_cell::sync::OnceCell;
 use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-async fn _join(
+// Skip votes per guild, tied to the encoded track they were cast for
+type SkipVotes = HashMap<serenity::GuildId, (String, HashSet<serenity::UserId>)>;
+static SKIP_VOTES: OnceCell<Mutex<SkipVotes>> = OnceCell::new();
+
+fn skip_votes() -> &'static Mutex<SkipVotes> {
+    SKIP_VOTES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Whether the author may control playback: server managers, the DJ role if the guild
+/// has one, and anyone listening alone. Everyone else votes to skip.
+async fn is_dj(ctx: Context<'_>, settings: &GuildMusicSettings) -> bool {
+    if let Some(member) = ctx.author_member().await {
+        if member.permissions.is_some_and(|p| p.administrator() || p.manage_guild()) {
+            return true;
+        }
+        if let Some(role_id) = settings.dj_role_id {
+            if member.roles.contains(&serenity::RoleId::new(role_id as u64)) {
+                return true;
+            }
+        }
+    }
+    let listeners = voice_listeners(ctx.cache(), ctx.guild_id().unwrap()).unwrap_or_default();
+    listeners == [ctx.author().id]
+}
+
+/// Refuse with an explanation unless the author is a DJ.
+async fn require_dj(ctx: Context<'_>, action: &str) -> Result<bool, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+    if is_dj(ctx, &settings).await {
+        return Ok(true);
+    }
+    let needed = match settings.dj_role_id {
+        Some(role_id) => format!("the <@&{}> role", role_id),
+        None => "the Manage Server permission".to_string(),
+    };
+    send_err_msg(
+        ctx,
+        "Not allowed",
+        format!("You need {} to {}, or be the only one listening.", needed, action).as_str(),
+    )
+    .await;
+    Ok(false)
+}
+
+/// The music backend, or an explanation to the user when there is none.
+pub(crate) async fn music_backend(ctx: Context<'_>) -> Option<Arc<dyn MusicBackend>> {
+    let backend = ctx.data().music.clone();
+    if backend.is_none() {
+        send_err_msg(ctx, "Error", "Music playback is not available.").await;
+    }
+    backend
+}
+
+pub(crate) async fn _join(
     ctx: &Context<'_>,
     guild_id: serenity::GuildId,
     channel_id: Option<serenity::ChannelId>,
 ) -> Result<bool, Error> {
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(*ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(false);
-        }
+    let Some(backend) = music_backend(*ctx).await else {
+        return Ok(false);
     };
 
-    let manager = songbird::get(ctx.serenity_context()).await.unwrap().clone();
-
-    if lava_client.get_player_context(guild_id).is_none() {
+    if !backend.is_connected(guild_id) {
         let connect_to = match channel_id {
             Some(x) => x,
             None => {
@@ -46,29 +101,11 @@ async fn _join(
             }
         };
 
-        let handler = manager.join_gateway(guild_id, connect_to).await;
-
-        match handler {
-            Ok((connection_info, _)) => {
-                lava_client
-                    // The turbofish here is Optional, but it helps to figure out what type to
-                    // provide in `PlayerContext::data()`
-                    //
-                    // While a tuple is used here as an example, you are free to use a custom
-                    // public structure with whatever data you wish.
-                    // This custom data is also present in the Client if you wish to have the
-                    // shared data be more global, rather than centralized to each player.
-                    .create_player_context_with_data::<(serenity::ChannelId, std::sync::Arc<serenity::Http>)>(
-                        guild_id,
-                        connection_info,
-                        std::sync::Arc::new((
-                            ctx.channel_id(),
-                            ctx.serenity_context().http.clone(),
-                        )),
-                    )
-                    .await?;
-                return Ok(true);
-            }
+        match backend
+            .join(ctx.serenity_context(), guild_id, connect_to, ctx.channel_id())
+            .await
+        {
+            Ok(()) => return Ok(true),
             Err(why) => {
                 send_err_msg(
                     *ctx,
@@ -76,7 +113,7 @@ async fn _join(
                     format!("Error joining the channel: {}", why).as_str(),
                 )
                 .await;
-                return Err(why.into());
+                return Err(why);
             }
         }
     }
@@ -92,66 +129,75 @@ pub async fn play(
     #[rest]
     term: String,
 ) -> Result<(), Error> {
+    _play(ctx, term).await
+}
+
+async fn _play(ctx: Context<'_>, term: String) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
     _join(&ctx, guild_id, None).await?;
-    let Some(player) = lava_client.get_player_context(guild_id) else {
+    if !backend.is_connected(guild_id) {
         return Ok(());
-    };
-
-    let query = if term.starts_with("http") {
-        term
-    } else {
-        SearchEngines::YouTube.to_query(&term)?
-    };
+    }
 
-    let loaded_tracks = lava_client.load_tracks(guild_id, &query).await?;
-    let mut playlist_info = None;
-    let mut tracks: Vec<TrackInQueue> = match loaded_tracks.data {
-        Some(TrackLoadData::Track(x)) => vec![x.into()],
-        Some(TrackLoadData::Search(x)) => vec![x[0].clone().into()],
-        Some(TrackLoadData::Playlist(x)) => {
-            playlist_info = Some(x.info);
-            x.tracks.iter().map(|x| x.clone().into()).collect()
-        }
-        Some(TrackLoadData::Error(x)) => {
-            send_err_msg(ctx, "Error", x.message.as_str()).await;
-            return Ok(());
-        }
-        _ => {
-            ctx.say(format!("{:?}", loaded_tracks)).await?;
+    let Resolved {
+        mut tracks,
+        playlist,
+    } = match backend.resolve(guild_id, &term).await {
+        Ok(x) => x,
+        Err(e) => {
+            send_err_msg(ctx, "Error", e.to_string().as_str()).await;
             return Ok(());
         }
     };
+    if tracks.is_empty() {
+        send_err_msg(ctx, "Error", "Nothing found.").await;
+        return Ok(());
+    }
 
-    let queue = player.get_queue();
     let mut duration = 0;
-    let position = queue.get_count().await.unwrap_or(0) + 1;
+    let queued = backend.queue(guild_id).await.unwrap_or_default();
+    let position = queued.len() + 1;
 
     for i in &mut tracks {
-        i.track.user_data = Some(serde_json::json!({"requester_id": ctx.author().id.get()}));
-        duration += i.track.info.length;
+        i.user_data = Some(serde_json::json!({"requester_id": ctx.author().id.get()}));
     }
 
-    let track = tracks.remove(0).track;
-    // If there is no track playing, just play the first track
-    if player.get_player().await.unwrap().track.is_none() {
-        player.play(&track).await?;
+    // Enforce the guild's queue limits and blocklist
+    let policy = QueuePolicy::load(ctx.data().db, guild_id).await;
+    let current = backend.now_playing(guild_id).await?.map(|np| np.track);
+    let (tracks, rejected) = policy.apply(
+        tracks,
+        current.as_ref(),
+        &queued,
+        ctx.author().id,
+        playlist.is_some(),
+    );
+    if tracks.is_empty() {
+        send_err_msg(
+            ctx,
+            "Nothing added to queue",
+            describe_rejections(&rejected, 4000).as_str(),
+        )
+        .await;
+        return Ok(());
     }
 
-    // Add the rest of the tracks to the queue
-    queue.append(tracks.clone().into())?;
+    crate::handlers::autoplay::reset(guild_id);
+    for i in &tracks {
+        duration += i.info.length;
+    }
+
+    let track = tracks[0].clone();
+    let count = tracks.len();
+    backend.enqueue(guild_id, tracks).await?;
 
     // If the queue is empty, reply with a hidden message to the user
-    if queue.get_count().await.unwrap_or(0) == 0 {
+    if backend.queue(guild_id).await.unwrap_or_default().is_empty() && rejected.is_empty() {
         let resp = ctx
             .send(
                 poise::CreateReply::default()
@@ -165,17 +211,17 @@ pub async fn play(
 
     let mut embed = serenity::CreateEmbed::default().color(0x2ECC71);
 
-    embed = if let Some(info) = playlist_info {
+    embed = if let Some(name) = playlist {
         embed
             .author(
                 serenity::CreateEmbedAuthor::new("Playlist added to queue")
                     .icon_url(ctx.author().avatar_url().unwrap_or_default()),
             )
-            .description(format!("Added playlist {}", info.name))
-            .field("Tracks", tracks.len().to_string(), false)
+            .description(format!("Added playlist {}", name))
+            .field("Tracks", count.to_string(), false)
             .field(
                 "Position",
-                format!("#{}-{}", position, 1 + tracks.len()),
+                format!("#{}-{}", position, position + count - 1),
                 true,
             )
             .field(
@@ -202,6 +248,13 @@ pub async fn play(
                 true,
             )
     };
+    if !rejected.is_empty() {
+        embed = embed.field(
+            format!("Rejected ({})", rejected.len()),
+            describe_rejections(&rejected, 1000),
+            false,
+        );
+    }
 
     ctx.send(poise::CreateReply::default().embed(embed)).await?;
 
@@ -233,22 +286,22 @@ pub async fn join(
 #[poise::command(slash_command, rename = "music-leave")]
 pub async fn leave(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !require_dj(ctx, "make me leave").await? {
+        return Ok(());
+    }
     let manager = songbird::get(ctx.serenity_context()).await.unwrap().clone();
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
-    if lava_client.get_player_context(guild_id).is_none() {
+    if !backend.is_connected(guild_id) {
         send_err_msg(ctx, "Error", "Im not playing anything! :rage:").await;
         return Ok(());
     }
 
-    let _ = lava_client.delete_player(guild_id).await;
+    crate::handlers::inactivity::forget(guild_id);
+    backend.leave(guild_id).await?;
 
     if manager.get(guild_id).is_some() {
         manager.remove(guild_id).await?;
@@ -269,20 +322,19 @@ pub async fn leave(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, rename = "music-pause")]
 pub async fn pause(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !require_dj(ctx, "pause the music").await? {
+        return Ok(());
+    }
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
-    let Some(player) = lava_client.get_player_context(guild_id) else {
+    if !backend.is_connected(guild_id) {
         send_err_msg(ctx, "Error", "Join the bot to a voice channel first.").await;
         return Ok(());
-    };
-    player.set_pause(true).await?;
+    }
+    backend.set_pause(guild_id, true).await?;
 
     let embed = serenity::CreateEmbed::new()
         .author(
@@ -299,21 +351,20 @@ pub async fn pause(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, rename = "music-resume")]
 pub async fn resume(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
+    if !require_dj(ctx, "resume the music").await? {
+        return Ok(());
+    }
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
-    let Some(player) = lava_client.get_player_context(guild_id) else {
+    if !backend.is_connected(guild_id) {
         send_err_msg(ctx, "Error", "Join the bot to a voice channel first.").await;
         return Ok(());
-    };
+    }
 
-    player.set_pause(false).await?;
+    backend.set_pause(guild_id, false).await?;
 
     let embed = serenity::CreateEmbed::new()
         .author(
@@ -331,25 +382,84 @@ pub async fn resume(ctx: Context<'_>) -> Result<(), Error> {
 pub async fn skip(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
-    let Some(player) = lava_client.get_player_context(guild_id) else {
+    if !backend.is_connected(guild_id) {
         send_err_msg(ctx, "Error", "Join the bot to a voice channel first.").await;
         return Ok(());
-    };
+    }
+
+    let current = backend.now_playing(guild_id).await?.map(|np| np.track);
+    let settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+    if let Some(track) = current.as_ref() {
+        let requester = track
+            .user_data
+            .as_ref()
+            .and_then(|data| data.get("requester_id"))
+            .and_then(|id| id.as_u64());
+        let own_track = requester == Some(ctx.author().id.get());
+
+        if !own_track && !is_dj(ctx, &settings).await {
+            let listeners = voice_listeners(ctx.cache(), guild_id).unwrap_or_default();
+            if !listeners.contains(&ctx.author().id) {
+                send_err_msg(
+                    ctx,
+                    "Not allowed",
+                    "You need to be listening in the voice channel to vote to skip.",
+                )
+                .await;
+                return Ok(());
+            }
+
+            let ratio = settings
+                .vote_skip_ratio
+                .unwrap_or(ctx.data().config.music.vote_skip_ratio)
+                .clamp(0.0, 1.0);
+            let required = ((listeners.len() as f64 * ratio).ceil() as usize).max(1);
+            let votes = {
+                let mut all = skip_votes().lock().unwrap();
+                let entry = all
+                    .entry(guild_id)
+                    .or_insert_with(|| (track.encoded.clone(), HashSet::new()));
+                if entry.0 != track.encoded {
+                    *entry = (track.encoded.clone(), HashSet::new());
+                }
+                entry.1.insert(ctx.author().id);
+                // People who left the channel no longer count
+                entry.1.retain(|u| listeners.contains(u));
+                entry.1.len()
+            };
 
-    player.skip()?;
+            if votes < required {
+                let embed = serenity::CreateEmbed::new()
+                    .author(
+                        serenity::CreateEmbedAuthor::new("Voted to skip")
+                            .icon_url(ctx.author().avatar_url().unwrap_or_default()),
+                    )
+                    .color(0x2ECC71)
+                    .description(format!(
+                        "{}/{} votes to skip [{}](<{}>)",
+                        votes,
+                        required,
+                        track.info.title,
+                        track.info.uri.as_ref().unwrap_or(&String::new())
+                    ));
+                ctx.send(poise::CreateReply::default().embed(embed)).await?;
+                return Ok(());
+            }
+        }
+
+        skip_votes().lock().unwrap().remove(&guild_id);
+        let _ = moonbot_db::mark_last_play_skipped(ctx.data().db, guild_id.get() as i64).await;
+    } else if !require_dj(ctx, "skip").await? {
+        return Ok(());
+    }
+    backend.skip(guild_id).await?;
 
     // If queue is empty and nothing is playing, send a different message
-    if player.get_queue().get_count().await? == 0
-        && player.get_player().await.unwrap().track.is_none()
-    {
+    if backend.queue(guild_id).await?.is_empty() && backend.now_playing(guild_id).await?.is_none() {
         let embed = serenity::CreateEmbed::new()
             .author(
                 serenity::CreateEmbedAuthor::new("Skipped")
@@ -377,56 +487,56 @@ pub async fn skip(ctx: Context<'_>) -> Result<(), Error> {
 pub async fn queue(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
-    let lava_client = match &ctx.data().lavalink {
-        Some(x) => x,
-        None => {
-            send_err_msg(ctx, "Error", "Lavalink client is not available.").await;
-            return Ok(());
-        }
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
     };
 
-    let Some(player) = lava_client.get_player_context(guild_id) else {
+    if !backend.is_connected(guild_id) {
         send_err_msg(ctx, "Error", "Join the bot to a voice channel first.").await;
         return Ok(());
-    };
+    }
 
-    let queue = player.get_queue();
-    let queue_count = queue.get_count().await?;
-    let player_data = player.get_player().await?;
+    let queue = backend.queue(guild_id).await?;
+    let queue_count = queue.len();
     let max = queue_count.min(5);
     let mut queue_message = queue
+        .iter()
+        .take(max)
         .enumerate()
-        .take_while(|(idx, _)| future::ready(*idx < max))
         .map(|(idx, x)| {
             format!(
                 "**{} - **[{} - {}](<{}>)\n*Requested By <@!{}>* | {}\n",
                 idx + 1,
-                x.track.info.author,
-                x.track.info.title,
-                x.track.info.uri.as_ref().unwrap_or(&String::new()),
-                x.track.user_data.unwrap()["requester_id"],
-                format_duration(Duration::from_millis(x.track.info.length)),
+                x.info.author,
+                x.info.title,
+                x.info.uri.as_ref().unwrap_or(&String::new()),
+                x.user_data.as_ref().unwrap()["requester_id"],
+                format_duration(Duration::from_millis(x.info.length)),
             )
         })
         .collect::<Vec<_>>()
-        .await
         .join("\n");
 
     if queue_count > max {
         queue_message.push_str(&format!("\n\nAnd {} more...", queue_count - max));
     }
 
-    let song_position = player.get_player().await.unwrap().state.position;
-    let now_playing_message = if let Some(track) = player_data.track {
+    let now_playing_message = if let Some(NowPlaying {
+        track,
+        position,
+        paused,
+    }) = backend.now_playing(guild_id).await?
+    {
         format!(
-            "[{} - {}](<{}>)\n*Requested by <@!{}>*\n{} Left\n",
+            "[{} - {}](<{}>)\n*Requested by <@!{}>*\n{} Left{}\n",
             track.info.author,
             track.info.title,
             track.info.uri.as_ref().unwrap_or(&String::new()),
             track.user_data.unwrap()["requester_id"],
             format_duration(Duration::from_millis(
-                (track.info.length - song_position) / 1000 * 1000
-            ))
+                track.info.length.saturating_sub(position) / 1000 * 1000
+            )),
+            if paused { " (paused)" } else { "" }
         )
     } else {
         "No song is currently playing".to_string()
@@ -436,9 +546,671 @@ pub async fn queue(ctx: Context<'_>) -> Result<(), Error> {
         .title("Queue")
         .color(0x2ECC71)
         .field("Now Playing", now_playing_message, false)
-        .field("Queue", queue_message, false);
+        .field("Queue", queue_message, false)
+        .footer(serenity::CreateEmbedFooter::new(format!("Playing through {}", backend.name())));
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum DjAction {
+    Show,
+    Set,
+    Clear,
+}
+
+/// Configure the DJ role and vote-skip threshold
+///
+/// DJs control playback and skip right away: server managers, members with the DJ role and
+/// anyone listening alone. Without a DJ role only server managers are DJs. Everyone else
+/// can skip their own tracks, and votes to skip the rest until the vote fraction of
+/// listeners agrees.
+#[poise::command(
+    slash_command,
+    rename = "music-dj",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn dj(
+    ctx: Context<'_>,
+    #[description = "Action"] action: DjAction,
+    #[description = "Role allowed to control playback (for set)"] role: Option<serenity::Role>,
+    #[description = "Fraction of listeners needed to skip, 0.0-1.0 (for set)"] vote_fraction: Option<f64>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let mut settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+
+    match action {
+        DjAction::Show => {}
+        DjAction::Set => {
+            if role.is_none() && vote_fraction.is_none() {
+                send_err_msg(ctx, "Error", "Give a role and/or a vote fraction to set.").await;
+                return Ok(());
+            }
+            if let Some(role) = role {
+                settings.dj_role_id = Some(role.id.get() as i64);
+            }
+            if let Some(fraction) = vote_fraction {
+                settings.vote_skip_ratio = Some(fraction.clamp(0.0, 1.0));
+            }
+            moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings.clone()).await?;
+        }
+        DjAction::Clear => {
+            settings.dj_role_id = None;
+            settings.vote_skip_ratio = None;
+            moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings.clone()).await?;
+        }
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Music Permissions")
+        .color(0x2ECC71)
+        .field(
+            "DJ Role",
+            settings
+                .dj_role_id
+                .map(|id| format!("<@&{}>", id))
+                .unwrap_or_else(|| "None, only server managers are DJs".to_string()),
+            false,
+        )
+        .field(
+            "Vote Skip",
+            format!(
+                "{:.0}% of listeners",
+                settings
+                    .vote_skip_ratio
+                    .unwrap_or(ctx.data().config.music.vote_skip_ratio)
+                    * 100.0
+            ),
+            false,
+        );
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Toggle 24/7 mode, which keeps the bot in voice when idle or alone
+#[poise::command(
+    slash_command,
+    rename = "music-247",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn stay_connected(
+    ctx: Context<'_>,
+    #[description = "Stay connected when nothing is playing or nobody is listening"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let mut settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+    settings.stay_connected = enabled;
+    moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings).await?;
+
+    let embed = serenity::CreateEmbed::new()
+        .author(
+            serenity::CreateEmbedAuthor::new(if enabled { "24/7 mode enabled" } else { "24/7 mode disabled" })
+                .icon_url(ctx.author().avatar_url().unwrap_or_default()),
+        )
+        .color(0x2ECC71);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum AutoplayToggle {
+    On,
+    Off,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum AutoplayMode {
+    #[name = "Related tracks"]
+    Related,
+    #[name = "AI DJ"]
+    Dj,
+}
+
+/// Keep the music going with related tracks when the queue runs out
+#[poise::command(slash_command, rename = "music-autoplay", guild_only)]
+pub async fn autoplay(
+    ctx: Context<'_>,
+    #[description = "Turn autoplay on or off"] state: AutoplayToggle,
+    #[description = "Where to find the next tracks"] mode: Option<AutoplayMode>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !require_dj(ctx, "change autoplay").await? {
+        return Ok(());
+    }
+    let mut settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+    settings.autoplay = matches!(state, AutoplayToggle::On);
+    if let Some(mode) = mode {
+        settings.autoplay_mode = match mode {
+            AutoplayMode::Related => "related".into(),
+            AutoplayMode::Dj => "dj".into(),
+        };
+    }
+    if settings.autoplay && settings.autoplay_mode == "dj" && ctx.data().llm.is_none() {
+        send_err_msg(ctx, "Error", "AI DJ mode needs OpenAI to be configured.").await;
+        return Ok(());
+    }
+    crate::handlers::autoplay::reset(guild_id);
+    moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings.clone()).await?;
+
+    let mut embed = serenity::CreateEmbed::new()
+        .author(
+            serenity::CreateEmbedAuthor::new(if settings.autoplay { "Autoplay enabled" } else { "Autoplay disabled" })
+                .icon_url(ctx.author().avatar_url().unwrap_or_default()),
+        )
+        .color(0x2ECC71);
+    if settings.autoplay {
+        embed = embed.field(
+            "Mode",
+            if settings.autoplay_mode == "dj" { "AI DJ" } else { "Related tracks" },
+            true,
+        );
+    }
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Shows the lyrics of the current song
+#[poise::command(slash_command, rename = "music-lyrics", guild_only)]
+pub async fn lyrics(
+    ctx: Context<'_>,
+    #[description = "Follow along with the song as it plays, when timed lyrics exist"] synced: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
+    };
+
+    if !backend.is_connected(guild_id) {
+        send_err_msg(ctx, "Error", "Join the bot to a voice channel first.").await;
+        return Ok(());
+    }
+    let Some(NowPlaying { track, .. }) = backend.now_playing(guild_id).await? else {
+        send_err_msg(ctx, "Error", "No song is currently playing").await;
+        return Ok(());
+    };
+
+    ctx.defer().await?;
+    let Some(lyrics) = crate::lyrics::fetch(backend.as_ref(), guild_id, &track).await else {
+        send_err_msg(
+            ctx,
+            "No lyrics found",
+            format!("Couldn't find lyrics for {} - {}", track.info.author, track.info.title).as_str(),
+        )
+        .await;
+        return Ok(());
+    };
+
+    let header = format!("**{} - {}**\n*Lyrics from {}*\n\n", track.info.author, track.info.title, lyrics.source);
+
+    if synced.unwrap_or(false) {
+        if let Some(lines) = lyrics.lines.as_ref() {
+            return live_lyrics(ctx, backend.as_ref(), guild_id, &track, &header, lines).await;
+        }
+    }
+
+    let pages = crate::lyrics::paginate(&lyrics.text, 1800)
+        .into_iter()
+        .map(|page| format!("{}{}", header, page))
+        .collect::<Vec<_>>();
+    if pages.len() == 1 {
+        let embed = serenity::CreateEmbed::new().color(0x2ECC71).description(&pages[0]);
+        ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    } else {
+        let pages = pages.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+        poise::builtins::paginate(ctx, &pages).await?;
+    }
+
+    Ok(())
+}
+
+// Keep editing one message so it shows the line being sung, until the song changes
+async fn live_lyrics(
+    ctx: Context<'_>,
+    backend: &dyn MusicBackend,
+    guild_id: serenity::GuildId,
+    track: &lavalink_rs::model::track::TrackData,
+    header: &str,
+    lines: &[crate::lyrics::LyricsLine],
+) -> Result<(), Error> {
+    let render = |position: u64| {
+        let current = crate::lyrics::current_line(lines, position);
+        let start = current.unwrap_or(0).saturating_sub(2);
+        let body = lines
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(6)
+            .map(|(idx, l)| {
+                let text = if l.line.is_empty() { "♪" } else { l.line.as_str() };
+                if Some(idx) == current {
+                    format!("**» {}**", text)
+                } else {
+                    text.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        serenity::CreateEmbed::new()
+            .color(0x2ECC71)
+            .description(format!("{}{}", header, body))
+    };
+
+    let position = backend.now_playing(guild_id).await?.map(|np| np.position).unwrap_or(0);
+    let reply = ctx.send(poise::CreateReply::default().embed(render(position))).await?;
+
+    let interval = Duration::from_secs(ctx.data().config.music.lyrics.sync_interval.max(1));
+    // Give up eventually if the song sits paused
+    let deadline = tokio::time::Instant::now()
+        + Duration::from_millis(track.info.length.saturating_sub(position))
+        + Duration::from_secs(300);
+    let mut last_line = None;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+        let position = match backend.now_playing(guild_id).await {
+            Ok(Some(now))
+                if now.track.encoded == track.encoded && now.track.info.identifier == track.info.identifier =>
+            {
+                now.position
+            }
+            _ => break,
+        };
+        // Only touch the message when the highlighted line moved
+        let line = crate::lyrics::current_line(lines, position);
+        if line == last_line {
+            continue;
+        }
+        last_line = line;
+        if reply
+            .edit(ctx, poise::CreateReply::default().embed(render(position)))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows what this server has been listening to
+#[poise::command(slash_command, rename = "music-stats", guild_only)]
+pub async fn music_stats(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let stats = moonbot_db::music_stats(ctx.data().db, guild_id.get() as i64, 5).await?;
+    if stats.total_plays == 0 {
+        send_err_msg(ctx, "Music Stats", "Nothing has been played in this server yet.").await;
+        return Ok(());
+    }
+
+    let top_tracks = stats
+        .top_tracks
+        .iter()
+        .enumerate()
+        .map(|(idx, t)| {
+            format!(
+                "**{} - **[{} - {}](<{}>) | {} plays",
+                idx + 1,
+                t.author,
+                t.title,
+                t.uri.as_deref().unwrap_or_default(),
+                t.plays
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let top_requesters = stats
+        .top_requesters
+        .iter()
+        .enumerate()
+        .map(|(idx, (user_id, plays))| format!("**{} - **<@!{}> | {} plays", idx + 1, user_id, plays))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let busiest_hours = stats
+        .busiest_hours
+        .iter()
+        .map(|(hour, plays)| format!("{:02}:00 | {} plays", hour, plays))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Music Stats")
+        .color(0x2ECC71)
+        .field("Top Tracks", top_tracks, false)
+        .field(
+            "Top Requesters",
+            if top_requesters.is_empty() { "Unknown".to_string() } else { top_requesters },
+            false,
+        )
+        .field(
+            "Total Listening Time",
+            format_duration(Duration::from_secs(stats.listened_ms.max(0) as u64 / 1000)).to_string(),
+            true,
+        )
+        .field(
+            "Plays",
+            format!("{} ({} skipped)", stats.total_plays, stats.skipped_plays),
+            true,
+        )
+        .field("Busiest Hours (UTC)", busiest_hours, false);
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Lists recently played songs, optionally re-queueing one of them
+#[poise::command(slash_command, rename = "music-history", guild_only)]
+pub async fn music_history(
+    ctx: Context<'_>,
+    #[description = "How many plays to list (1-20)"] limit: Option<u8>,
+    #[description = "Number of a listed play to add back to the queue"] requeue: Option<u8>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let limit = limit.unwrap_or(10).clamp(1, 20);
+
+    if let Some(number) = requeue {
+        let plays = moonbot_db::recent_plays(ctx.data().db, guild_id.get() as i64, 20).await?;
+        let Some(entry) = plays.get((number as usize).wrapping_sub(1)) else {
+            send_err_msg(ctx, "Error", format!("There is no play #{} in the history.", number).as_str())
+                .await;
+            return Ok(());
+        };
+        let term = match &entry.uri {
+            Some(uri) if uri.starts_with("http") => uri.clone(),
+            _ => format!("{} {}", entry.author, entry.title),
+        };
+        return _play(ctx, term).await;
+    }
+
+    let plays = moonbot_db::recent_plays(ctx.data().db, guild_id.get() as i64, limit as u64).await?;
+    if plays.is_empty() {
+        send_err_msg(ctx, "Music History", "Nothing has been played in this server yet.").await;
+        return Ok(());
+    }
+
+    let history_message = plays
+        .iter()
+        .enumerate()
+        .map(|(idx, p)| {
+            format!(
+                "**{} - **[{} - {}](<{}>){}\n*Requested By {}* | <t:{}:R>",
+                idx + 1,
+                p.author,
+                p.title,
+                p.uri.as_deref().unwrap_or_default(),
+                if p.skipped { " *(skipped)*" } else { "" },
+                p.requester_id
+                    .map(|id| format!("<@!{}>", id))
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                p.played_at.timestamp(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Recently Played")
+        .color(0x2ECC71)
+        .description(history_message)
+        .footer(serenity::CreateEmbedFooter::new(
+            "Use /music-history requeue:<number> to play one again",
+        ));
 
     ctx.send(poise::CreateReply::default().embed(embed)).await?;
 
     Ok(())
 }
+
+/// Shows the Lavalink nodes and how busy they are
+#[poise::command(slash_command, rename = "music-nodes", guild_only)]
+pub async fn nodes(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
+    };
+    let Some(lava_client) = backend.lavalink() else {
+        send_err_msg(
+            ctx,
+            "Error",
+            format!("Music is playing through {}, node status needs Lavalink.", backend.name()).as_str(),
+        )
+        .await;
+        return Ok(());
+    };
+
+    let mut embed = serenity::CreateEmbed::new().title("Lavalink Nodes").color(0x2ECC71);
+    for node in crate::handlers::nodes::status(lava_client) {
+        let state = if !node.connected {
+            "🔴 Disconnected"
+        } else if !node.health.healthy {
+            "🟠 Unhealthy"
+        } else {
+            "🟢 Healthy"
+        };
+        let mut lines = vec![state.to_string()];
+        if !node.region.is_empty() {
+            lines.push(format!("Region: {}", node.region));
+        }
+        lines.push(format!("Priority: {}", node.priority));
+        lines.push(format!("Servers here: {}", node.guilds));
+        if let Some(stats) = &node.health.stats {
+            lines.push(format!("Players: {} playing / {} total", stats.playing_players, stats.players));
+            lines.push(format!(
+                "CPU: {:.0}% ({} cores)",
+                stats.cpu.lavalink_load * 100.0,
+                stats.cpu.cores
+            ));
+            lines.push(format!(
+                "Memory: {} / {} MB",
+                stats.memory.used / 1024 / 1024,
+                stats.memory.allocated / 1024 / 1024
+            ));
+            lines.push(format!(
+                "Uptime: {}",
+                format_duration(Duration::from_secs(stats.uptime / 1000))
+            ));
+        }
+        if let Some(error) = &node.health.last_error {
+            lines.push(format!("Last error: {}", error));
+        }
+        embed = embed.field(node.name, lines.join("\n"), true);
+    }
+    if let Some(name) = crate::handlers::nodes::node_name_for(lava_client, guild_id) {
+        embed = embed.footer(serenity::CreateEmbedFooter::new(format!("This server is playing on {}", name)));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum LimitsAction {
+    Show,
+    Set,
+    Reset,
+}
+
+/// Configure queue limits for this server
+#[poise::command(
+    slash_command,
+    rename = "music-limits",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn limits(
+    ctx: Context<'_>,
+    #[description = "Action"] action: LimitsAction,
+    #[description = "Most tracks in the queue, 0 for unlimited (for set)"] max_queue: Option<u32>,
+    #[description = "Most queued tracks per user, 0 for unlimited (for set)"] max_per_user: Option<u32>,
+    #[description = "Longest track in minutes, 0 for unlimited (for set)"] max_minutes: Option<u32>,
+    #[description = "Most tracks taken from a playlist, 0 for unlimited (for set)"] max_playlist: Option<u32>,
+    #[description = "Refuse tracks that are already queued (for set)"] no_duplicates: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let mut settings = moonbot_db::get_music_settings(ctx.data().db, guild_id.get() as i64).await;
+
+    match action {
+        LimitsAction::Show => {}
+        LimitsAction::Set => {
+            if max_queue.is_none()
+                && max_per_user.is_none()
+                && max_minutes.is_none()
+                && max_playlist.is_none()
+                && no_duplicates.is_none()
+            {
+                send_err_msg(ctx, "Error", "Give at least one limit to set.").await;
+                return Ok(());
+            }
+            if let Some(v) = max_queue {
+                settings.max_queue_length = Some(v.min(i32::MAX as u32) as i32);
+            }
+            if let Some(v) = max_per_user {
+                settings.max_user_tracks = Some(v.min(i32::MAX as u32) as i32);
+            }
+            if let Some(v) = max_minutes {
+                settings.max_track_seconds = Some(v as i64 * 60);
+            }
+            if let Some(v) = max_playlist {
+                settings.max_playlist_size = Some(v.min(i32::MAX as u32) as i32);
+            }
+            if let Some(v) = no_duplicates {
+                settings.prevent_duplicates = Some(v);
+            }
+            moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings.clone()).await?;
+        }
+        LimitsAction::Reset => {
+            settings.max_queue_length = None;
+            settings.max_user_tracks = None;
+            settings.max_track_seconds = None;
+            settings.max_playlist_size = None;
+            settings.prevent_duplicates = None;
+            moonbot_db::set_music_settings(ctx.data().db, guild_id.get() as i64, settings.clone()).await?;
+        }
+    }
+
+    let policy = QueuePolicy::new(&settings, vec![]);
+    let limit = |v: u64| if v == 0 { "Unlimited".to_string() } else { v.to_string() };
+    let embed = serenity::CreateEmbed::new()
+        .title("Queue Limits")
+        .color(0x2ECC71)
+        .field("Queue Length", limit(policy.max_queue_length as u64), true)
+        .field("Tracks Per User", limit(policy.max_user_tracks as u64), true)
+        .field(
+            "Track Length",
+            if policy.max_track_seconds == 0 {
+                "Unlimited".to_string()
+            } else {
+                format_duration(Duration::from_secs(policy.max_track_seconds)).to_string()
+            },
+            true,
+        )
+        .field("Playlist Import", limit(policy.max_playlist_size as u64), true)
+        .field(
+            "Duplicates",
+            if policy.prevent_duplicates { "Refused" } else { "Allowed" },
+            true,
+        );
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum BlocklistAction {
+    List,
+    Add,
+    Remove,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum BlockKind {
+    #[name = "URI (matches the start of a link)"]
+    Uri,
+    #[name = "Keyword (matches title or artist)"]
+    Keyword,
+}
+
+/// Block links or keywords from being queued
+#[poise::command(
+    slash_command,
+    rename = "music-blocklist",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn blocklist(
+    ctx: Context<'_>,
+    #[description = "Action"] action: BlocklistAction,
+    #[description = "What to match (for add/remove)"] kind: Option<BlockKind>,
+    #[description = "Link prefix or keyword (for add/remove)"] value: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+
+    if !matches!(action, BlocklistAction::List) {
+        let (Some(kind), Some(value)) = (kind, value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())) else {
+            send_err_msg(ctx, "Error", "Give a kind and a value.").await;
+            return Ok(());
+        };
+        let kind = match kind {
+            BlockKind::Uri => "uri",
+            BlockKind::Keyword => "keyword",
+        };
+        let (changed, title) = match action {
+            BlocklistAction::Add => (
+                moonbot_db::add_music_block(db, guild_id.get() as i64, kind, &value, Some(ctx.author().id.get() as i64)).await?,
+                "Blocked",
+            ),
+            _ => (
+                moonbot_db::remove_music_block(db, guild_id.get() as i64, kind, &value).await?,
+                "Unblocked",
+            ),
+        };
+        if !changed {
+            send_err_msg(
+                ctx,
+                "Error",
+                format!("`{}` was {} blocked.", value, if title == "Blocked" { "already" } else { "not" }).as_str(),
+            )
+            .await;
+            return Ok(());
+        }
+        let embed = serenity::CreateEmbed::new()
+            .title(title)
+            .color(0x2ECC71)
+            .description(format!("{} `{}`", kind, value));
+        ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let entries = moonbot_db::music_blocklist(db, guild_id.get() as i64).await?;
+    let list = |kind: &str| {
+        let items = entries
+            .iter()
+            .filter(|e| e.kind == kind)
+            .map(|e| format!("`{}`", e.value))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if items.is_empty() { "None".to_string() } else { items }
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title("Music Blocklist")
+        .color(0x2ECC71)
+        .field("URIs", list("uri"), false)
+        .field("Keywords", list("keyword"), false);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::vec;
 
 use crate::{Context, Error};
 use crate::context;
+use crate::prompt_history;
 use async_openai::types::{
     ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
     CreateImageRequestArgs, Image, ImageModel, ImageResponseFormat, ImageSize,
@@ -21,7 +22,7 @@ pub async fn askgpt(
     #[description = "The prompt to send to OpenAI"] prompt: String,
     #[description = "Use personalization (profile/mood)"] personalize: Option<bool>,
 ) -> Result<(), Error> {
-    let Some(client) = ctx.data().openai_client.as_ref() else {
+    let Some(llm) = ctx.data().llm.as_ref() else {
         ctx.say("OpenAI is not configured.").await?;
         return Ok(());
     };
@@ -30,14 +31,24 @@ pub async fn askgpt(
     // Build message list; centralized system prompt for consistency
     let mut msgs: Vec<async_openai::types::ChatCompletionRequestMessage> = vec![];
     if personalize.unwrap_or(false) && ctx.data().config.openai.auto.personalize {
-        let mut sys = context::build_system_prompt(ctx.data(), ctx.author().id.get() as i64, ctx.author().name.as_str()).await;
-        if let Some(guild_id) = ctx.guild_id() {
-            if let Some(p) = moonbot_db::get_guild_roleplay(ctx.data().db, guild_id.get() as i64).await {
-                sys = format!("### Roleplay persona (guild-wide)\n{}\n\n### Instruction\nStay in the above persona for this conversation. Reflect its style and diction consistently. Avoid generic chatbot greetings.\n\n{}", p, sys);
-            } else if let Some(p) = moonbot_db::get_channel_roleplay(ctx.data().db, ctx.channel_id().get() as i64).await {
-                sys = format!("### Roleplay persona\n{}\n\n### Instruction\nStay in the above persona for this conversation. Reflect its style and diction consistently. Avoid generic chatbot greetings.\n\n{}", p, sys);
+        let roleplay = match ctx.guild_id() {
+            Some(guild_id) => {
+                let location = crate::personas::Location::find(ctx, guild_id, ctx.channel_id()).await;
+                crate::personas::resolve(ctx.data().db, &location).await
             }
-        }
+            None => None,
+        };
+        let scene = context::PromptScene {
+            user_id: ctx.author().id.get() as i64,
+            user_name: &ctx.author().name,
+            guild_id: ctx.guild_id().map(|g| g.get() as i64),
+            guild_name: ctx.guild_id().and_then(|g| g.name(ctx.cache())).unwrap_or_default(),
+            channel_name: ctx.channel_id().name(ctx).await.unwrap_or_default(),
+            roleplay: roleplay.as_ref().map(|(_, s)| s),
+                retrieved: String::new(),
+            overrides: Default::default(),
+        };
+        let sys = context::build_system_prompt(ctx.data(), &scene).await;
         if !sys.is_empty() {
             msgs.push(async_openai::types::ChatCompletionRequestSystemMessageArgs::default()
                 .content(sys).build()?.into());
@@ -66,7 +77,7 @@ pub async fn askgpt(
                 .frequency_penalty(freq_pen)
                 .user(ctx.author().id.get().to_string())
                 .build()?;
-        match client.chat().create(request).await {
+        match llm.chat(request).await {
                 Ok(r) => { out = Some(r); break; },
                 Err(e) => {
             last_err = Some(format!("{}", e));
@@ -74,7 +85,7 @@ pub async fn askgpt(
                 }
             }
         }
-    if let Some(r) = out { r } else { return Err(std::io::Error::new(std::io::ErrorKind::Other, last_err.unwrap_or_else(|| "unknown error".into())).into()); }
+    if let Some(r) = out { r } else { return Err(std::io::Error::other(last_err.unwrap_or_else(|| "unknown error".into())).into()); }
     };
     ctx.say(
         resp.choices
@@ -107,7 +118,7 @@ pub async fn genimage(
     #[description = "The size of the image to generate"] size: Option<ImageSizeType>,
     #[description = "The number of images to generate"] amount: Option<u8>,
 ) -> Result<(), Error> {
-    let Some(client) = ctx.data().openai_client.as_ref() else {
+    let Some(llm) = ctx.data().llm.as_ref() else {
         ctx.say("OpenAI is not configured.").await?;
         return Ok(());
     };
@@ -162,7 +173,7 @@ pub async fn genimage(
                 .response_format(ImageResponseFormat::B64Json)
                 .build()
                 .unwrap();
-            match client.images().create(req).await {
+            match llm.images(req).await {
                 Ok(r) => { out = Some(r); break; },
                 Err(e) => {
                     last_err = Some(format!("{}", e));
@@ -170,7 +181,7 @@ pub async fn genimage(
                 }
             }
         }
-        if let Some(r) = out { r } else { return Err(std::io::Error::new(std::io::ErrorKind::Other, last_err.unwrap_or_else(|| "unknown error".into())).into()); }
+        if let Some(r) = out { r } else { return Err(std::io::Error::other(last_err.unwrap_or_else(|| "unknown error".into())).into()); }
     };
 
     let mut builder = EditMessage::new();
@@ -246,10 +257,13 @@ pub async fn prompt_set(
     #[description = "New system context; use new lines to separate multiple lines"] content: String,
 ) -> Result<(), Error> {
     let lines: Vec<String> = content.lines().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    let target = prompt_history::Target::global();
+    let before = prompt_history::current(ctx.data(), &target).await?;
     if let Err(e) = moonbot_db::set_global_system_context(ctx.data().db, lines).await {
     ctx.send(poise::CreateReply::default().content(format!("Failed to set global prompt: {}", e)).ephemeral(true)).await?;
     } else {
     context::invalidate_global_context().await;
+    prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
     ctx.send(poise::CreateReply::default().content("Updated global system_context.").ephemeral(true)).await?;
     }
     Ok(())
@@ -261,10 +275,13 @@ pub async fn prompt_add(
     ctx: Context<'_>,
     #[description = "Line to append to system_context"] line: String,
 ) -> Result<(), Error> {
+    let target = prompt_history::Target::global();
+    let before = prompt_history::current(ctx.data(), &target).await?;
     if let Err(e) = moonbot_db::add_global_system_context_line(ctx.data().db, line).await {
     ctx.send(poise::CreateReply::default().content(format!("Failed to add line: {}", e)).ephemeral(true)).await?;
     } else {
     context::invalidate_global_context().await;
+    prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
     ctx.send(poise::CreateReply::default().content("Appended line to global system_context.").ephemeral(true)).await?;
     }
     Ok(())
@@ -273,10 +290,13 @@ pub async fn prompt_add(
 /// Clear the global system_context (reverts to config fallback)
 #[poise::command(slash_command, rename = "prompt-clear", default_member_permissions = "ADMINISTRATOR", guild_only)]
 pub async fn prompt_clear(ctx: Context<'_>) -> Result<(), Error> {
+    let target = prompt_history::Target::global();
+    let before = prompt_history::current(ctx.data(), &target).await?;
     if let Err(e) = moonbot_db::clear_global_system_context(ctx.data().db).await {
     ctx.send(poise::CreateReply::default().content(format!("Failed to clear: {}", e)).ephemeral(true)).await?;
     } else {
     context::invalidate_global_context().await;
+    prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
     ctx.send(poise::CreateReply::default().content("Cleared global system_context.").ephemeral(true)).await?;
     }
     Ok(())
@@ -0,0 +1,419 @@
+use crate::{
+    personas::{self, Format, PersonaFile},
+    prompt_history::{self, Target},
+    utils::send_err_msg,
+    Context, Error,
+};
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+};
+use moonbot_db::Persona;
+use poise::serenity_prelude as serenity;
+
+pub async fn autocomplete_persona(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    moonbot_db::personas(ctx.data().db, guild_id.get() as i64)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|persona| persona.name)
+        .filter(|name| name.contains(&partial))
+        .take(25)
+        .collect()
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum FileFormat {
+    #[name = "TOML"]
+    Toml,
+    #[name = "JSON"]
+    Json,
+}
+
+/// Find a persona by name, telling the user if there's none.
+pub async fn find(ctx: Context<'_>, name: &str) -> Result<Option<Persona>, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let persona = moonbot_db::get_persona(ctx.data().db, guild_id.get() as i64, &name).await?;
+    if persona.is_none() {
+        send_err_msg(ctx, "Error", format!("There's no persona called `{}`.", name).as_str()).await;
+    }
+    Ok(persona)
+}
+
+fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn describe(persona: &Persona) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("Persona {}", persona.name))
+        .color(0x2ECC71)
+        .description(if persona.description.is_empty() { "No description" } else { &persona.description });
+    if !persona.display_name.is_empty() {
+        embed = embed.field("Speaks as", &persona.display_name, false);
+    }
+    // Keep the whole embed under Discord's 6000 characters, with room for a preview
+    for (title, text, max) in [
+        ("System", &persona.system_text, 1000),
+        ("Personality", &persona.personality, 500),
+        ("Scenario", &persona.scenario, 500),
+        ("Examples", &persona.example_dialogues, 500),
+        ("First message", &persona.first_message, 500),
+    ] {
+        if !text.is_empty() {
+            embed = embed.field(title, shorten(text, max), false);
+        }
+    }
+    embed
+        .field("Model", persona.model.as_deref().unwrap_or("Default"), true)
+        .field(
+            "Temperature",
+            persona.temperature.map(|t| format!("{:.2}", t)).unwrap_or_else(|| "Default".to_string()),
+            true,
+        )
+}
+
+/// Named personas the bot can roleplay as
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("list", "create", "edit", "delete", "preview", "import", "export"),
+    subcommand_required
+)]
+pub async fn persona(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// List this server's personas and where they're active
+#[poise::command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let library = moonbot_db::personas(db, guild_id.get() as i64).await?;
+    let activations = moonbot_db::persona_activations(db, guild_id.get() as i64).await?;
+
+    let mut description = String::new();
+    for (idx, persona) in library.iter().enumerate() {
+        let active = activations
+            .iter()
+            .filter(|(_, id)| *id == persona.id)
+            .map(|(channel, _)| if *channel == 0 { "server".to_string() } else { format!("<#{}>", channel) })
+            .collect::<Vec<_>>();
+        let active = if active.is_empty() { String::new() } else { format!(" | active in {}", active.join(", ")) };
+        let line = format!("`{}` {}{}\n", persona.name, shorten(&persona.description, 80), active);
+        if description.len() + line.len() > 4000 {
+            description.push_str(&format!("...and {} more", library.len() - idx));
+            break;
+        }
+        description.push_str(&line);
+    }
+    if description.is_empty() {
+        description = "No personas yet, admins add them with /persona create or /persona import and play them with /roleplay set.".to_string();
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Personas")
+        .color(0x2ECC71)
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Add a persona, write \n for new lines
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "Name (a-z, 0-9, - and _)"] name: String,
+    #[description = "Who the bot is and how it talks"] system: String,
+    #[description = "Short description for the list"] description: Option<String>,
+    #[description = "Example dialogues"] examples: Option<String>,
+    #[description = "Chat model, empty for the default"] model: Option<String>,
+    #[description = "Temperature, empty for the default"]
+    #[min = 0.0]
+    #[max = 2.0]
+    temperature: Option<f64>,
+) -> Result<(), Error> {
+    let file = PersonaFile {
+        name: name.trim().to_lowercase(),
+        description: description.unwrap_or_default().trim().to_string(),
+        system: system.replace("\\n", "\n"),
+        examples: examples.unwrap_or_default().replace("\\n", "\n"),
+        model: model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        temperature,
+        ..Default::default()
+    };
+    save_new(ctx, file, false, "Added").await
+}
+
+/// Validate and add a persona from a file or the create command, or replace the one
+/// with its name.
+async fn save_new(ctx: Context<'_>, file: PersonaFile, replace: bool, verb: &str) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    if let Some(problem) = personas::validate(&file) {
+        send_err_msg(ctx, "Error", problem.as_str()).await;
+        return Ok(());
+    }
+    let existing = moonbot_db::get_persona(db, guild_id.get() as i64, &file.name).await?;
+    if existing.is_some() && !replace {
+        send_err_msg(ctx, "Error", format!("`{}` already exists, edit or delete it first.", file.name).as_str()).await;
+        return Ok(());
+    }
+
+    let target = Target::persona(guild_id.get() as i64, &file.name);
+    let before = prompt_history::current(ctx.data(), &target).await?;
+    let persona = personas::save(db, guild_id.get() as i64, file, Some(ctx.author().id.get() as i64)).await?;
+    prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+
+    let embed = describe(&persona).title(format!("{} persona {}", verb, persona.name));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Change a persona, write \n for new lines
+#[allow(clippy::too_many_arguments)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[description = "Persona"]
+    #[autocomplete = "autocomplete_persona"]
+    name: String,
+    #[description = "New name"] rename: Option<String>,
+    #[description = "Who the bot is and how it talks"] system: Option<String>,
+    #[description = "Short description for the list"] description: Option<String>,
+    #[description = "Example dialogues, \"-\" for none"] examples: Option<String>,
+    #[description = "Chat model, \"-\" for the default"] model: Option<String>,
+    #[description = "Temperature, negative for the default"]
+    #[min = -1.0]
+    #[max = 2.0]
+    temperature: Option<f64>,
+) -> Result<(), Error> {
+    let Some(mut persona) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let mut file = PersonaFile::from(&persona);
+    if let Some(rename) = rename {
+        file.name = rename.trim().to_lowercase();
+    }
+    if let Some(system) = system {
+        file.system = system.replace("\\n", "\n");
+    }
+    if let Some(description) = description {
+        file.description = description.trim().to_string();
+    }
+    if let Some(examples) = examples {
+        file.examples = if examples.trim() == "-" { String::new() } else { examples.replace("\\n", "\n") };
+    }
+    if let Some(model) = model {
+        file.model = Some(model.trim().to_string()).filter(|m| !m.is_empty() && m != "-");
+    }
+    if let Some(temperature) = temperature {
+        file.temperature = Some(temperature).filter(|t| *t >= 0.0);
+    }
+
+    if let Some(problem) = personas::validate(&file) {
+        send_err_msg(ctx, "Error", problem.as_str()).await;
+        return Ok(());
+    }
+    if file.name != persona.name
+        && moonbot_db::get_persona(ctx.data().db, persona.guild_id, &file.name).await?.is_some()
+    {
+        send_err_msg(ctx, "Error", format!("`{}` already exists.", file.name).as_str()).await;
+        return Ok(());
+    }
+
+    // A rename shows up as the old name going away and the new one appearing
+    let mut targets = vec![Target::persona(persona.guild_id, &persona.name)];
+    if file.name != persona.name {
+        targets.push(Target::persona(persona.guild_id, &file.name));
+    }
+    let mut before = Vec::new();
+    for target in &targets {
+        before.push(prompt_history::current(ctx.data(), target).await?);
+    }
+    personas::apply(&mut persona, file);
+    moonbot_db::update_persona(ctx.data().db, &persona).await?;
+    for (target, before) in targets.iter().zip(before) {
+        prompt_history::record(ctx.data(), target, before, Some(ctx.author().id.get() as i64)).await;
+    }
+
+    let embed = describe(&persona).title(format!("Edited persona {}", persona.name));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Delete a persona
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Persona"]
+    #[autocomplete = "autocomplete_persona"]
+    name: String,
+) -> Result<(), Error> {
+    let Some(persona) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let target = Target::persona(persona.guild_id, &persona.name);
+    let before = prompt_history::current(ctx.data(), &target).await?;
+    moonbot_db::delete_persona(ctx.data().db, persona.id).await?;
+    prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Persona deleted")
+        .color(0x2ECC71)
+        .description(format!("`{}`, it's no longer active anywhere", persona.name));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show a persona, and how it would answer a message
+#[poise::command(slash_command, guild_only)]
+pub async fn preview(
+    ctx: Context<'_>,
+    #[description = "Persona"]
+    #[autocomplete = "autocomplete_persona"]
+    name: String,
+    #[description = "Message to answer in character"] message: Option<String>,
+) -> Result<(), Error> {
+    let Some(persona) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let mut embed = describe(&persona);
+
+    if let Some(message) = message {
+        let Some(llm) = ctx.data().llm.as_ref() else {
+            send_err_msg(ctx, "Error", "OpenAI isn't configured, so there's nothing to preview with.").await;
+            return Ok(());
+        };
+        ctx.defer_ephemeral().await?;
+        let system = crate::context::persona_prompt(
+            ctx.data(),
+            ctx.guild_id().map(|g| g.get() as i64),
+            &personas::Source::Persona(Box::new(persona.clone())),
+            ctx.author().id.get() as i64,
+            &ctx.author().name,
+            &Default::default(),
+        )
+        .await;
+        let auto = &ctx.data().config.openai.auto;
+        let model = persona.model.as_deref().filter(|m| !m.is_empty()).unwrap_or(auto.model.as_str());
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(model)
+            .max_tokens(auto.max_tokens)
+            .temperature(persona.temperature.map(|t| t as f32).unwrap_or(auto.temperature))
+            .messages(vec![
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(system)
+                    .build()?
+                    .into(),
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(message.as_str())
+                    .build()?
+                    .into(),
+            ])
+            .build()?;
+        let reply = match llm.chat(request).await {
+            Ok(response) => response
+                .choices
+                .first()
+                .and_then(|c| c.message.content.clone())
+                .unwrap_or_default(),
+            Err(e) => format!("Couldn't generate a reply: {}", e),
+        };
+        embed = embed
+            .field("Message", shorten(&message, 1000), false)
+            .field("Reply", shorten(&reply, 1000), false);
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Add a persona from a TOML or JSON file, or a SillyTavern character card
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "crate::utils::admin_only"
+)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "Persona file (.toml or .json) or character card (.json or .png)"] file: serenity::Attachment,
+    #[description = "Name to save it as, instead of the one in the file"] name: Option<String>,
+    #[description = "Replace the persona with the same name"] replace: Option<bool>,
+) -> Result<(), Error> {
+    // Card images are pictures, give them more room than text files
+    let max_bytes = if file.filename.to_lowercase().ends_with(".png") { 8 * 1024 * 1024 } else { 256 * 1024 };
+    if file.size > max_bytes {
+        send_err_msg(ctx, "Error", format!("That file is too big, the limit is {} KB.", max_bytes / 1024).as_str())
+            .await;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+    let data = file.download().await?;
+    let format = Format::detect(&file.filename, &data);
+    let mut parsed = match personas::parse(&data, format) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            send_err_msg(ctx, "Error", format!("Couldn't read that persona: {}", e).as_str()).await;
+            return Ok(());
+        }
+    };
+    if let Some(name) = name {
+        parsed.name = name;
+    }
+    parsed.name = parsed.name.trim().to_lowercase();
+    let verb = if format == Format::Card { "Imported character card as" } else { "Imported" };
+    save_new(ctx, parsed, replace.unwrap_or(false), verb).await
+}
+
+/// Download a persona as a TOML or JSON file
+#[poise::command(slash_command, guild_only)]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "Persona"]
+    #[autocomplete = "autocomplete_persona"]
+    name: String,
+    #[description = "File format, TOML by default"] format: Option<FileFormat>,
+) -> Result<(), Error> {
+    let Some(persona) = find(ctx, &name).await? else {
+        return Ok(());
+    };
+    let format = match format {
+        Some(FileFormat::Json) => Format::Json,
+        _ => Format::Toml,
+    };
+    let text = match personas::export(&persona, format) {
+        Ok(text) => text,
+        Err(e) => {
+            send_err_msg(ctx, "Error", format!("Couldn't export that persona: {}", e).as_str()).await;
+            return Ok(());
+        }
+    };
+    let attachment = serenity::CreateAttachment::bytes(text.into_bytes(), format!("{}.{}", persona.name, format.extension()));
+    ctx.send(poise::CreateReply::default().attachment(attachment).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use crate::{
+    context::{self, PromptScene},
+    eval::{self, Model, Variant},
+    personas::{self, Location},
+    prompt_history::{self, Kind, Target},
+    prompts::{self, Template, MAX_SOURCE},
+    utils::send_err_msg,
+    Context, Error,
+};
+use moonbot_db::PromptVersion;
+use poise::serenity_prelude as serenity;
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum TemplateAction {
+    Show,
+    Set,
+    Reset,
+}
+
+/// Send text in a code block, or as a file when it doesn't fit in a message.
+async fn send_text(ctx: Context<'_>, embed: serenity::CreateEmbed, text: &str, file_name: &str) -> Result<(), Error> {
+    let reply = poise::CreateReply::default().ephemeral(true);
+    let reply = if text.chars().count() <= 1900 {
+        reply
+            .embed(embed)
+            .content(format!("```\n{}\n```", text.replace("```", "'''")))
+    } else {
+        reply
+            .embed(embed)
+            .attachment(serenity::CreateAttachment::bytes(text.as_bytes().to_vec(), file_name))
+    };
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Edit the MiniJinja templates the bot's prompts are built from
+#[poise::command(
+    slash_command,
+    rename = "prompt-template",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_template(
+    ctx: Context<'_>,
+    #[description = "Action"] action: TemplateAction,
+    #[description = "Which template"] template: Template,
+    #[description = "New template, \\n for new lines (for set)"] content: Option<String>,
+    #[description = "New template as a file (for set)"] file: Option<serenity::Attachment>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let target = Target::template(guild_id.get() as i64, template);
+    let before = prompt_history::current(ctx.data(), &target).await?;
+
+    match action {
+        TemplateAction::Show => {}
+        TemplateAction::Set => {
+            let source = match (content, file) {
+                (Some(content), None) => content.replace("\\n", "\n"),
+                (None, Some(file)) => {
+                    if file.size as usize > MAX_SOURCE * 4 {
+                        send_err_msg(ctx, "Error", "That file is too big for a template.").await;
+                        return Ok(());
+                    }
+                    match String::from_utf8(file.download().await?) {
+                        Ok(source) => source,
+                        Err(_) => {
+                            send_err_msg(ctx, "Error", "The file isn't text.").await;
+                            return Ok(());
+                        }
+                    }
+                }
+                _ => {
+                    send_err_msg(ctx, "Error", "Give the template as content or as a file.").await;
+                    return Ok(());
+                }
+            };
+            if source.trim().is_empty() || source.chars().count() > MAX_SOURCE {
+                send_err_msg(ctx, "Error", format!("A template has 1 to {} characters.", MAX_SOURCE).as_str()).await;
+                return Ok(());
+            }
+            if let Err(e) = prompts::check(&source) {
+                send_err_msg(ctx, "Error", format!("That template doesn't compile: {}", e).as_str()).await;
+                return Ok(());
+            }
+            moonbot_db::set_prompt_template(
+                db,
+                guild_id.get() as i64,
+                template.key(),
+                Some(source),
+                Some(ctx.author().id.get() as i64),
+            )
+            .await?;
+            prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+        }
+        TemplateAction::Reset => {
+            moonbot_db::set_prompt_template(db, guild_id.get() as i64, template.key(), None, None).await?;
+            prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+        }
+    }
+
+    let custom = moonbot_db::get_prompt_template(db, guild_id.get() as i64, template.key()).await;
+    let embed = serenity::CreateEmbed::new()
+        .title(format!("Prompt template {}", template.key()))
+        .color(0x2ECC71)
+        .field("Source", if custom.is_some() { "This server's own" } else { "Config default" }, true)
+        .field("Variables", template.variables(), false);
+    let source = custom.unwrap_or_else(|| template.config_source(ctx.data()).to_string());
+    send_text(ctx, embed, &source, &format!("{}.jinja", template.key())).await
+}
+
+/// Show the system prompt the bot would use for a user in a channel
+#[poise::command(
+    slash_command,
+    rename = "prompt-preview",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_preview(
+    ctx: Context<'_>,
+    #[description = "User, yourself by default"] user: Option<serenity::User>,
+    #[description = "Channel or thread, this one by default"] channel: Option<serenity::GuildChannel>,
+    #[description = "Message to look up retrieved context for"] message: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let guild_id = ctx.guild_id().unwrap();
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let channel_id = channel.as_ref().map(|c| c.id).unwrap_or(ctx.channel_id());
+
+    let location = Location::find(ctx, guild_id, channel_id).await;
+    let roleplay = personas::resolve(ctx.data().db, &location).await;
+    let retrieved = match &message {
+        Some(message) => {
+            context::retrieve(ctx.data(), message, Some(guild_id.get() as i64), channel_id.get() as i64).await
+        }
+        None => String::new(),
+    };
+    let scene = PromptScene {
+        user_id: user.id.get() as i64,
+        user_name: &user.name,
+        guild_id: Some(guild_id.get() as i64),
+        guild_name: guild_id.name(ctx.cache()).unwrap_or_default(),
+        channel_name: channel_id.name(ctx).await.unwrap_or_default(),
+        roleplay: roleplay.as_ref().map(|(_, s)| s),
+        retrieved,
+        overrides: Default::default(),
+    };
+    let prompt = context::build_system_prompt(ctx.data(), &scene).await;
+
+    let roleplay = match &roleplay {
+        Some((scope, source)) => match source.persona() {
+            Some(persona) => format!("`{}` from the {}", persona.name, scope.label()),
+            None => format!("Text from the {}", scope.label()),
+        },
+        None => "None".to_string(),
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title("Prompt preview")
+        .color(0x2ECC71)
+        .description(format!("For <@{}> in <#{}>", user.id, channel_id))
+        .field("Roleplay", roleplay, true)
+        .field("Length", format!("{} characters", prompt.chars().count()), true);
+    send_text(ctx, embed, &prompt, "prompt.md").await
+}
+
+/// Find a version this server can see, telling the user if there's none.
+async fn find_version(ctx: Context<'_>, id: i64) -> Result<Option<PromptVersion>, Error> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let version = moonbot_db::get_prompt_version(ctx.data().db, id)
+        .await?
+        .filter(|v| v.guild_id == guild_id || v.guild_id == 0);
+    if version.is_none() {
+        send_err_msg(ctx, "Error", format!("There's no prompt version #{} here.", id).as_str()).await;
+    }
+    Ok(version)
+}
+
+fn version_line(version: &PromptVersion) -> String {
+    let target = Target::of(version).map(|t| t.describe()).unwrap_or_else(|| version.kind.clone());
+    let author = version.author.map(|a| format!(" by <@{}>", a)).unwrap_or_default();
+    let size = if version.content.is_empty() {
+        "cleared".to_string()
+    } else {
+        format!("{} lines", version.content.lines().count())
+    };
+    format!("`#{}` {} | <t:{}:R>{} | {}", version.id, target, version.created_at.timestamp(), author, size)
+}
+
+/// List changes to this server's prompts and the global prompt
+#[poise::command(
+    slash_command,
+    rename = "prompt-history",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_history(
+    ctx: Context<'_>,
+    #[description = "Only this kind of prompt"] kind: Option<Kind>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let versions =
+        moonbot_db::prompt_versions(ctx.data().db, guild_id.get() as i64, kind.map(|k| k.key()), 25).await?;
+
+    let mut description = String::new();
+    for version in &versions {
+        let line = format!("{}\n", version_line(version));
+        if description.len() + line.len() > 4000 {
+            break;
+        }
+        description.push_str(&line);
+    }
+    if description.is_empty() {
+        description = "No prompt changes yet.".to_string();
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Prompt history")
+        .color(0x2ECC71)
+        .description(description)
+        .footer(serenity::CreateEmbedFooter::new(
+            "Compare with /prompt-diff, restore with /prompt-rollback",
+        ));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show what changed between two prompt versions
+#[poise::command(
+    slash_command,
+    rename = "prompt-diff",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_diff(
+    ctx: Context<'_>,
+    #[description = "Older version"] a: i64,
+    #[description = "Newer version, the prompt as it is now if empty"] b: Option<i64>,
+) -> Result<(), Error> {
+    let Some(old) = find_version(ctx, a).await? else {
+        return Ok(());
+    };
+    let (new_name, new_content) = match b {
+        Some(b) => {
+            let Some(new) = find_version(ctx, b).await? else {
+                return Ok(());
+            };
+            (format!("#{}", new.id), new.content)
+        }
+        None => {
+            let target = Target::of(&old).ok_or("unknown kind of prompt")?;
+            ("now".to_string(), prompt_history::current(ctx.data(), &target).await?)
+        }
+    };
+
+    let old_name = format!("#{}", old.id);
+    let diff = similar::TextDiff::from_lines(&old.content, &new_content)
+        .unified_diff()
+        .context_radius(3)
+        .header(&old_name, &new_name)
+        .to_string();
+    let embed = serenity::CreateEmbed::new()
+        .title(format!("Prompt diff {} → {}", old_name, new_name))
+        .color(0x2ECC71)
+        .description(version_line(&old));
+    if diff.is_empty() {
+        ctx.send(poise::CreateReply::default().embed(embed.field("Changes", "None", false)).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    let reply = poise::CreateReply::default().embed(embed).ephemeral(true);
+    let reply = if diff.chars().count() <= 1900 {
+        reply.content(format!("```diff\n{}\n```", diff.replace("```", "'''")))
+    } else {
+        reply.attachment(serenity::CreateAttachment::bytes(diff.into_bytes(), "prompt.diff"))
+    };
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Put an old version of a prompt back
+#[poise::command(
+    slash_command,
+    rename = "prompt-rollback",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_rollback(
+    ctx: Context<'_>,
+    #[description = "Version to restore, see /prompt-history"] version: i64,
+) -> Result<(), Error> {
+    let Some(version) = find_version(ctx, version).await? else {
+        return Ok(());
+    };
+    if let Err(e) = prompt_history::restore(ctx.data(), &version, Some(ctx.author().id.get() as i64)).await {
+        send_err_msg(ctx, "Error", format!("Couldn't restore that version: {}", e).as_str()).await;
+        return Ok(());
+    }
+
+    let target = Target::of(&version).map(|t| t.describe()).unwrap_or_default();
+    let embed = serenity::CreateEmbed::new()
+        .title("Prompt rolled back")
+        .color(0x2ECC71)
+        .description(format!("{} is back to version #{}.", target, version.id));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+async fn autocomplete_fixture(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    eval::fixture_files(ctx.data())
+        .into_iter()
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .collect()
+}
+
+/// Replay test conversations with two prompt versions and compare the replies
+#[poise::command(
+    slash_command,
+    rename = "prompt-eval",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prompt_eval(
+    ctx: Context<'_>,
+    #[description = "Fixture from the evals directory"]
+    #[autocomplete = "autocomplete_fixture"]
+    fixture: Option<String>,
+    #[description = "Or a YAML/JSON fixture file"] file: Option<serenity::Attachment>,
+    #[description = "Prompt version like #12, current by default"] a: Option<String>,
+    #[description = "Prompt version to compare with, like current or #12"] b: Option<String>,
+    #[description = "Score the replies with the judge model"] judge: Option<bool>,
+    #[description = "Use canned replies instead of the model"] mock: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let guild_id = Some(ctx.guild_id().unwrap().get() as i64);
+
+    let fixture = match (fixture, file) {
+        (Some(name), None) => eval::load_fixture(ctx.data(), &name),
+        (None, Some(file)) => {
+            if file.size as usize > eval::MAX_FIXTURE {
+                send_err_msg(ctx, "Error", "That file is too big for a fixture.").await;
+                return Ok(());
+            }
+            eval::parse_fixture(&file.filename, &file.download().await?)
+        }
+        _ => Err("Give either a fixture name or a file.".to_string()),
+    };
+    let fixture = match fixture {
+        Ok(fixture) => fixture,
+        Err(e) => {
+            send_err_msg(ctx, "Error", format!("Couldn't load the fixture: {}", e).as_str()).await;
+            return Ok(());
+        }
+    };
+
+    let mut variants = vec![];
+    for spec in [a.or(Some("current".to_string())), b].into_iter().flatten() {
+        match Variant::parse(ctx.data(), guild_id, &spec).await {
+            Ok(variant) => variants.push(variant),
+            Err(e) => {
+                send_err_msg(ctx, "Error", format!("Bad prompt version: {}", e).as_str()).await;
+                return Ok(());
+            }
+        }
+    }
+
+    let options = eval::Options {
+        guild_id,
+        model: if mock.unwrap_or(false) { Model::Mock } else { Model::Configured },
+        judge: judge.unwrap_or(false),
+    };
+    let report = match eval::run(ctx.data(), &fixture, &variants, &options).await {
+        Ok(report) => report,
+        Err(e) => {
+            send_err_msg(ctx, "Error", format!("The eval failed: {}", e).as_str()).await;
+            return Ok(());
+        }
+    };
+
+    let averages = report.averages();
+    let failures = report.failures();
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("Prompt eval: {}", report.fixture))
+        .color(0x2ECC71)
+        .description(format!(
+            "{} conversations with {}. The replies are in the attached report.",
+            report.outcomes.len(),
+            report.model
+        ));
+    for (i, variant) in report.variants.iter().enumerate() {
+        let score = averages[i].map(|a| format!("{:.1}/10", a)).unwrap_or_else(|| "Not scored".to_string());
+        embed = embed.field(
+            format!("{}: {}", (b'A' + i as u8) as char, variant),
+            format!("{}\n{} failed", score, failures[i]),
+            true,
+        );
+    }
+    let attachment = serenity::CreateAttachment::bytes(report.to_markdown().into_bytes(), "eval.md");
+    ctx.send(poise::CreateReply::default().embed(embed).attachment(attachment).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use crate::{
+    handlers::scheduler::{self, next_after, parse_cron, parse_timezone, parse_when, user_timezone},
+    utils::send_err_msg,
+    Context, Error,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use moonbot_db::ScheduledJob;
+use poise::serenity_prelude as serenity;
+
+async fn autocomplete_timezone(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    chrono_tz::TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(String::from)
+        .collect()
+}
+
+/// `<t:..>` markup showing a time in everyone's own timezone.
+fn discord_time(time: chrono::DateTime<Utc>) -> String {
+    format!("<t:{0}:f> (<t:{0}:R>)", time.timestamp())
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    format!("{}…", text.chars().take(max - 1).collect::<String>())
+}
+
+/// Set a reminder
+#[poise::command(slash_command)]
+pub async fn remind(
+    ctx: Context<'_>,
+    #[description = "When, e.g. \"in 2h\", \"tomorrow 9:30\" or \"2025-12-24 18:00\""] when: String,
+    #[description = "What to remind you of"] text: String,
+) -> Result<(), Error> {
+    let tz = user_timezone(ctx.author().id).await;
+    let result = match parse_when(&when, tz, Utc::now()) {
+        Ok(at) => scheduler::add_reminder(ctx.author().id, ctx.guild_id(), ctx.channel_id(), at, &text, tz)
+            .await
+            .map(|id| (id, at)),
+        Err(e) => Err(e),
+    };
+    let (id, at) = match result {
+        Ok(x) => x,
+        Err(e) => {
+            send_err_msg(ctx, "Error", e.as_str()).await;
+            return Ok(());
+        }
+    };
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Reminder set")
+        .color(0x2ECC71)
+        .description(truncate(&text, 1000))
+        .field("When", discord_time(at), false)
+        .footer(serenity::CreateEmbedFooter::new(format!("#{} | {}", id, tz.name())));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum RemindersAction {
+    List,
+    Cancel,
+}
+
+/// List or cancel your reminders
+#[poise::command(slash_command)]
+pub async fn reminders(
+    ctx: Context<'_>,
+    #[description = "Action"] action: RemindersAction,
+    #[description = "Reminder number (for cancel)"] id: Option<i64>,
+) -> Result<(), Error> {
+    let db = ctx.data().db;
+
+    if let RemindersAction::Cancel = action {
+        let Some(id) = id else {
+            send_err_msg(ctx, "Error", "Give the number of the reminder to cancel.").await;
+            return Ok(());
+        };
+        let job = moonbot_db::get_scheduled_job(db, id).await?;
+        let Some(job) = job.filter(|j| j.kind == "reminder" && j.user_id == ctx.author().id.get() as i64) else {
+            send_err_msg(ctx, "Error", format!("You have no reminder #{}.", id).as_str()).await;
+            return Ok(());
+        };
+        moonbot_db::delete_scheduled_job(db, job.id).await?;
+        scheduler::wake();
+        let embed = serenity::CreateEmbed::new()
+            .title("Reminder cancelled")
+            .color(0x2ECC71)
+            .description(truncate(&job.message, 1000));
+        ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let pending = moonbot_db::user_reminders(db, ctx.author().id.get() as i64).await?;
+    let mut description = pending
+        .iter()
+        .map(|job| format!("**#{}** {}\n{}", job.id, discord_time(job.next_run), truncate(&job.message, 100)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if description.is_empty() {
+        description = "No reminders, set one with `/remind`.".to_string();
+    }
+    let embed = serenity::CreateEmbed::new()
+        .title("Your reminders")
+        .color(0x2ECC71)
+        .description(truncate(&description, 4000));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show or set your timezone for reminders
+#[poise::command(slash_command)]
+pub async fn timezone(
+    ctx: Context<'_>,
+    #[description = "IANA timezone, e.g. Europe/Berlin"]
+    #[autocomplete = "autocomplete_timezone"]
+    timezone: Option<String>,
+) -> Result<(), Error> {
+    if let Some(name) = timezone {
+        let tz = match parse_timezone(&name) {
+            Ok(tz) => tz,
+            Err(e) => {
+                send_err_msg(ctx, "Error", e.as_str()).await;
+                return Ok(());
+            }
+        };
+        moonbot_db::set_user_timezone(ctx.data().db, ctx.author().id.get() as i64, Some(tz.name().to_string()))
+            .await?;
+    }
+
+    let tz = user_timezone(ctx.author().id).await;
+    let local = Utc::now().with_timezone(&tz);
+    let embed = serenity::CreateEmbed::new()
+        .title("Timezone")
+        .color(0x2ECC71)
+        .description(format!("**{}**, where it's {} now.", tz.name(), local.format("%H:%M on %Y-%m-%d")));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ScheduleAction {
+    Add,
+    List,
+    Cancel,
+}
+
+/// Post recurring messages to a channel
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn schedule(
+    ctx: Context<'_>,
+    #[description = "Action"] action: ScheduleAction,
+    #[description = "Cron expression, e.g. \"0 9 * * MON\" for Mondays at 9:00 (for add)"] cron: Option<String>,
+    #[description = "Channel to post in (for add)"]
+    #[channel_types("Text", "News")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Message to post (for add)"] message: Option<String>,
+    #[description = "Timezone the cron times are in, defaults to yours (for add)"]
+    #[autocomplete = "autocomplete_timezone"]
+    timezone: Option<String>,
+    #[description = "Schedule number (for cancel)"] id: Option<i64>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let config = &ctx.data().config.scheduler;
+
+    match action {
+        ScheduleAction::Add => {
+            let (Some(expr), Some(channel), Some(message)) = (cron, channel, message) else {
+                send_err_msg(ctx, "Error", "Give a cron expression, a channel and a message.").await;
+                return Ok(());
+            };
+            let tz = match timezone {
+                Some(name) => match parse_timezone(&name) {
+                    Ok(tz) => tz,
+                    Err(e) => {
+                        send_err_msg(ctx, "Error", e.as_str()).await;
+                        return Ok(());
+                    }
+                },
+                None => user_timezone(ctx.author().id).await,
+            };
+            let schedule = match parse_cron(&expr) {
+                Ok(s) => s,
+                Err(e) => {
+                    send_err_msg(ctx, "Error", e.as_str()).await;
+                    return Ok(());
+                }
+            };
+
+            let now = Utc::now();
+            let Some(first) = next_after(&schedule, tz, now) else {
+                send_err_msg(ctx, "Error", "That schedule never runs.").await;
+                return Ok(());
+            };
+            if let Some(second) = next_after(&schedule, tz, first) {
+                if second - first < ChronoDuration::minutes(config.min_interval_minutes as i64) {
+                    send_err_msg(
+                        ctx,
+                        "Error",
+                        format!("Schedules can run at most every {} minutes.", config.min_interval_minutes).as_str(),
+                    )
+                    .await;
+                    return Ok(());
+                }
+            }
+            let existing = moonbot_db::guild_schedules(db, guild_id.get() as i64).await?;
+            if config.max_schedules_per_guild > 0 && existing.len() >= config.max_schedules_per_guild as usize {
+                send_err_msg(
+                    ctx,
+                    "Error",
+                    format!("This server already has {} schedules.", existing.len()).as_str(),
+                )
+                .await;
+                return Ok(());
+            }
+
+            let id = moonbot_db::add_scheduled_job(
+                db,
+                &ScheduledJob {
+                    id: 0,
+                    kind: "schedule".to_string(),
+                    guild_id: Some(guild_id.get() as i64),
+                    channel_id: channel.id.get() as i64,
+                    user_id: ctx.author().id.get() as i64,
+                    message: message.clone(),
+                    cron: Some(expr.trim().to_string()),
+                    timezone: tz.name().to_string(),
+                    next_run: first,
+                    last_run: None,
+                },
+            )
+            .await?;
+            scheduler::wake();
+
+            let embed = serenity::CreateEmbed::new()
+                .title("Schedule added")
+                .color(0x2ECC71)
+                .description(truncate(&message, 1000))
+                .field("Channel", format!("<#{}>", channel.id), true)
+                .field("Cron", format!("`{}` ({})", expr.trim(), tz.name()), true)
+                .field("Next run", discord_time(first), false)
+                .footer(serenity::CreateEmbedFooter::new(format!("#{}", id)));
+            ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        }
+        ScheduleAction::Cancel => {
+            let Some(id) = id else {
+                send_err_msg(ctx, "Error", "Give the number of the schedule to cancel.").await;
+                return Ok(());
+            };
+            let job = moonbot_db::get_scheduled_job(db, id).await?;
+            let Some(job) = job.filter(|j| j.kind == "schedule" && j.guild_id == Some(guild_id.get() as i64)) else {
+                send_err_msg(ctx, "Error", format!("This server has no schedule #{}.", id).as_str()).await;
+                return Ok(());
+            };
+            moonbot_db::delete_scheduled_job(db, job.id).await?;
+            scheduler::wake();
+            let embed = serenity::CreateEmbed::new()
+                .title("Schedule cancelled")
+                .color(0x2ECC71)
+                .description(truncate(&job.message, 1000));
+            ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        }
+        ScheduleAction::List => {
+            let jobs = moonbot_db::guild_schedules(db, guild_id.get() as i64).await?;
+            let mut description = jobs
+                .iter()
+                .map(|job| {
+                    format!(
+                        "**#{}** `{}` ({}) in <#{}>\nNext {}\n{}",
+                        job.id,
+                        job.cron.as_deref().unwrap_or_default(),
+                        job.timezone,
+                        job.channel_id,
+                        discord_time(job.next_run),
+                        truncate(&job.message, 100)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            if description.is_empty() {
+                description = "No schedules.".to_string();
+            }
+            let embed = serenity::CreateEmbed::new()
+                .title("Schedules")
+                .color(0x2ECC71)
+                .description(truncate(&description, 4000));
+            ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+        }
+    }
+    Ok(())
+}
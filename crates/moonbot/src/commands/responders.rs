@@ -0,0 +1,71 @@
+use crate::{handlers::responders, utils::send_err_msg, Context, Error};
+use poise::serenity_prelude as serenity;
+
+async fn autocomplete_responder(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    responders::all()
+        .iter()
+        .map(|r| r.name().to_string())
+        .filter(|n| n.contains(&partial.to_lowercase()))
+        .collect()
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum RespondersAction {
+    List,
+    Enable,
+    Disable,
+    Reset,
+}
+
+/// Turn passive message responders on or off for the server
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn responders(
+    ctx: Context<'_>,
+    #[description = "Action"] action: RespondersAction,
+    #[description = "Responder (for enable, disable and reset)"]
+    #[autocomplete = "autocomplete_responder"]
+    name: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    if !matches!(action, RespondersAction::List) {
+        let Some(name) = name else {
+            send_err_msg(ctx, "Error", "Give the name of a responder.").await;
+            return Ok(());
+        };
+        let Some(responder) = responders::find(name.trim()) else {
+            send_err_msg(ctx, "Error", format!("There's no responder called `{}`.", name).as_str()).await;
+            return Ok(());
+        };
+        let enabled = match action {
+            RespondersAction::Enable => Some(true),
+            RespondersAction::Disable => Some(false),
+            _ => None,
+        };
+        responder.set_enabled(guild_id, enabled).await?;
+    }
+
+    let mut embed = serenity::CreateEmbed::new().title("Responders").color(0x2ECC71);
+    for responder in responders::all() {
+        let limits = responder.limits(Some(guild_id)).await;
+        let status = if responder.enabled(guild_id).await { "Enabled" } else { "Disabled" };
+        embed = embed.field(
+            responder.name(),
+            format!(
+                "{}\n**{}**, {:.0}% chance, {} cooldown",
+                responder.description(),
+                status,
+                limits.chance * 100.0,
+                humantime::format_duration(limits.cooldown)
+            ),
+            false,
+        );
+    }
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
@@ -1,38 +1,187 @@
-use crate::{Context, Error};
-
-/// Manage the guild-wide roleplay persona
-#[poise::command(slash_command, rename = "roleplay", default_member_permissions = "ADMINISTRATOR", guild_only)]
-pub async fn command(
-	ctx: Context<'_>,
-	#[description = "Action"] action: RoleplayAction,
-	#[description = "Persona text (for set)"] persona: Option<String>,
+use crate::{
+    commands::persona::{self, autocomplete_persona},
+    personas::{self, Location, Scope, Source},
+    prompt_history::{self, Target},
+    utils::send_err_msg,
+    Context, Error,
+};
+use poise::serenity_prelude as serenity;
+
+/// Where this command was run, with the thread split from its channel.
+async fn location(ctx: Context<'_>) -> Location {
+    Location::find(ctx, ctx.guild_id().unwrap(), ctx.channel_id()).await
+}
+
+/// The key for a scope here, telling the user if there's no thread to set it on.
+async fn scope_key(ctx: Context<'_>, location: &Location, scope: Scope) -> Result<Option<i64>, Error> {
+    let key = location.key(scope);
+    if key.is_none() {
+        send_err_msg(ctx, "Error", "This isn't a thread, run it in the thread or pick another scope.").await;
+    }
+    Ok(key)
+}
+
+fn scope_name(location: &Location, scope: Scope) -> String {
+    match (scope, location.key(scope)) {
+        (Scope::Guild, _) => "the whole server".to_string(),
+        (_, Some(key)) => format!("<#{}>", key),
+        (_, None) => format!("this {}", scope.label()),
+    }
+}
+
+fn describe_source(source: Option<&Source>) -> String {
+    match source {
+        Some(Source::Persona(persona)) => format!("Persona `{}`", persona.name),
+        Some(Source::Text(text)) => {
+            let text = text.trim();
+            if text.chars().count() > 200 {
+                format!("Text: {}…", text.chars().take(199).collect::<String>())
+            } else {
+                format!("Text: {}", text)
+            }
+        }
+        None => "Nothing".to_string(),
+    }
+}
+
+/// Choose which persona the bot plays, for the server, a channel or a thread
+#[poise::command(slash_command, guild_only, subcommands("set", "clear", "show"), subcommand_required)]
+pub async fn roleplay(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Play a persona from the library, or some text, in this thread, channel or the server
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Where it applies, the thread or channel this is run in, or the server"] scope: Scope,
+    #[description = "Persona from /persona"]
+    #[autocomplete = "autocomplete_persona"]
+    persona: Option<String>,
+    #[description = "Or just describe who the bot is (not for the server)"] text: Option<String>,
 ) -> Result<(), Error> {
-	let Some(gid) = ctx.guild_id() else { return Ok(()); };
-	match action {
-		RoleplayAction::Show => {
-			if let Some(p) = moonbot_db::get_guild_roleplay(ctx.data().db, gid.get() as i64).await {
-				ctx.send(poise::CreateReply::default().content(format!("Persona:\n{}", p)).ephemeral(true)).await?;
-			} else {
-				ctx.send(poise::CreateReply::default().content("No guild persona set").ephemeral(true)).await?;
-			}
-		}
-		RoleplayAction::Set => {
-			let Some(txt) = persona else { ctx.send(poise::CreateReply::default().content("Missing persona").ephemeral(true)).await?; return Ok(()); };
-			match moonbot_db::set_guild_roleplay(ctx.data().db, gid.get() as i64, txt).await {
-				Ok(()) => ctx.send(poise::CreateReply::default().content("Updated guild persona").ephemeral(true)).await?,
-				Err(e) => ctx.send(poise::CreateReply::default().content(format!("Failed: {}", e)).ephemeral(true)).await?,
-			};
-		}
-		RoleplayAction::Clear => {
-			match moonbot_db::clear_guild_roleplay(ctx.data().db, gid.get() as i64).await {
-				Ok(_) => ctx.send(poise::CreateReply::default().content("Cleared guild persona").ephemeral(true)).await?,
-				Err(e) => ctx.send(poise::CreateReply::default().content(format!("Failed: {}", e)).ephemeral(true)).await?,
-			};
-		}
-	}
-	Ok(())
-}
-
-#[derive(Debug, poise::ChoiceParameter)]
-pub enum RoleplayAction { Show, Set, Clear }
+    let db = ctx.data().db;
+    let location = location(ctx).await;
+    let Some(key) = scope_key(ctx, &location, scope).await? else {
+        return Ok(());
+    };
 
+    let source = match (persona, text) {
+        (Some(name), None) => match persona::find(ctx, &name).await? {
+            Some(persona) => Source::Persona(Box::new(persona)),
+            None => return Ok(()),
+        },
+        (None, Some(_)) if scope == Scope::Guild => {
+            send_err_msg(ctx, "Error", "The server plays a persona from the library, add one with /persona create.")
+                .await;
+            return Ok(());
+        }
+        (None, Some(text)) if !text.trim().is_empty() => {
+            if text.chars().count() > personas::MAX_TEXT {
+                send_err_msg(ctx, "Error", format!("The text can be at most {} characters.", personas::MAX_TEXT).as_str())
+                    .await;
+                return Ok(());
+            }
+            Source::Text(text.replace("\\n", "\n"))
+        }
+        _ => {
+            send_err_msg(ctx, "Error", "Give either a persona or some text.").await;
+            return Ok(());
+        }
+    };
+
+    // One thing per scope, so what /roleplay show says is what the bot does
+    let target = Target::channel(location.guild_id, key);
+    let before = if key != 0 { prompt_history::current(ctx.data(), &target).await? } else { String::new() };
+    match &source {
+        Source::Persona(persona) => {
+            moonbot_db::set_active_persona(db, location.guild_id, key, Some(persona.id)).await?;
+            if key != 0 {
+                moonbot_db::clear_channel_roleplay(db, key).await?;
+            }
+        }
+        Source::Text(text) => {
+            moonbot_db::set_active_persona(db, location.guild_id, key, None).await?;
+            moonbot_db::set_channel_roleplay(db, key, text.clone()).await?;
+        }
+    }
+    if key != 0 {
+        prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Roleplay")
+        .color(0x2ECC71)
+        .description(format!("{} in {}.", describe_source(Some(&source)), scope_name(&location, scope)));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Stop roleplaying in this thread, channel or the server
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn clear(
+    ctx: Context<'_>,
+    #[description = "Which scope to clear"] scope: Scope,
+) -> Result<(), Error> {
+    let db = ctx.data().db;
+    let location = location(ctx).await;
+    let Some(key) = scope_key(ctx, &location, scope).await? else {
+        return Ok(());
+    };
+    moonbot_db::set_active_persona(db, location.guild_id, key, None).await?;
+    if key != 0 {
+        let target = Target::channel(location.guild_id, key);
+        let before = prompt_history::current(ctx.data(), &target).await?;
+        moonbot_db::clear_channel_roleplay(db, key).await?;
+        prompt_history::record(ctx.data(), &target, before, Some(ctx.author().id.get() as i64)).await;
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Roleplay")
+        .color(0x2ECC71)
+        .description(format!("Nothing is set for {} anymore.", scope_name(&location, scope)));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Show which roleplay is in effect here, and why
+#[poise::command(slash_command, guild_only)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let db = ctx.data().db;
+    let location = location(ctx).await;
+
+    let mut effective = None;
+    let mut embed = serenity::CreateEmbed::new().title("Roleplay").color(0x2ECC71);
+    for scope in [Scope::Thread, Scope::Channel, Scope::Guild] {
+        if location.key(scope).is_none() {
+            continue;
+        }
+        let source = personas::lookup(db, &location, scope).await;
+        let mut value = describe_source(source.as_ref());
+        if source.is_some() {
+            if effective.is_none() {
+                effective = Some(scope);
+                value.push_str("\n**In effect**");
+            } else {
+                value.push_str("\nOverridden");
+            }
+        }
+        let title = match scope {
+            Scope::Thread => format!("Thread {}", scope_name(&location, scope)),
+            Scope::Channel => format!("Channel {}", scope_name(&location, scope)),
+            Scope::Guild => "Server".to_string(),
+        };
+        embed = embed.field(title, value, false);
+    }
+
+    let description = match effective {
+        Some(scope) => format!(
+            "The {} setting is in effect here. A thread's setting beats its channel's, which beats the server's.",
+            scope.label()
+        ),
+        None => "No roleplay here, the bot talks as itself.".to_string(),
+    };
+    ctx.send(poise::CreateReply::default().embed(embed.description(description)).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use crate::{
+    commands::music::{_join, music_backend},
+    soundboard,
+    utils::send_err_msg,
+    Context, Error,
+};
+use humantime::format_duration;
+use poise::serenity_prelude as serenity;
+use std::time::Duration;
+
+async fn autocomplete_clip(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    moonbot_db::sound_clips(ctx.data().db, guild_id.get() as i64)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|clip| clip.name)
+        .filter(|name| name.contains(&partial))
+        .take(25)
+        .collect()
+}
+
+/// Play short clips from this server's soundboard
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("play", "add", "remove", "list"),
+    subcommand_required
+)]
+pub async fn sound(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Play a clip in your voice channel
+#[poise::command(slash_command, guild_only)]
+pub async fn play(
+    ctx: Context<'_>,
+    #[description = "Clip name"]
+    #[autocomplete = "autocomplete_clip"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+
+    let Some(clip) = moonbot_db::get_sound_clip(ctx.data().db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no clip called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
+    };
+
+    let (user_channel, bot_channel) = {
+        let guild = ctx.guild().unwrap();
+        let channel_of = |user| guild.voice_states.get(&user).and_then(|vs| vs.channel_id);
+        (channel_of(ctx.author().id), channel_of(ctx.cache().current_user().id))
+    };
+    let Some(user_channel) = user_channel else {
+        send_err_msg(ctx, "Error", "You are not in a voice channel, please join one first.").await;
+        return Ok(());
+    };
+    if backend.is_connected(guild_id) {
+        if let Some(bot_channel) = bot_channel.filter(|c| *c != user_channel) {
+            send_err_msg(
+                ctx,
+                "Error",
+                format!("I'm in <#{}>, join it to use the soundboard.", bot_channel).as_str(),
+            )
+            .await;
+            return Ok(());
+        }
+    } else {
+        _join(&ctx, guild_id, Some(user_channel)).await?;
+        if !backend.is_connected(guild_id) {
+            return Ok(());
+        }
+    }
+
+    let path = soundboard::clip_path(guild_id, &clip);
+    if !path.is_file() {
+        send_err_msg(ctx, "Error", "The file for this clip is missing, remove it and add it again.").await;
+        return Ok(());
+    }
+    let mut track = match backend.load_clip(guild_id, &path).await {
+        Ok(track) => track,
+        Err(e) => {
+            send_err_msg(ctx, "Error", e.to_string().as_str()).await;
+            return Ok(());
+        }
+    };
+    track.user_data = Some(serde_json::json!({
+        "soundboard": clip.name,
+        "requester_id": ctx.author().id.get(),
+    }));
+    backend.play_clip(guild_id, track).await?;
+    let _ = moonbot_db::record_sound_play(ctx.data().db, guild_id.get() as i64, &clip.name).await;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("Playing `{}`", clip.name))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Add a clip to the soundboard from an audio file
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name to play it by (a-z, 0-9, - and _)"] name: String,
+    #[description = "Audio file"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = ctx.data().db;
+    let config = &ctx.data().config.music.soundboard;
+    let name = name.trim().to_lowercase();
+
+    if !soundboard::valid_name(&name) {
+        send_err_msg(ctx, "Error", "Names are up to 32 characters of a-z, 0-9, - and _.").await;
+        return Ok(());
+    }
+    let clips = moonbot_db::sound_clips(db, guild_id.get() as i64).await?;
+    if clips.iter().any(|c| c.name == name) {
+        send_err_msg(ctx, "Error", format!("`{}` already exists, remove it first.", name).as_str()).await;
+        return Ok(());
+    }
+    if config.max_clips > 0 && clips.len() >= config.max_clips as usize {
+        send_err_msg(
+            ctx,
+            "Error",
+            format!("The soundboard is full ({} clips).", config.max_clips).as_str(),
+        )
+        .await;
+        return Ok(());
+    }
+    if file.size as u64 > config.max_bytes {
+        send_err_msg(
+            ctx,
+            "Error",
+            format!("Clips can be at most {} KB.", config.max_bytes / 1024).as_str(),
+        )
+        .await;
+        return Ok(());
+    }
+    let extension = file
+        .filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .filter(|ext| soundboard::EXTENSIONS.contains(&ext.as_str()));
+    let Some(extension) = extension else {
+        send_err_msg(
+            ctx,
+            "Error",
+            format!("Upload one of: {}.", soundboard::EXTENSIONS.join(", ")).as_str(),
+        )
+        .await;
+        return Ok(());
+    };
+    let Some(backend) = music_backend(ctx).await else {
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+    let data = file.download().await?;
+    let dir = soundboard::guild_dir(guild_id);
+    tokio::fs::create_dir_all(&dir).await?;
+    let file_name = format!("{}.{}", name, extension);
+    let path = dir.join(&file_name);
+    tokio::fs::write(&path, &data).await?;
+
+    // Probe it through the backend that will play it, which also gives the length
+    let problem = match backend.load_clip(guild_id, &path).await {
+        Err(e) => Some(format!("Couldn't read that as audio: {}", e)),
+        Ok(track) if track.info.is_stream || track.info.length == 0 => {
+            Some("Couldn't work out how long that clip is.".to_string())
+        }
+        Ok(track) if track.info.length > config.max_seconds * 1000 => {
+            Some(format!("Clips can be at most {} seconds long.", config.max_seconds))
+        }
+        Ok(track) => {
+            let clip = moonbot_db::SoundClip {
+                name: name.clone(),
+                file_name,
+                size_bytes: data.len() as i64,
+                duration_ms: track.info.length as i64,
+                added_by: Some(ctx.author().id.get() as i64),
+                plays: 0,
+            };
+            if moonbot_db::add_sound_clip(db, guild_id.get() as i64, &clip).await? {
+                let embed = serenity::CreateEmbed::new()
+                    .title("Clip added")
+                    .color(0x2ECC71)
+                    .field("Name", format!("`{}`", clip.name), true)
+                    .field(
+                        "Duration",
+                        format_duration(Duration::from_millis(clip.duration_ms as u64)).to_string(),
+                        true,
+                    )
+                    .field("Size", format!("{} KB", clip.size_bytes / 1024), true);
+                ctx.send(poise::CreateReply::default().embed(embed)).await?;
+                return Ok(());
+            }
+            Some(format!("`{}` already exists, remove it first.", name))
+        }
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    if let Some(problem) = problem {
+        send_err_msg(ctx, "Error", problem.as_str()).await;
+    }
+    Ok(())
+}
+
+/// Remove a clip from the soundboard
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Clip name"]
+    #[autocomplete = "autocomplete_clip"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = name.trim().to_lowercase();
+    let Some(clip) = moonbot_db::remove_sound_clip(ctx.data().db, guild_id.get() as i64, &name).await? else {
+        send_err_msg(ctx, "Error", format!("There's no clip called `{}`.", name).as_str()).await;
+        return Ok(());
+    };
+    let _ = tokio::fs::remove_file(soundboard::clip_path(guild_id, &clip)).await;
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Clip removed")
+        .color(0x2ECC71)
+        .description(format!("`{}`", clip.name));
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// List the clips on the soundboard
+#[poise::command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let clips = moonbot_db::sound_clips(ctx.data().db, guild_id.get() as i64).await?;
+
+    let mut description = String::new();
+    for (idx, clip) in clips.iter().enumerate() {
+        let line = format!(
+            "`{}` {} | {} plays\n",
+            clip.name,
+            format_duration(Duration::from_millis((clip.duration_ms as u64) / 100 * 100)),
+            clip.plays
+        );
+        if description.len() + line.len() > 4000 {
+            description.push_str(&format!("...and {} more", clips.len() - idx));
+            break;
+        }
+        description.push_str(&line);
+    }
+    if description.is_empty() {
+        description = "No clips yet, admins can add some with `/sound add`.".to_string();
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .title("Soundboard")
+        .color(0x2ECC71)
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+    Ok(())
+}
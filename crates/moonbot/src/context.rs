@@ -1,4 +1,9 @@
-use crate::Data;
+use crate::{
+	personas::{self, Source},
+	prompts::{self, Template},
+	Data,
+};
+use minijinja::context;
 use moonbot_db as db;
 use once_cell::sync::OnceCell;
 use std::sync::RwLock;
@@ -17,11 +22,60 @@ fn disposition_cache() -> &'static RwLock<Option<db::Disposition>> {
 pub async fn invalidate_global_context() { *global_ctx_cache().write().unwrap() = None; }
 pub async fn invalidate_disposition() { *disposition_cache().write().unwrap() = None; }
 
-/// Build the base system prompt using global/channel context, bot disposition, and user profile hints.
-pub async fn build_system_prompt(data: &Data, user_id: i64, user_name: &str) -> String {
+/// Prompt pieces to use instead of the saved ones, to try out other versions.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOverrides {
+	pub global_context: Option<Vec<String>>,
+	pub system_template: Option<String>,
+	pub persona_template: Option<String>,
+}
+
+/// Who and where a system prompt is built for.
+pub struct PromptScene<'a> {
+	pub user_id: i64,
+	pub user_name: &'a str,
+	pub guild_id: Option<i64>,
+	pub guild_name: String,
+	pub channel_name: String,
+	pub roleplay: Option<&'a Source>,
+	/// Retrieved corpus lines, see `retrieve`
+	pub retrieved: String,
+	pub overrides: PromptOverrides,
+}
+
+/// Look up corpus lines relevant to a message, scoped to its guild and channel.
+pub async fn retrieve(data: &Data, query: &str, guild_id: Option<i64>, channel_id: i64) -> String {
+	let mut retrieved = String::new();
+	if let Ok(hits) = db::search_corpus_fts(data.db, query, 6, guild_id, Some(channel_id)).await {
+		for h in hits.iter().take(6) { retrieved.push_str(&format!("- [{}] {}\n", h.kind, h.content)); }
+	}
+	retrieved
+}
+
+/// Render the roleplay part of the system prompt from the persona template.
+pub async fn persona_prompt(
+	data: &Data,
+	guild_id: Option<i64>,
+	source: &Source,
+	user_id: i64,
+	user_name: &str,
+	overrides: &PromptOverrides,
+) -> String {
+	let vars = context! {
+		persona => personas::prompt_fields(source, user_name),
+		user => context! { id => user_id, name => user_name },
+	};
+	prompts::render_for(data, guild_id, Template::Persona, overrides.persona_template.as_deref(), &vars).await
+}
+
+/// Build the system prompt from the system template, with global context, bot disposition,
+/// the user's profile and the roleplay in effect.
+pub async fn build_system_prompt(data: &Data, scene: &PromptScene<'_>) -> String {
 	// Global system context
 	let cached_ctx = { global_ctx_cache().read().unwrap().clone() };
-	let sys_ctx = if let Some(cached) = cached_ctx {
+	let sys_ctx = if let Some(lines) = &scene.overrides.global_context {
+		lines.clone()
+	} else if let Some(cached) = cached_ctx {
 		cached
 	} else {
 		let fresh = if let Some(db_ctx) = db::get_global_system_context(data.db).await {
@@ -44,30 +98,33 @@ pub async fn build_system_prompt(data: &Data, user_id: i64, user_name: &str) ->
 	};
 
 	// User profile
-	let profile = db::get_user_profile(data.db, user_id).await;
+	let profile = db::get_user_profile(data.db, scene.user_id).await;
 
-	let mut parts: Vec<String> = Vec::new();
-	if !sys_ctx.is_empty() {
-		parts.push(format!("### Global context\n{}", sys_ctx.join("\n")));
-	}
-	if let Some(d) = disposition {
-		parts.push(format!("### Bot disposition\nmood='{}' level={} notes={}", d.mood, d.mood_level, d.notes));
-	}
-	if let Some(p) = profile {
-		parts.push(format!(
-			"### User profile\nuser_id={} name={}\ntraits={:?}\ntrust_level={}\npreferences={}\nsummary={}",
-			user_id,
-			user_name,
-			p.traits,
-			p.trust_level,
-			p.preferences,
-			p.summary
-		));
-	}
-	// Base operating instruction
-	parts.push("### Operating rules\n- Be helpful, concise, and accurate.\n- If unsure, ask a brief clarifying question.\n- Avoid unsolicited replies and refrain from profanity or slurs.".to_string());
-
-	parts.join("\n\n")
+	let persona = match scene.roleplay {
+		Some(source) => {
+			persona_prompt(data, scene.guild_id, source, scene.user_id, scene.user_name, &scene.overrides).await
+		}
+		None => String::new(),
+	};
+	let persona_name = scene.roleplay.map(|source| personas::prompt_fields(source, scene.user_name).name);
+	let vars = context! {
+		user => context! { id => scene.user_id, name => scene.user_name },
+		summary => profile.as_ref().map(|p| p.summary.clone()),
+		profile => profile.map(|p| context! {
+			traits => p.traits,
+			trust_level => p.trust_level,
+			preferences => p.preferences.to_string(),
+		}),
+		mood => disposition.map(|d| context! { mood => d.mood, level => d.mood_level, notes => d.notes }),
+		persona,
+		persona_name,
+		global_context => sys_ctx,
+		guild => scene.guild_name,
+		channel => scene.channel_name,
+		time => chrono::Utc::now().format("%A %Y-%m-%d %H:%M UTC").to_string(),
+		context => scene.retrieved.trim(),
+	};
+	prompts::render_for(data, scene.guild_id, Template::System, scene.overrides.system_template.as_deref(), &vars).await
 }
 
 /// Compute generation parameters adjusted by user preferences and bot mood.
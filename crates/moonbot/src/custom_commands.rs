@@ -0,0 +1,147 @@
+use crate::{Data, Error};
+use moonbot_db::CustomCommand;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use regex::Regex;
+
+// Canned responses that server admins define with /cc.
+//
+// They live in the `custom_commands` table per guild and are run with the `~` prefix
+// (`~name args`, which poise hands over as an unknown command) or with /cc run. The
+// content is a template, see `render`, and can be sent as an embed.
+
+/// Longest content, Discord's limits for a message and an embed description.
+pub const MAX_CONTENT: usize = 2000;
+pub const MAX_EMBED_CONTENT: usize = 4096;
+
+static VARIABLE: OnceCell<Regex> = OnceCell::new();
+
+/// Names and aliases are typed after `~`, keep them short and simple.
+pub fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Fill in the template variables: `{user}` mentions the caller, `{user.name}` is their
+/// name, `{channel}` mentions the channel, `{server}` is the server's name, `{args}` is
+/// whatever followed the command and `{uses}` counts this use. All in one pass, so what
+/// users typed is never expanded itself.
+pub fn render(
+    command: &CustomCommand,
+    user: &serenity::User,
+    channel_id: serenity::ChannelId,
+    server: &str,
+    args: &str,
+) -> String {
+    let variable = VARIABLE.get_or_init(|| Regex::new(r"\{(user|user\.name|channel|server|args|uses)\}").unwrap());
+    variable
+        .replace_all(&command.content, |caps: &regex::Captures| match &caps[1] {
+            "user" => format!("<@{}>", user.id),
+            "user.name" => user.global_name.as_deref().unwrap_or(&user.name).to_string(),
+            "channel" => format!("<#{}>", channel_id),
+            "server" => server.to_string(),
+            "args" => args.trim().to_string(),
+            _ => (command.uses + 1).to_string(),
+        })
+        .into_owned()
+}
+
+/// Cut text to at most `max` characters.
+fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() > max {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// The reply for a rendered command, cut to fit. Only users can be pinged, `{args}` is
+/// untrusted.
+pub fn build_reply(command: &CustomCommand, text: String) -> poise::CreateReply {
+    let reply = poise::CreateReply::default()
+        .allowed_mentions(serenity::CreateAllowedMentions::new().all_users(true));
+    if command.embed {
+        let text = truncate(text, MAX_EMBED_CONTENT);
+        reply.embed(serenity::CreateEmbed::new().color(0x2ECC71).description(text))
+    } else {
+        reply.content(truncate(text, MAX_CONTENT))
+    }
+}
+
+/// Run `~name args` if `name` is a custom command here. Returns whether it was one.
+pub async fn run_prefixed(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+    invocation: &str,
+) -> Result<bool, Error> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(false);
+    };
+    if message.author.bot {
+        return Ok(false);
+    }
+    let (name, args) = invocation.trim().split_once(char::is_whitespace).unwrap_or((invocation.trim(), ""));
+    let db = framework.user_data.db;
+    let Some(command) = moonbot_db::find_custom_command(db, guild_id.get() as i64, &name.to_lowercase()).await? else {
+        return Ok(false);
+    };
+
+    let server = guild_id.name(&ctx.cache).unwrap_or_default();
+    let text = render(&command, &message.author, message.channel_id, &server, args);
+    let reply = build_reply(&command, text).to_prefix(message.into());
+    message.channel_id.send_message(ctx, reply).await?;
+    moonbot_db::record_custom_command_use(db, command.id).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(content: &str) -> CustomCommand {
+        CustomCommand {
+            id: 1,
+            guild_id: 1,
+            name: "test".to_string(),
+            content: content.to_string(),
+            embed: false,
+            aliases: Vec::new(),
+            uses: 4,
+            created_by: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn user() -> serenity::User {
+        let mut user = serenity::User::default();
+        user.id = serenity::UserId::new(42);
+        user.name = "moon".to_string();
+        user
+    }
+
+    #[test]
+    fn variables_are_filled_in_once() {
+        let text = render(
+            &command("{user} ({user.name}) in {channel} on {server}, use {uses}: {args} {nope}"),
+            &user(),
+            serenity::ChannelId::new(7),
+            "{args}",
+            " {user} ",
+        );
+        assert_eq!(text, "<@42> (moon) in <#7> on {args}, use 5: {user} {nope}");
+    }
+
+    #[test]
+    fn replies_are_cut_to_discords_limits() {
+        let text = "é".repeat(MAX_EMBED_CONTENT + 10);
+        let reply = build_reply(&command(""), text.clone());
+        let content = reply.content.unwrap();
+        assert_eq!(content.chars().count(), MAX_CONTENT);
+        assert!(content.ends_with('…'));
+        assert_eq!(truncate(text, MAX_EMBED_CONTENT).chars().count(), MAX_EMBED_CONTENT);
+        assert_eq!(truncate("short".to_string(), MAX_CONTENT), "short");
+    }
+}
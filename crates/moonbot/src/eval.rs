@@ -0,0 +1,621 @@
+use crate::{
+    context::{self, PromptOverrides, PromptScene},
+    handlers::openai::{self as generation, chat_messages, chat_name, Generation, Turn},
+    personas::{self, Format, Source},
+    prompt_history::{Kind, Target},
+    prompts::Template,
+    Data, Error,
+};
+use crate::llm_backend::{LlmBackend, MockBackend};
+use moonbot_db::PromptVersion;
+use serde::Deserialize;
+use std::sync::Arc;
+
+// Dry runs of prompts against stored test conversations.
+//
+// A fixture is a YAML or JSON file of conversations (see evals/example.yaml). Each one is
+// replayed through the same prompt building and generation as an auto reply, once for every
+// prompt variant: the prompts as they are now, or with an old version from /prompt-history
+// swapped in. Nothing is saved or sent to Discord. The replies can be scored by a judge model.
+// Runs from /prompt-eval or `moonbot eval <fixture>`.
+
+/// What the bot's own lines in fixtures are shown as, there's no Discord user to name them.
+const BOT_NAME: &str = "moonbot";
+
+/// Biggest fixture file.
+pub const MAX_FIXTURE: usize = 512 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct Fixture {
+    #[serde(default)]
+    pub name: String,
+    pub conversations: Vec<Conversation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Conversation {
+    pub name: String,
+    /// Who's talking to the bot
+    #[serde(default = "default_user")]
+    pub user: String,
+    #[serde(default)]
+    pub guild: String,
+    #[serde(default)]
+    pub channel: String,
+    /// Persona from the server's library to play
+    #[serde(default)]
+    pub persona: Option<String>,
+    pub messages: Vec<Line>,
+    /// What a good reply looks like, for the judge
+    #[serde(default)]
+    pub criteria: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Line {
+    #[serde(default)]
+    pub bot: bool,
+    /// Someone else than the conversation's user
+    #[serde(default)]
+    pub user: Option<String>,
+    pub content: String,
+}
+
+fn default_user() -> String {
+    "tester".to_string()
+}
+
+/// Parse a fixture, as JSON if the file name says so, otherwise as YAML.
+pub fn parse_fixture(file_name: &str, data: &[u8]) -> Result<Fixture, String> {
+    if data.len() > MAX_FIXTURE {
+        return Err(format!("fixtures can be at most {} KB", MAX_FIXTURE / 1024));
+    }
+    let text = std::str::from_utf8(data).map_err(|_| "the fixture isn't text".to_string())?;
+    let mut fixture: Fixture = if file_name.to_lowercase().ends_with(".json") {
+        serde_json::from_str(text).map_err(|e| e.to_string())?
+    } else {
+        serde_yaml::from_str(text).map_err(|e| e.to_string())?
+    };
+
+    if fixture.name.is_empty() {
+        let stem = std::path::Path::new(file_name).file_stem().and_then(|s| s.to_str());
+        fixture.name = stem.unwrap_or("fixture").to_string();
+    }
+    if fixture.conversations.is_empty() {
+        return Err("the fixture has no conversations".to_string());
+    }
+    for conversation in &fixture.conversations {
+        if conversation.messages.last().is_none_or(|line| line.bot) {
+            return Err(format!("`{}` has to end with a message for the bot to reply to", conversation.name));
+        }
+    }
+    Ok(fixture)
+}
+
+/// Fixture files in the configured directory.
+pub fn fixture_files(data: &Data) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(&data.config.openai.eval.fixtures) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| {
+            let name = name.to_lowercase();
+            name.ends_with(".yaml") || name.ends_with(".yml") || name.ends_with(".json")
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Load a fixture from the configured directory by file name.
+pub fn load_fixture(data: &Data, name: &str) -> Result<Fixture, String> {
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err("give a fixture's file name, without a path".to_string());
+    }
+    let path = std::path::Path::new(&data.config.openai.eval.fixtures).join(name);
+    let bytes = std::fs::read(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    parse_fixture(name, &bytes)
+}
+
+/// The prompts a run uses.
+pub enum Variant {
+    /// As they are saved now
+    Current,
+    /// With one old version from /prompt-history in place of the current one
+    Version(PromptVersion),
+}
+
+impl Variant {
+    /// Parse `current`, or a version id like `#12`, that the guild can see.
+    pub async fn parse(data: &Data, guild_id: Option<i64>, spec: &str) -> Result<Self, Error> {
+        let spec = spec.trim();
+        if spec.is_empty() || spec.eq_ignore_ascii_case("current") {
+            return Ok(Variant::Current);
+        }
+        let id: i64 = spec
+            .trim_start_matches('#')
+            .parse()
+            .map_err(|_| format!("`{}` isn't `current` or a version like #12", spec))?;
+        let version = moonbot_db::get_prompt_version(data.db, id)
+            .await?
+            .filter(|v| v.guild_id == 0 || Some(v.guild_id) == guild_id)
+            .ok_or_else(|| format!("there's no prompt version #{} here", id))?;
+        Ok(Variant::Version(version))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Variant::Current => "current".to_string(),
+            Variant::Version(version) => {
+                let target = Target::of(version).map(|t| t.describe()).unwrap_or_else(|| version.kind.clone());
+                format!("#{} ({})", version.id, target)
+            }
+        }
+    }
+}
+
+/// Which model replies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// The one generate_response would use
+    Configured,
+    /// The offline mock backend set up in [openai.mock], to check a fixture without any requests
+    Mock,
+}
+
+pub struct Options {
+    pub guild_id: Option<i64>,
+    pub model: Model,
+    pub judge: bool,
+}
+
+/// The replies to one conversation, one per variant.
+pub struct Outcome {
+    pub conversation: String,
+    pub transcript: String,
+    pub replies: Vec<Result<String, String>>,
+    pub scores: Vec<Option<f32>>,
+    pub reason: Option<String>,
+}
+
+pub struct Report {
+    pub fixture: String,
+    pub model: String,
+    pub variants: Vec<String>,
+    pub outcomes: Vec<Outcome>,
+    /// Conversations left out because of `max_conversations`
+    pub skipped: usize,
+}
+
+fn letter(i: usize) -> char {
+    (b'A' + i as u8) as char
+}
+
+impl Report {
+    /// Mean judge score of each variant, if any of its replies were scored.
+    pub fn averages(&self) -> Vec<Option<f32>> {
+        (0..self.variants.len())
+            .map(|i| {
+                let scores: Vec<f32> = self.outcomes.iter().filter_map(|o| o.scores.get(i).copied().flatten()).collect();
+                (!scores.is_empty()).then(|| scores.iter().sum::<f32>() / scores.len() as f32)
+            })
+            .collect()
+    }
+
+    pub fn failures(&self) -> Vec<usize> {
+        (0..self.variants.len())
+            .map(|i| self.outcomes.iter().filter(|o| o.replies.get(i).is_some_and(|r| r.is_err())).count())
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Prompt eval: {}\n\nModel: {}\n", self.fixture, self.model);
+        for (i, variant) in self.variants.iter().enumerate() {
+            out.push_str(&format!("- {}: {}\n", letter(i), variant));
+        }
+        if self.skipped > 0 {
+            out.push_str(&format!("\n{} more conversations were skipped.\n", self.skipped));
+        }
+
+        let averages = self.averages();
+        if averages.iter().any(|a| a.is_some()) {
+            let letters: Vec<String> = (0..self.variants.len()).map(|i| letter(i).to_string()).collect();
+            out.push_str(&format!("\n| Conversation | {} |\n", letters.join(" | ")));
+            out.push_str(&format!("|---|{}\n", "---|".repeat(letters.len())));
+            let cell = |score: Option<f32>| score.map(|s| format!("{:.1}", s)).unwrap_or_else(|| "-".to_string());
+            for outcome in &self.outcomes {
+                let cells: Vec<String> = outcome.scores.iter().map(|s| cell(*s)).collect();
+                out.push_str(&format!("| {} | {} |\n", outcome.conversation, cells.join(" | ")));
+            }
+            let cells: Vec<String> = averages.iter().map(|s| cell(*s)).collect();
+            out.push_str(&format!("| **Average** | {} |\n", cells.join(" | ")));
+        }
+
+        for outcome in &self.outcomes {
+            out.push_str(&format!("\n## {}\n\n", outcome.conversation));
+            for line in outcome.transcript.lines() {
+                out.push_str(&format!("> {}\n", line));
+            }
+            for (i, reply) in outcome.replies.iter().enumerate() {
+                let score = match outcome.scores.get(i).copied().flatten() {
+                    Some(score) => format!(" | {:.0}/10", score),
+                    None => String::new(),
+                };
+                out.push_str(&format!("\n**{}: {}**{}\n\n", letter(i), self.variants[i], score));
+                match reply {
+                    Ok(text) => out.push_str(&format!("{}\n", text.trim())),
+                    Err(e) => out.push_str(&format!("*Failed: {}*\n", e)),
+                }
+            }
+            if let Some(reason) = &outcome.reason {
+                out.push_str(&format!("\n*Judge: {}*\n", reason));
+            }
+        }
+        out
+    }
+}
+
+/// The prompt overrides and roleplay for a conversation with a variant.
+async fn setup(
+    data: &Data,
+    options: &Options,
+    variant: &Variant,
+    conversation: &Conversation,
+) -> Result<(PromptOverrides, Option<Source>), Error> {
+    let mut overrides = PromptOverrides::default();
+    let mut roleplay = match (&conversation.persona, options.guild_id) {
+        (Some(name), Some(guild_id)) => {
+            let persona = moonbot_db::get_persona(data.db, guild_id, name)
+                .await?
+                .ok_or_else(|| format!("there's no persona `{}` in the library", name))?;
+            Some(Source::Persona(Box::new(persona)))
+        }
+        (Some(_), None) => return Err("personas need a server, pass one with --guild".into()),
+        _ => None,
+    };
+
+    let Variant::Version(version) = variant else {
+        return Ok((overrides, roleplay));
+    };
+    let target = Target::of(version).ok_or("unknown kind of prompt")?;
+    let content = &version.content;
+    match target.kind {
+        Kind::Global => {
+            let lines = content.lines().map(|l| l.to_string()).filter(|l| !l.trim().is_empty()).collect();
+            overrides.global_context = Some(lines);
+        }
+        // A channel's roleplay text or a persona version stands in for whatever the
+        // conversation would play, like it does when it's active in a channel
+        Kind::Channel => roleplay = (!content.is_empty()).then(|| Source::Text(content.clone())),
+        Kind::Persona => {
+            roleplay = if content.is_empty() {
+                None
+            } else {
+                let mut file = personas::parse(content.as_bytes(), Format::Toml)?;
+                file.name = target.key.clone();
+                Some(Source::Persona(Box::new(personas::from_file(target.guild_id, file, None))))
+            };
+        }
+        Kind::Template => {
+            let template = if target.key == Template::Persona.key() { Template::Persona } else { Template::System };
+            let source = if content.is_empty() { template.config_source(data).to_string() } else { content.clone() };
+            match template {
+                Template::System => overrides.system_template = Some(source),
+                Template::Persona => overrides.persona_template = Some(source),
+            }
+        }
+    }
+    Ok((overrides, roleplay))
+}
+
+fn turns(conversation: &Conversation) -> Vec<Turn> {
+    conversation
+        .messages
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.bot {
+                return Turn { name: chat_name(BOT_NAME), from_bot: true, content: line.content.clone(), image_urls: vec![] };
+            }
+            let user = line.user.as_deref().unwrap_or(&conversation.user);
+            Turn { name: format!("{}__{}", chat_name(user), i + 1), from_bot: false, content: line.content.clone(), image_urls: vec![] }
+        })
+        .collect()
+}
+
+fn transcript(conversation: &Conversation) -> String {
+    conversation
+        .messages
+        .iter()
+        .map(|line| {
+            let name = if line.bot {
+                BOT_NAME.to_string()
+            } else {
+                line.user.clone().unwrap_or_else(|| conversation.user.clone())
+            };
+            format!("{}: {}", name, line.content.replace('\n', " "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn reply(
+    data: &Data,
+    llm: &dyn LlmBackend,
+    options: &Options,
+    variant: &Variant,
+    conversation: &Conversation,
+    turns: &[Turn],
+) -> Result<String, Error> {
+    let (overrides, roleplay) = setup(data, options, variant, conversation).await?;
+    let scene = PromptScene {
+        user_id: 0,
+        user_name: &conversation.user,
+        guild_id: options.guild_id,
+        guild_name: conversation.guild.clone(),
+        channel_name: conversation.channel.clone(),
+        roleplay: roleplay.as_ref(),
+        retrieved: String::new(),
+        overrides,
+    };
+    let system = context::build_system_prompt(data, &scene).await;
+    let messages = chat_messages(system, None, turns);
+    let persona = roleplay.as_ref().and_then(|s| s.persona());
+    let generation = generation::auto_generation(data, 0, persona, "eval".to_string()).await;
+    generation::complete(llm, &messages, &generation).await
+}
+
+/// Ask the judge model to score each reply from 1 to 10.
+async fn judge(
+    data: &Data,
+    llm: &dyn LlmBackend,
+    conversation: &Conversation,
+    transcript: &str,
+    replies: &[Result<String, String>],
+) -> Result<(Vec<Option<f32>>, String), Error> {
+    let criteria = if conversation.criteria.is_empty() {
+        "A natural, helpful reply that fits the conversation.".to_string()
+    } else {
+        conversation.criteria.clone()
+    };
+    let mut prompt = format!("Conversation:\n{}\n\nWhat a good reply looks like: {}\n", transcript, criteria);
+    for (i, reply) in replies.iter().enumerate() {
+        if let Ok(text) = reply {
+            prompt.push_str(&format!("\nReply {}:\n{}\n", letter(i), text));
+        }
+    }
+    let system = "You grade replies of a Discord chat bot to the last message of a conversation. Score every \
+                  reply from 1 to 10. Answer with JSON only, like {\"scores\": {\"A\": 7, \"B\": 4}, \"reason\": \
+                  \"one short sentence\"}.";
+    let messages = chat_messages(
+        system.to_string(),
+        None,
+        &[Turn { name: "eval".to_string(), from_bot: false, content: prompt, image_urls: vec![] }],
+    );
+    let eval = &data.config.openai.eval;
+    let model = if eval.judge_model.is_empty() { &data.config.openai.auto.model } else { &eval.judge_model };
+    let generation = Generation {
+        model: model.clone(),
+        max_tokens: 300,
+        temperature: 0.0,
+        frequency_penalty: 0.0,
+        user: "eval-judge".to_string(),
+    };
+    let answer = generation::complete(llm, &messages, &generation).await?;
+    parse_scores(&answer, replies.len())
+}
+
+/// Scores for `n` replies and the reason from a judge's answer. Scores are kept within 1 to
+/// 10, and a reply the judge didn't score has none.
+fn parse_scores(answer: &str, n: usize) -> Result<(Vec<Option<f32>>, String), Error> {
+    let json = match (answer.find('{'), answer.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &answer[start..=end],
+        _ => return Err(format!("the judge didn't answer with JSON: {}", answer).into()),
+    };
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let scores = (0..n)
+        .map(|i| value["scores"][letter(i).to_string()].as_f64().map(|s| s.clamp(1.0, 10.0) as f32))
+        .collect();
+    let reason = value["reason"].as_str().unwrap_or_default().to_string();
+    Ok((scores, reason))
+}
+
+/// Replay a fixture's conversations with every variant.
+pub async fn run(data: &Data, fixture: &Fixture, variants: &[Variant], options: &Options) -> Result<Report, Error> {
+    let llm: Arc<dyn LlmBackend> = match options.model {
+        Model::Configured => data.llm.clone().ok_or("OpenAI isn't configured, use the mock model")?,
+        Model::Mock => Arc::new(MockBackend::new(&data.config.openai.mock)),
+    };
+    let max = data.config.openai.eval.max_conversations;
+    let mut outcomes = vec![];
+    for conversation in fixture.conversations.iter().take(max) {
+        let turns = turns(conversation);
+        let transcript = transcript(conversation);
+        let mut replies = vec![];
+        for variant in variants {
+            replies.push(reply(data, llm.as_ref(), options, variant, conversation, &turns).await.map_err(|e| e.to_string()));
+        }
+
+        let (scores, reason) = if !options.judge || replies.iter().all(|r| r.is_err()) {
+            (vec![None; replies.len()], None)
+        } else {
+            match judge(data, llm.as_ref(), conversation, &transcript, &replies).await {
+                Ok((scores, reason)) => (scores, Some(reason)),
+                Err(e) => (vec![None; replies.len()], Some(format!("couldn't score: {}", e))),
+            }
+        };
+        outcomes.push(Outcome { conversation: conversation.name.clone(), transcript, replies, scores, reason });
+    }
+
+    let model = match options.model {
+        Model::Configured => format!("{} ({})", data.config.openai.auto.model, llm.name()),
+        Model::Mock => "mock".to_string(),
+    };
+    Ok(Report {
+        fixture: fixture.name.clone(),
+        model,
+        variants: variants.iter().map(|v| v.label()).collect(),
+        outcomes,
+        skipped: fixture.conversations.len().saturating_sub(max),
+    })
+}
+
+const USAGE: &str = "usage: moonbot eval <fixture> [--a VERSION] [--b VERSION] [--guild ID] [--judge] [--mock] [--out FILE]
+
+Replays the fixture's conversations with prompt version A (current by default) and, if given,
+version B, and prints a markdown report. Versions are `current` or ids from /prompt-history.";
+
+/// `moonbot eval`, the same as /prompt-eval from the command line.
+pub async fn cli(args: &[String]) -> Result<(), Error> {
+    let mut fixture = None;
+    let mut a = "current".to_string();
+    let mut b = None;
+    let mut guild_id = None;
+    let mut judge = false;
+    let mut model = Model::Configured;
+    let mut out = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value\n\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--a" => a = value()?,
+            "--b" => b = Some(value()?),
+            "--guild" => guild_id = Some(value()?.parse::<i64>()?),
+            "--judge" => judge = true,
+            "--mock" => model = Model::Mock,
+            "--out" => out = Some(value()?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if fixture.is_none() && !arg.starts_with("--") => fixture = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n\n{}", arg, USAGE).into()),
+        }
+    }
+    let fixture = fixture.ok_or(USAGE)?;
+
+    let config = moonbot_config::get_config();
+    moonbot_db::init_db(&config.database.url).await;
+    let data = Data {
+        config,
+        llm: crate::llm_backend::from_config(config),
+        music: None,
+        db: moonbot_db::get_db().await,
+    };
+
+    // A path, or a file in the fixtures directory
+    let fixture = match std::fs::read(&fixture) {
+        Ok(bytes) => parse_fixture(&fixture, &bytes)?,
+        Err(_) => load_fixture(&data, &fixture)?,
+    };
+    let mut variants = vec![Variant::parse(&data, guild_id, &a).await?];
+    if let Some(b) = b {
+        variants.push(Variant::parse(&data, guild_id, &b).await?);
+    }
+
+    let report = run(&data, &fixture, &variants, &Options { guild_id, model, judge }).await?;
+    let markdown = report.to_markdown();
+    match out {
+        Some(path) => {
+            std::fs::write(&path, markdown)?;
+            eprintln!("Wrote {}", path);
+        }
+        None => println!("{}", markdown),
+    }
+    for (i, variant) in report.variants.iter().enumerate() {
+        let average = report.averages()[i].map(|a| format!("{:.1}/10", a)).unwrap_or_else(|| "not scored".to_string());
+        eprintln!("{}: {} | {} | {} failed", letter(i), variant, average, report.failures()[i]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = "
+name: greetings
+conversations:
+  - name: hello
+    messages:
+      - content: hi there
+      - bot: true
+        content: hello!
+      - user: someone
+        content: how are you?
+    criteria: Friendly
+";
+
+    const JSON: &str = r#"{"conversations": [{"name": "hello", "messages": [{"content": "hi there"}]}]}"#;
+
+    #[test]
+    fn parses_yaml() {
+        let fixture = parse_fixture("greetings.yaml", YAML.as_bytes()).unwrap();
+        assert_eq!(fixture.name, "greetings");
+        let conversation = &fixture.conversations[0];
+        assert_eq!(conversation.user, "tester");
+        assert_eq!(conversation.messages.len(), 3);
+        assert!(conversation.messages[1].bot);
+        assert_eq!(conversation.messages[2].user.as_deref(), Some("someone"));
+        assert_eq!(conversation.criteria, "Friendly");
+    }
+
+    #[test]
+    fn json_by_file_name() {
+        // Named after the file when it has no name
+        let fixture = parse_fixture("Small.JSON", JSON.as_bytes()).unwrap();
+        assert_eq!(fixture.name, "Small");
+        assert_eq!(fixture.conversations[0].messages[0].content, "hi there");
+        // YAML is the default, and JSON is valid YAML
+        assert!(parse_fixture("small.yml", JSON.as_bytes()).is_ok());
+        assert!(parse_fixture("greetings.json", YAML.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn example_fixture_parses() {
+        let example = include_str!("../../../evals/example.yaml");
+        assert!(parse_fixture("example.yaml", example.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_fixtures() {
+        let ends_on_bot = "
+conversations:
+  - name: answered
+    messages:
+      - content: hi
+      - bot: true
+        content: hello!
+";
+        let err = parse_fixture("f.yaml", ends_on_bot.as_bytes()).unwrap_err();
+        assert!(err.contains("`answered`"), "{}", err);
+        assert!(parse_fixture("f.yaml", b"conversations:\n  - name: empty\n    messages: []\n").is_err());
+        assert!(parse_fixture("f.yaml", b"conversations: []\n").is_err());
+        assert!(parse_fixture("f.yaml", &[0xff, 0xfe]).is_err());
+        assert!(parse_fixture("f.yaml", &vec![b' '; MAX_FIXTURE + 1]).is_err());
+    }
+
+    #[test]
+    fn parses_scores() {
+        let answer = r#"Sure! {"scores": {"A": 7, "B": 4.5}, "reason": "A is friendlier"} Hope that helps."#;
+        let (scores, reason) = parse_scores(answer, 2).unwrap();
+        assert_eq!(scores, [Some(7.0), Some(4.5)]);
+        assert_eq!(reason, "A is friendlier");
+    }
+
+    #[test]
+    fn clamps_scores_and_leaves_missing_ones_out() {
+        let (scores, reason) = parse_scores(r#"{"scores": {"A": 14, "B": -2, "D": "8"}}"#, 4).unwrap();
+        assert_eq!(scores, [Some(10.0), Some(1.0), None, None]);
+        assert_eq!(reason, "");
+    }
+
+    #[test]
+    fn rejects_answers_without_json() {
+        assert!(parse_scores("A is better", 2).is_err());
+        assert!(parse_scores("} nope {", 2).is_err());
+        assert!(parse_scores("{not json}", 2).is_err());
+    }
+}
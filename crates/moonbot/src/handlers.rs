@@ -7,9 +7,16 @@ use moonbot_db::entities::prelude::*;
 
 use tracing::info;
 
-mod dad;
+pub mod auto_reply;
+pub mod autoplay;
+pub mod inactivity;
+pub mod intents;
 pub mod lavalink;
-mod openai;
+pub mod moderation;
+pub mod nodes;
+pub mod openai;
+pub mod responders;
+pub mod scheduler;
 
 pub async fn handler(
     ctx: &serenity::Context,
@@ -19,12 +26,19 @@ pub async fn handler(
 ) -> Result<(), Error> {
     match event {
         serenity::FullEvent::Message { new_message } => {
-            dad::handle_message(ctx, framework, new_message).await?;
+            responders::handle_message(ctx, framework, new_message).await?;
+            moderation::handle_message(ctx, framework, new_message).await?;
             // Always analyze to update personalization state
             openai::handle_analysis_only(ctx, framework, new_message).await?;
             openai::handle_random_message(ctx, framework, new_message).await?;
             openai::handle_reply(ctx, framework, new_message).await?;
         }
+        serenity::FullEvent::InteractionCreate { interaction: serenity::Interaction::Component(component) } => {
+            moderation::handle_component(ctx, framework, component).await?;
+        }
+        serenity::FullEvent::VoiceStateUpdate { old, new } => {
+            inactivity::handle_voice_state_update(ctx, framework, old.as_ref(), new).await?;
+        }
         serenity::FullEvent::ReactionAdd { add_reaction } => {
             // Adjust trust when users react to the bot's messages
             if let Some(user_id) = add_reaction.user_id {
@@ -84,9 +98,20 @@ pub async fn handler(
     Ok(())
 }
 
-pub async fn error_handler<U, E: std::fmt::Display + std::fmt::Debug>(
-    error: FrameworkError<'_, U, E>,
-) {
+pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
+    // `~name` that isn't a built-in command may be a custom one
+    if let FrameworkError::UnknownCommand { ctx, msg, msg_content, framework, trigger, .. } = &error {
+        if *trigger == poise::MessageDispatchTrigger::MessageCreate {
+            match crate::custom_commands::run_prefixed(ctx, *framework, msg, msg_content).await {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!("Custom command `{}` failed: {}", msg_content, e);
+                    return;
+                }
+            }
+        }
+    }
     if let Err(e) = poise_on_error(error).await {
         tracing::error!("Error while handling error: {}", e);
     }
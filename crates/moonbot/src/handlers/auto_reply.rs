@@ -0,0 +1,211 @@
+use super::intents::Matcher;
+use crate::{Data, Error};
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::warn;
+
+// Decides when the bot joins in on messages that aren't addressed to it.
+//
+// The chance, cooldown and thread setting come from `openai.auto.random`, overridden
+// per server and then per channel with /auto-reply. Triggers from /auto-reply-triggers
+// apply from both levels: a suppress trigger always wins, a force trigger replies
+// regardless of the chance and the usual cues. Threads use their parent's rules but
+// have their own cooldown.
+
+// Last random reply per channel, in unix seconds
+static LAST_RESPONSE: OnceCell<Mutex<HashMap<serenity::ChannelId, u64>>> = OnceCell::new();
+// Compiled triggers per guild, until /auto-reply-triggers changes them
+static TRIGGER_CACHE: OnceCell<RwLock<HashMap<serenity::GuildId, Arc<Vec<Trigger>>>>> = OnceCell::new();
+
+struct Trigger {
+    channel_id: i64,
+    effect: String,
+    matcher: Matcher,
+}
+
+fn trigger_cache() -> &'static RwLock<HashMap<serenity::GuildId, Arc<Vec<Trigger>>>> {
+    TRIGGER_CACHE.get_or_init(Default::default)
+}
+
+/// Compile a trigger the way messages are matched against it, so bad regexes can be
+/// turned away when they're added.
+pub fn compile_trigger(kind: &str, value: &str) -> Result<Matcher, regex::Error> {
+    match kind {
+        "regex" => Matcher::new(&[] as &[&str], &[value]),
+        _ => Matcher::new(&[value], &[] as &[&str]),
+    }
+}
+
+/// Forget a guild's compiled triggers after they changed.
+pub fn invalidate_triggers(guild_id: serenity::GuildId) {
+    trigger_cache().write().unwrap().remove(&guild_id);
+}
+
+async fn triggers(db: &DatabaseConnection, guild_id: serenity::GuildId) -> Arc<Vec<Trigger>> {
+    if let Some(cached) = trigger_cache().read().unwrap().get(&guild_id) {
+        return cached.clone();
+    }
+    let rows = match moonbot_db::auto_reply_triggers(db, guild_id.get() as i64).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            warn!("Failed to load auto reply triggers for guild {}: {}", guild_id, e);
+            return Arc::default();
+        }
+    };
+    let compiled = rows
+        .into_iter()
+        .filter_map(|t| match compile_trigger(&t.kind, &t.value) {
+            Ok(matcher) => Some(Trigger { channel_id: t.channel_id, effect: t.effect, matcher }),
+            // Only possible for triggers saved before they were checked
+            Err(e) => {
+                warn!("Ignoring auto reply {} `{}` in guild {}: {}", t.kind, t.value, guild_id, e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let compiled = Arc::new(compiled);
+    trigger_cache().write().unwrap().insert(guild_id, compiled.clone());
+    compiled
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// The channel whose rules apply, and whether the message is in a thread.
+fn rules_channel(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    channel_id: serenity::ChannelId,
+) -> (serenity::ChannelId, bool) {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return (channel_id, false);
+    };
+    match guild.threads.iter().find(|t| t.id == channel_id) {
+        Some(thread) => (thread.parent_id.unwrap_or(channel_id), true),
+        None => (channel_id, false),
+    }
+}
+
+/// Effective settings for a channel: the config, then server and channel overrides.
+pub async fn settings(
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    guild_id: serenity::GuildId,
+    channel_id: Option<serenity::ChannelId>,
+) -> (f64, u64, bool) {
+    let config = &framework.user_data.config.openai.auto.random;
+    let (mut chance, mut cooldown, mut threads) = (config.trigger_chance, config.cooldown, config.threads);
+    let levels = [Some(0), channel_id.map(|c| c.get() as i64)];
+    for channel in levels.into_iter().flatten() {
+        let o = moonbot_db::get_auto_reply_settings(framework.user_data.db, guild_id.get() as i64, channel).await;
+        chance = o.chance.unwrap_or(chance);
+        cooldown = o.cooldown_secs.map(|s| s.max(0) as u64).unwrap_or(cooldown);
+        threads = o.threads.unwrap_or(threads);
+    }
+    (chance, cooldown, threads)
+}
+
+/// Whether to reply to a message nobody asked the bot about.
+pub async fn should_reply(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+) -> bool {
+    let config = &framework.user_data.config.openai.auto.random;
+    let (mut chance, mut cooldown, mut threads) = (config.trigger_chance, config.cooldown, config.threads);
+    let mut in_thread = false;
+    let mut forced = false;
+
+    if let Some(guild_id) = message.guild_id {
+        let (channel_id, thread) = rules_channel(ctx, guild_id, message.channel_id);
+        in_thread = thread;
+        (chance, cooldown, threads) = settings(framework, guild_id, Some(channel_id)).await;
+
+        let triggers = triggers(framework.user_data.db, guild_id).await;
+        let matches = |effect: &str| {
+            triggers
+                .iter()
+                .filter(|t| t.effect == effect && (t.channel_id == 0 || t.channel_id == channel_id.get() as i64))
+                .any(|t| t.matcher.is_match(&message.content))
+        };
+        if matches("suppress") {
+            return false;
+        }
+        forced = matches("force");
+    }
+
+    if in_thread && !threads {
+        return false;
+    }
+
+    // Check Cooldown
+    let last = LAST_RESPONSE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .get(&message.channel_id)
+        .copied()
+        .unwrap_or(0);
+    if last + cooldown > now_secs() {
+        return false;
+    }
+    if forced {
+        return true;
+    }
+
+    // Message must be longer than min length
+    if message.content.len() < config.min_length as usize {
+        return false;
+    }
+
+    // Heuristic: only consider random replies if user shows intent (a question or actionable cue)
+    let lc = message.content.to_lowercase();
+    let looks_like_question = lc.contains('?');
+    let has_cue = config.cue_words.is_empty() || config.cue_words.iter().any(|w| lc.contains(&w.to_lowercase()));
+    if !(looks_like_question || has_cue) {
+        return false;
+    }
+
+    // Roll the dice
+    rand::rng().random::<f64>() < chance
+}
+
+/// Start the cooldown after replying in a channel.
+pub fn mark_replied(channel_id: serenity::ChannelId) {
+    LAST_RESPONSE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(channel_id, now_secs());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_match_whole_words_in_any_case() {
+        let matcher = compile_trigger("keyword", "pizza").unwrap();
+        assert!(matcher.is_match("Who wants PIZZA?"));
+        assert!(!matcher.is_match("pizzas are great"));
+        // Keywords are literal text, not patterns
+        let matcher = compile_trigger("keyword", "c++ (").unwrap();
+        assert!(matcher.is_match("I love c++ (really)"));
+        assert!(!matcher.is_match("cpp"));
+    }
+
+    #[test]
+    fn regexes_are_checked_and_ignore_case() {
+        let matcher = compile_trigger("regex", r"^!\w+").unwrap();
+        assert!(matcher.is_match("!ROLL d20"));
+        assert!(!matcher.is_match("say !roll"));
+        assert!(compile_trigger("regex", "(unclosed").is_err());
+    }
+}
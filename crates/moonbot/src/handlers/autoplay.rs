@@ -0,0 +1,245 @@
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequestArgs,
+};
+use crate::llm_backend::LlmBackend;
+use crate::music_backend::MusicBackend;
+use lavalink_rs::model::track::TrackData;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+// Keeps music going when the queue runs out by queueing related tracks, either from
+// YouTube's mix playlists ("related") or by asking the chat model ("dj").
+struct Autoplay {
+    music: Arc<dyn MusicBackend>,
+    llm: Option<Arc<dyn LlmBackend>>,
+    bot_id: serenity::UserId,
+    // Tracks queued by autoplay since a person last queued something
+    streak: Mutex<HashMap<serenity::GuildId, u32>>,
+}
+
+static AUTOPLAY: OnceCell<Autoplay> = OnceCell::new();
+
+/// Set up autoplay, call once the client is ready.
+pub fn init(
+    music: Arc<dyn MusicBackend>,
+    llm: Option<Arc<dyn LlmBackend>>,
+    bot_id: serenity::UserId,
+) {
+    let _ = AUTOPLAY.set(Autoplay {
+        music,
+        llm,
+        bot_id,
+        streak: Mutex::new(HashMap::new()),
+    });
+}
+
+/// Someone queued a track themselves, so autoplay may run for a full streak again.
+pub fn reset(guild_id: serenity::GuildId) {
+    if let Some(state) = AUTOPLAY.get() {
+        state.streak.lock().unwrap().remove(&guild_id);
+    }
+}
+
+/// Whether a track was queued by autoplay rather than a person.
+pub fn is_autoplayed(track: &TrackData) -> bool {
+    track
+        .user_data
+        .as_ref()
+        .and_then(|data| data.get("autoplay"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Refill the queue after `ended` finished or was skipped, if autoplay is on for the guild.
+pub async fn handle_track_end(guild_id: serenity::GuildId, ended: &TrackData) {
+    let Some(state) = AUTOPLAY.get() else { return };
+    let music = state.music.as_ref();
+    // Nothing to do once the bot left, or while there's still something queued
+    match music.queue(guild_id).await {
+        Ok(queue) if queue.is_empty() => {}
+        _ => return,
+    }
+
+    let db = moonbot_db::get_db().await;
+    let settings = moonbot_db::get_music_settings(db, guild_id.get() as i64).await;
+    if !settings.autoplay {
+        return;
+    }
+
+    let config = &moonbot_config::get_config().music.autoplay;
+    let streak = state.streak.lock().unwrap().get(&guild_id).copied().unwrap_or(0);
+    if streak >= config.max_tracks {
+        info!("Autoplay limit reached in guild {}", guild_id);
+        return;
+    }
+    let wanted = config.batch_size.min(config.max_tracks - streak).max(1) as usize;
+
+    // Never repeat anything from the recent history
+    let history = moonbot_db::recent_plays(db, guild_id.get() as i64, config.history_window)
+        .await
+        .unwrap_or_default();
+    let mut seen: HashSet<String> = history
+        .iter()
+        .filter_map(|p| p.uri.clone())
+        .collect();
+    seen.extend(ended.info.uri.clone());
+
+    let candidates = match settings.autoplay_mode.as_str() {
+        "dj" => dj_candidates(state, guild_id, &history, wanted).await,
+        _ => related_candidates(music, guild_id, ended).await,
+    };
+
+    // Autoplay obeys the blocklist and length limit like everyone else
+    let policy = crate::queue_policy::QueuePolicy::load(db, guild_id).await;
+
+    let mut tracks: Vec<TrackData> = Vec::new();
+    for mut track in candidates {
+        if tracks.len() >= wanted {
+            break;
+        }
+        let Some(uri) = track.info.uri.clone() else { continue };
+        if track.info.is_stream || policy.check_content(&track).is_some() || !seen.insert(uri) {
+            continue;
+        }
+        track.user_data = Some(serde_json::json!({
+            "requester_id": state.bot_id.get(),
+            "autoplay": true,
+        }));
+        tracks.push(track);
+    }
+
+    if tracks.is_empty() {
+        debug!("Autoplay found nothing new to queue in guild {}", guild_id);
+        return;
+    }
+
+    info!("Autoplay queueing {} track(s) in guild {}", tracks.len(), guild_id);
+    *state.streak.lock().unwrap().entry(guild_id).or_default() += tracks.len() as u32;
+    // Nothing is playing any more, so this starts the first one
+    if let Err(e) = music.enqueue(guild_id, tracks).await {
+        warn!("Autoplay failed to queue tracks in guild {}: {}", guild_id, e);
+    }
+}
+
+/// YouTube's radio mix for the track that just ended. Backends that can't search
+/// YouTube find nothing.
+async fn related_candidates(
+    music: &dyn MusicBackend,
+    guild_id: serenity::GuildId,
+    seed: &TrackData,
+) -> Vec<TrackData> {
+    // Mixes only exist for YouTube videos; find the closest one for other sources
+    let video_id = if seed.info.source_name == "youtube" {
+        Some(seed.info.identifier.clone())
+    } else {
+        let term = format!("{} {}", seed.info.author, seed.info.title);
+        match music.resolve(guild_id, &term).await {
+            Ok(resolved) => resolved.tracks.first().map(|t| t.info.identifier.clone()),
+            Err(e) => {
+                debug!("Autoplay couldn't find {} on YouTube: {}", term, e);
+                None
+            }
+        }
+    };
+    let Some(video_id) = video_id else {
+        return vec![];
+    };
+
+    let mix = format!("https://www.youtube.com/watch?v={0}&list=RD{0}", video_id);
+    match music.resolve(guild_id, &mix).await {
+        Ok(resolved) if resolved.playlist.is_some() => resolved
+            .tracks
+            .into_iter()
+            .filter(|t| t.info.identifier != video_id)
+            .collect(),
+        Ok(_) => vec![],
+        Err(e) => {
+            warn!("Failed to load YouTube mix {}: {}", mix, e);
+            vec![]
+        }
+    }
+}
+
+/// Ask the chat model for songs like the last few played, then resolve them through the
+/// music backend.
+async fn dj_candidates(
+    state: &Autoplay,
+    guild_id: serenity::GuildId,
+    history: &[moonbot_db::PlayHistoryEntry],
+    wanted: usize,
+) -> Vec<TrackData> {
+    let Some(llm) = state.llm.as_ref() else {
+        warn!("Autoplay DJ mode needs OpenAI to be configured");
+        return vec![];
+    };
+    let config = moonbot_config::get_config();
+    let model = if config.music.autoplay.model.is_empty() {
+        config.openai.auto.model.as_str()
+    } else {
+        config.music.autoplay.model.as_str()
+    };
+
+    let recent = history
+        .iter()
+        .take(5)
+        .map(|p| format!("{} - {}", p.author, p.title))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if recent.is_empty() {
+        return vec![];
+    }
+
+    let request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .max_tokens(200u32)
+        .messages(vec![
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content("You are a radio DJ picking the next songs. Reply with one song per line formatted as 'Artist - Title'. No numbering, commentary or songs from the list you were given.")
+                .build()
+                .unwrap()
+                .into(),
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(format!("Suggest {} songs like these recently played ones:\n{}", wanted * 2, recent))
+                .build()
+                .unwrap()
+                .into(),
+        ])
+        .build();
+    let Ok(request) = request else {
+        return vec![];
+    };
+
+    let reply = match llm.chat(request).await {
+        Ok(r) => r
+            .choices
+            .first()
+            .and_then(|c| c.message.content.clone())
+            .unwrap_or_default(),
+        Err(e) => {
+            warn!("Autoplay DJ request failed: {}", e);
+            return vec![];
+        }
+    };
+
+    let mut out = Vec::new();
+    for line in reply.lines() {
+        let term = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | '-' | '*' | ')'))
+            .trim();
+        if term.is_empty() {
+            continue;
+        }
+        if let Ok(resolved) = state.music.resolve(guild_id, term).await {
+            out.extend(resolved.tracks.into_iter().next());
+        }
+        if out.len() >= wanted * 2 {
+            break;
+        }
+    }
+    out
+}
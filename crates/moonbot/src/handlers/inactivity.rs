@@ -0,0 +1,196 @@
+use crate::{music_backend::MusicBackend, utils::voice_listeners, Data, Error};
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// Leaves voice after a period with nobody listening or nothing playing.
+//
+// Triggers come from Discord (VoiceStateUpdate) and the music backend (track start/end).
+// Each guild has at most one pending timer; when it fires the conditions are
+// checked again, so stale timers are harmless.
+struct Inactivity {
+    songbird: Arc<songbird::Songbird>,
+    music: Arc<dyn MusicBackend>,
+    cache: Arc<serenity::Cache>,
+    timers: Mutex<HashMap<serenity::GuildId, tokio::task::JoinHandle<()>>>,
+    // Guilds we paused because the channel emptied, so we know to resume them
+    auto_paused: Mutex<HashSet<serenity::GuildId>>,
+}
+
+static INACTIVITY: OnceCell<Inactivity> = OnceCell::new();
+
+/// Set up the inactivity manager, call once the client is ready.
+pub fn init(songbird: Arc<songbird::Songbird>, cache: Arc<serenity::Cache>, music: Arc<dyn MusicBackend>) {
+    let _ = INACTIVITY.set(Inactivity {
+        songbird,
+        music,
+        cache,
+        timers: Mutex::new(HashMap::new()),
+        auto_paused: Mutex::new(HashSet::new()),
+    });
+}
+
+/// Number of non-bot users in the bot's voice channel, None if the bot isn't in one.
+fn listener_count(cache: &serenity::Cache, guild_id: serenity::GuildId) -> Option<usize> {
+    voice_listeners(cache, guild_id).map(|l| l.len())
+}
+
+async fn is_idle(music: &dyn MusicBackend, guild_id: serenity::GuildId) -> bool {
+    let playing = music.now_playing(guild_id).await.ok().flatten().is_some();
+    !playing && music.queue(guild_id).await.map(|q| q.is_empty()).unwrap_or(true)
+}
+
+fn cancel(guild_id: serenity::GuildId) {
+    let Some(state) = INACTIVITY.get() else { return };
+    if let Some(handle) = state.timers.lock().unwrap().remove(&guild_id) {
+        debug!("Cancelled inactivity timer for guild {}", guild_id);
+        handle.abort();
+    }
+}
+
+fn schedule(guild_id: serenity::GuildId) {
+    let Some(state) = INACTIVITY.get() else { return };
+    let timeout = moonbot_config::get_config().music.inactivity_timeout;
+    if timeout == 0 {
+        return;
+    }
+
+    let mut timers = state.timers.lock().unwrap();
+    if timers.get(&guild_id).is_some_and(|h| !h.is_finished()) {
+        return;
+    }
+    debug!("Scheduled inactivity timer for guild {} in {}s", guild_id, timeout);
+    timers.insert(
+        guild_id,
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(timeout)).await;
+            expire(guild_id).await;
+        }),
+    );
+}
+
+async fn expire(guild_id: serenity::GuildId) {
+    let Some(state) = INACTIVITY.get() else { return };
+    state.timers.lock().unwrap().remove(&guild_id);
+
+    let music = state.music.as_ref();
+    if !music.is_connected(guild_id) {
+        return;
+    }
+
+    let db = moonbot_db::get_db().await;
+    if moonbot_db::get_music_settings(db, guild_id.get() as i64).await.stay_connected {
+        return;
+    }
+
+    let empty = listener_count(&state.cache, guild_id).unwrap_or(0) == 0;
+    let reason = if empty {
+        "Left the voice channel because nobody was listening."
+    } else if is_idle(music, guild_id).await {
+        "Left the voice channel because nothing was playing."
+    } else {
+        return;
+    };
+
+    info!("Leaving voice in guild {} due to inactivity", guild_id);
+    // Leaving forgets the channel, so look it up first
+    let text_channel = music.text_channel(guild_id);
+
+    if let Err(e) = music.leave(guild_id).await {
+        warn!("Failed to stop the player in guild {}: {}", guild_id, e);
+    }
+    if state.songbird.get(guild_id).is_some() {
+        if let Err(e) = state.songbird.remove(guild_id).await {
+            warn!("Failed to leave voice in guild {}: {}", guild_id, e);
+        }
+    }
+    state.auto_paused.lock().unwrap().remove(&guild_id);
+
+    if let Some((channel_id, http)) = text_channel {
+        let embed = serenity::CreateEmbed::new()
+            .author(serenity::CreateEmbedAuthor::new("Disconnected"))
+            .color(0x2ECC71)
+            .description(reason);
+        let _ = channel_id
+            .send_message(&http, serenity::CreateMessage::new().embed(embed))
+            .await;
+    }
+}
+
+/// Pause/schedule when the bot's channel empties, resume/cancel when someone comes back.
+pub async fn handle_voice_state_update(
+    _ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
+) -> Result<(), Error> {
+    let Some(state) = INACTIVITY.get() else { return Ok(()) };
+    let Some(guild_id) = new.guild_id.or_else(|| old.and_then(|o| o.guild_id)) else {
+        return Ok(());
+    };
+
+    // The bot itself was disconnected from voice
+    if new.user_id == framework.bot_id && new.channel_id.is_none() {
+        cancel(guild_id);
+        state.auto_paused.lock().unwrap().remove(&guild_id);
+        return Ok(());
+    }
+
+    let music = state.music.as_ref();
+    if !music.is_connected(guild_id) {
+        return Ok(());
+    }
+    let Some(listeners) = listener_count(&state.cache, guild_id) else {
+        return Ok(());
+    };
+
+    if listeners == 0 {
+        if framework.user_data.config.music.pause_when_empty {
+            let playing = music.now_playing(guild_id).await.ok().flatten();
+            if playing.is_some_and(|p| !p.paused) {
+                music.set_pause(guild_id, true).await?;
+                state.auto_paused.lock().unwrap().insert(guild_id);
+            }
+        }
+        schedule(guild_id);
+    } else {
+        if state.auto_paused.lock().unwrap().remove(&guild_id) {
+            music.set_pause(guild_id, false).await?;
+        }
+        if !is_idle(music, guild_id).await {
+            cancel(guild_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// A track started, so the player is no longer idle.
+pub fn handle_track_start(guild_id: serenity::GuildId) {
+    let Some(state) = INACTIVITY.get() else { return };
+    if listener_count(&state.cache, guild_id).unwrap_or(0) > 0 {
+        cancel(guild_id);
+    }
+}
+
+/// A track ended; if the queue is exhausted start counting down.
+pub async fn handle_track_end(guild_id: serenity::GuildId) {
+    let Some(state) = INACTIVITY.get() else { return };
+    if !state.music.is_connected(guild_id) {
+        return;
+    }
+    if state.music.queue(guild_id).await.map(|q| q.is_empty()).unwrap_or(true) {
+        schedule(guild_id);
+    }
+}
+
+/// Stop tracking a guild, e.g. after an explicit leave.
+pub fn forget(guild_id: serenity::GuildId) {
+    cancel(guild_id);
+    if let Some(state) = INACTIVITY.get() {
+        state.auto_paused.lock().unwrap().remove(&guild_id);
+    }
+}
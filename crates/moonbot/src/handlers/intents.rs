@@ -0,0 +1,259 @@
+use crate::{llm_backend::LlmBackend, Data, Error};
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequestArgs, ResponseFormat, ResponseFormatJsonSchema,
+};
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use regex::Regex;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+mod misname;
+mod remind;
+mod simple;
+mod tag;
+
+// Replies to mentions go through intents before the bot falls back to a chat reply.
+//
+// Every intent has rules (keywords and patterns) and a priority. The router tries the
+// intents whose rules match, highest priority first, until one handles the message.
+// If none did and `openai.intents.classifier_model` is set, a cheap model may pick one
+// of the intents that describe themselves. Simple intents with a fixed reply come from
+// `openai.intents.simple` in the config.
+
+/// What an intent did with a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The message was dealt with, nothing else should reply
+    Handled,
+    /// Not for this intent after all, try the next one
+    Pass,
+}
+
+#[async_trait]
+pub trait Intent: Send + Sync {
+    /// Name used in logs and by the classifier
+    fn name(&self) -> &str;
+
+    /// Higher priorities are tried first.
+    fn priority(&self) -> i32;
+
+    /// Rule-based check of the message text, without the bot's mention.
+    fn matches(&self, text: &str) -> bool;
+
+    /// What the intent is for. Intents with a description may be picked by the
+    /// classifier when no rule matched.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
+    async fn handle(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+        text: &str,
+    ) -> Result<Outcome, Error>;
+}
+
+/// Keywords and regular expressions, all case-insensitive.
+pub struct Matcher {
+    patterns: Vec<Regex>,
+}
+
+impl Matcher {
+    pub fn new<K: AsRef<str>, P: AsRef<str>>(keywords: &[K], patterns: &[P]) -> Result<Self, regex::Error> {
+        let mut compiled = Vec::new();
+        if !keywords.is_empty() {
+            // Word boundaries only make sense next to word characters, so "c++" still
+            // matches at the end of a sentence
+            let words = keywords
+                .iter()
+                .map(|k| {
+                    let k = k.as_ref().trim();
+                    let edge = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                    let start = if edge(k.chars().next()) { r"\b" } else { "" };
+                    let end = if edge(k.chars().last()) { r"\b" } else { "" };
+                    format!("{}{}{}", start, regex::escape(k), end)
+                })
+                .collect::<Vec<_>>()
+                .join("|");
+            compiled.push(Regex::new(&format!("(?i)(?:{})", words))?);
+        }
+        for pattern in patterns {
+            compiled.push(Regex::new(&format!("(?i){}", pattern.as_ref()))?);
+        }
+        Ok(Matcher { patterns: compiled })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.patterns.iter().any(|p| p.is_match(text))
+    }
+
+    /// Capture groups of the first expression that matches.
+    pub fn captures<'t>(&self, text: &'t str) -> Option<regex::Captures<'t>> {
+        self.patterns.iter().find_map(|p| p.captures(text))
+    }
+}
+
+struct Router {
+    // Sorted by priority, highest first
+    intents: Vec<Box<dyn Intent>>,
+    llm: Option<Arc<dyn LlmBackend>>,
+}
+
+static ROUTER: OnceCell<Router> = OnceCell::new();
+
+/// Set up the built-in and configured intents, call once the client is ready.
+pub fn init(llm: Option<Arc<dyn LlmBackend>>) {
+    let mut intents: Vec<Box<dyn Intent>> = vec![
+        Box::new(tag::Tag::new()),
+        Box::new(remind::Remind::new()),
+        Box::new(misname::Misname::new()),
+    ];
+    for config in &moonbot_config::get_config().openai.intents.simple {
+        match simple::Simple::new(config.clone()) {
+            Ok(intent) => intents.push(Box::new(intent)),
+            Err(e) => warn!("Skipping intent {}: {}", config.name, e),
+        }
+    }
+    intents.sort_by_key(|intent| -intent.priority());
+    info!(
+        "Intents: {}",
+        intents.iter().map(|i| i.name()).collect::<Vec<_>>().join(", ")
+    );
+    let _ = ROUTER.set(Router {
+        intents,
+        llm,
+    });
+}
+
+/// Give a message addressed to the bot to the intents. Returns whether one handled it.
+pub async fn route(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+) -> Result<bool, Error> {
+    let Some(router) = ROUTER.get() else {
+        return Ok(false);
+    };
+    let text = message
+        .content
+        .replace(&format!("<@{}>", framework.bot_id), "")
+        .replace(&format!("<@!{}>", framework.bot_id), "");
+    let text = text.trim();
+
+    let mut tried = Vec::new();
+    for (i, intent) in router.intents.iter().enumerate() {
+        if !intent.matches(text) {
+            continue;
+        }
+        debug!("Intent {} matched message {}", intent.name(), message.id);
+        if intent.handle(ctx, framework, message, text).await? == Outcome::Handled {
+            return Ok(true);
+        }
+        tried.push(i);
+    }
+
+    let candidates = router
+        .intents
+        .iter()
+        .enumerate()
+        .filter(|(i, intent)| !tried.contains(i) && intent.description().is_some())
+        .map(|(_, intent)| intent.as_ref())
+        .collect::<Vec<_>>();
+    let Some(intent) = classify(router, &candidates, text).await else {
+        return Ok(false);
+    };
+    debug!("Intent {} classified for message {}", intent.name(), message.id);
+    Ok(intent.handle(ctx, framework, message, text).await? == Outcome::Handled)
+}
+
+/// Ask the classifier model which of `candidates` the text is, if any.
+async fn classify<'a>(router: &Router, candidates: &[&'a dyn Intent], text: &str) -> Option<&'a dyn Intent> {
+    let model = &moonbot_config::get_config().openai.intents.classifier_model;
+    let llm = router.llm.as_ref()?;
+    if model.is_empty() || candidates.is_empty() {
+        return None;
+    }
+
+    let mut names = candidates.iter().map(|i| i.name()).collect::<Vec<_>>();
+    names.push("none");
+    let list = candidates
+        .iter()
+        .map(|i| format!("- {}: {}", i.name(), i.description().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let request = CreateChatCompletionRequestArgs::default()
+        .model(model.as_str())
+        .max_tokens(20u32)
+        .response_format(ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description: None,
+                name: "intent".to_string(),
+                schema: Some(serde_json::json!({
+                    "type": "object",
+                    "properties": { "intent": { "type": "string", "enum": names } },
+                    "required": ["intent"],
+                    "additionalProperties": false
+                })),
+                strict: Some(true),
+            },
+        })
+        .messages(vec![
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(format!(
+                    "Classify what the user wants from a chat bot. Answer \"none\" unless the message \
+                     clearly is one of these:\n{}",
+                    list
+                ))
+                .build()
+                .ok()?
+                .into(),
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(text)
+                .build()
+                .ok()?
+                .into(),
+        ])
+        .build()
+        .ok()?;
+
+    let response = match llm.chat(request).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Intent classification failed: {}", e);
+            return None;
+        }
+    };
+    let json = response.choices.first()?.message.content.as_deref()?;
+    let name = serde_json::from_str::<serde_json::Value>(json).ok()?["intent"]
+        .as_str()?
+        .to_string();
+    candidates.iter().find(|i| i.name() == name).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let matcher = Matcher::new(&["oaf", "c++", "#help"], &[] as &[&str]).unwrap();
+        assert!(matcher.is_match("you OAF"));
+        assert!(!matcher.is_match("a loaf of bread"));
+        assert!(matcher.is_match("I write c++"));
+        assert!(matcher.is_match("c++, mostly"));
+        assert!(matcher.is_match("see #help"));
+        assert!(!matcher.is_match("see #helpdesk"));
+    }
+
+    #[test]
+    fn patterns_match_anywhere() {
+        let matcher = Matcher::new(&[] as &[&str], &["stupid|oaf"]).unwrap();
+        assert!(matcher.is_match("Stupidity"));
+        assert!(matcher.is_match("a loaf of bread"));
+    }
+}
@@ -0,0 +1,46 @@
+use super::{Intent, Matcher, Outcome};
+use crate::handlers::openai::generate_response_with;
+use crate::{Data, Error};
+use async_trait::async_trait;
+use poise::serenity_prelude as serenity;
+
+const INSTRUCTION: &str = "If the user refers to you as 'Sunbot', respond with exactly one curt sentence that corrects the name to 'Moonbot'. Use 2-6 words. No emojis or flourish. Output only that sentence—nothing else. Keep it PG-13, no profanity or slurs, and do not target or insult any person or group.";
+
+/// Being called by the old name gets a curt correction instead of a normal reply.
+pub struct Misname {
+    matcher: Matcher,
+}
+
+impl Misname {
+    pub fn new() -> Self {
+        Misname {
+            matcher: Matcher::new(&[] as &[&str], &["sunbot"]).unwrap(),
+        }
+    }
+}
+
+#[async_trait]
+impl Intent for Misname {
+    fn name(&self) -> &str {
+        "sunbot"
+    }
+
+    fn priority(&self) -> i32 {
+        10
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        self.matcher.is_match(text)
+    }
+
+    async fn handle(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+        _text: &str,
+    ) -> Result<Outcome, Error> {
+        generate_response_with(ctx, framework, message, Some(INSTRUCTION)).await?;
+        Ok(Outcome::Handled)
+    }
+}
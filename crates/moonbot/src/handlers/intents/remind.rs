@@ -0,0 +1,178 @@
+use super::{Intent, Matcher, Outcome};
+use crate::handlers::scheduler::{add_reminder, parse_when, user_timezone};
+use crate::{Data, Error};
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequestArgs, ResponseFormat, ResponseFormatJsonSchema,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use poise::serenity_prelude as serenity;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+// "@moonbot remind me tomorrow at 9 to file the report"
+//
+// The message goes through the chat model with a JSON schema, so the model only
+// extracts the time and the text. The time comes back as local wall-clock time and
+// goes through the same parsing and checks as /remind.
+
+#[derive(Debug, Deserialize)]
+struct Extraction {
+    is_reminder: bool,
+    when: String,
+    text: String,
+}
+
+fn schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "is_reminder": {
+                "type": "boolean",
+                "description": "Whether the user asks to be reminded of something"
+            },
+            "when": {
+                "type": "string",
+                "description": "When to remind, as local time in the format YYYY-MM-DD HH:MM, or empty if no time was given"
+            },
+            "text": {
+                "type": "string",
+                "description": "What to remind the user of, phrased as a short note to them"
+            }
+        },
+        "required": ["is_reminder", "when", "text"],
+        "additionalProperties": false
+    })
+}
+
+pub struct Remind {
+    matcher: Matcher,
+}
+
+impl Remind {
+    pub fn new() -> Self {
+        Remind {
+            matcher: Matcher::new(&[] as &[&str], &[r"\bremind"]).unwrap(),
+        }
+    }
+}
+
+#[async_trait]
+impl Intent for Remind {
+    fn name(&self) -> &str {
+        "remind"
+    }
+
+    fn priority(&self) -> i32 {
+        40
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        self.matcher.is_match(text)
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Asks to be reminded of something at a later time")
+    }
+
+    async fn handle(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+        content: &str,
+    ) -> Result<Outcome, Error> {
+        let Some(llm) = framework.user_data.llm.as_ref() else {
+            return Ok(Outcome::Pass);
+        };
+
+        let tz = user_timezone(message.author.id).await;
+        let now = Utc::now();
+        let local = now.with_timezone(&tz);
+
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(framework.user_data.config.openai.auto.model.as_str())
+            .max_tokens(200u32)
+            .response_format(ResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    description: None,
+                    name: "reminder".to_string(),
+                    schema: Some(schema()),
+                    strict: Some(true),
+                },
+            })
+            .messages(vec![
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(format!(
+                        "You extract reminders from chat messages. It is currently {} ({}) for the user. \
+                         Resolve relative times like \"tomorrow at 9\" or \"in an hour\" against that. \
+                         A bare hour without am/pm means the next such hour during the day.",
+                        local.format("%A %Y-%m-%d %H:%M"),
+                        tz.name()
+                    ))
+                    .build()?
+                    .into(),
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(content)
+                    .build()?
+                    .into(),
+            ])
+            .build()?;
+
+        let extraction = match llm.chat(request).await {
+            Ok(response) => response
+                .choices
+                .first()
+                .and_then(|choice| choice.message.content.as_deref())
+                .and_then(|json| serde_json::from_str::<Extraction>(json).ok()),
+            Err(e) => {
+                warn!("Reminder extraction failed: {}", e);
+                None
+            }
+        };
+        let Some(extraction) = extraction else {
+            message
+                .reply(ctx, "I couldn't work out that reminder, try `/remind` instead.")
+                .await?;
+            return Ok(Outcome::Handled);
+        };
+        if !extraction.is_reminder {
+            return Ok(Outcome::Pass);
+        }
+        info!("Reminder intent from {}: {:?}", message.author.id, extraction);
+
+        let text = extraction.text.trim();
+        let text = if text.is_empty() { content } else { text };
+        let result = match parse_when(&extraction.when, tz, now) {
+            Ok(at) => add_reminder(message.author.id, message.guild_id, message.channel_id, at, text, tz)
+                .await
+                .map(|id| (id, at)),
+            Err(_) => Err("I couldn't tell when to remind you, try `/remind` instead.".to_string()),
+        };
+        match result {
+            Ok((id, at)) => {
+                let embed = serenity::CreateEmbed::new()
+                    .title("Reminder set")
+                    .color(0x2ECC71)
+                    .description(text)
+                    .field("When", format!("<t:{0}:f> (<t:{0}:R>)", at.timestamp()), false)
+                    .footer(serenity::CreateEmbedFooter::new(format!("#{} | {}", id, tz.name())));
+                message
+                    .channel_id
+                    .send_message(
+                        ctx,
+                        serenity::CreateMessage::new()
+                            .embed(embed)
+                            .reference_message(message)
+                            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+                    )
+                    .await?;
+            }
+            Err(e) => {
+                message.reply(ctx, e).await?;
+            }
+        }
+        Ok(Outcome::Handled)
+    }
+}
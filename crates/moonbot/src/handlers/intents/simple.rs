@@ -0,0 +1,52 @@
+use super::{Intent, Matcher, Outcome};
+use crate::{Data, Error};
+use async_trait::async_trait;
+use moonbot_config::config::SimpleIntent;
+use poise::serenity_prelude as serenity;
+
+/// An intent from `openai.intents.simple`, answered with a fixed reply.
+pub struct Simple {
+    config: SimpleIntent,
+    matcher: Matcher,
+}
+
+impl Simple {
+    pub fn new(config: SimpleIntent) -> Result<Self, regex::Error> {
+        let matcher = Matcher::new(&config.keywords, &config.patterns)?;
+        Ok(Simple { config, matcher })
+    }
+}
+
+#[async_trait]
+impl Intent for Simple {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.config.priority
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        (self.config.max_words == 0 || text.split_whitespace().count() <= self.config.max_words)
+            && self.matcher.is_match(text)
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(self.config.description.as_str()).filter(|d| !d.is_empty())
+    }
+
+    async fn handle(
+        &self,
+        ctx: &serenity::Context,
+        _framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+        _text: &str,
+    ) -> Result<Outcome, Error> {
+        if !self.config.reply.is_empty() {
+            let reply = self.config.reply.replace("{user}", &format!("<@{}>", message.author.id));
+            message.reply(ctx, reply).await?;
+        }
+        Ok(Outcome::Handled)
+    }
+}
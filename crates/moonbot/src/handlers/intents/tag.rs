@@ -0,0 +1,69 @@
+use super::{Intent, Outcome};
+use crate::{Data, Error};
+use async_trait::async_trait;
+use moonbot_db as db;
+use poise::serenity_prelude as serenity;
+use regex::Regex;
+
+/// "tag <query>": mention the users that match the query, from the user directory.
+pub struct Tag {
+    pattern: Regex,
+}
+
+impl Tag {
+    pub fn new() -> Self {
+        Tag {
+            pattern: Regex::new(r"(?is)(?:^|\s)tag\s+(\S.*)").unwrap(),
+        }
+    }
+}
+
+#[async_trait]
+impl Intent for Tag {
+    fn name(&self) -> &str {
+        "tag"
+    }
+
+    fn priority(&self) -> i32 {
+        50
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+
+    async fn handle(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+        text: &str,
+    ) -> Result<Outcome, Error> {
+        let Some(query) = self.pattern.captures(text).map(|c| c[1].trim().to_string()) else {
+            return Ok(Outcome::Pass);
+        };
+        // If the message already mentions users (other than the bot), just echo them (ensures proper tags)
+        let mut mentions: Vec<String> = message
+            .mentions
+            .iter()
+            .filter(|u| u.id != framework.bot_id)
+            .map(|u| format!("<@{}>", u.id.get()))
+            .collect();
+
+        if mentions.is_empty() {
+            // Use FTS first, fallback to LIKE
+            let list = match db::search_users_fts(framework.user_data.db, &query, 5).await {
+                Ok(v) => v,
+                Err(_) => db::search_users_like(framework.user_data.db, &query, 5).await.unwrap_or_default(),
+            };
+            for e in list { mentions.push(format!("<@{}>", e.user_id)); }
+        }
+
+        if mentions.is_empty() {
+            message.reply(ctx, "No matches.").await?;
+        } else {
+            message.reply(ctx, mentions.join(" ")).await?;
+        }
+        Ok(Outcome::Handled)
+    }
+}
@@ -1,5 +1,5 @@
 use humantime::format_duration;
-use lavalink_rs::{hook, model::events, prelude::*};
+use lavalink_rs::{hook, model::{events, track::TrackData}, prelude::*};
 use poise::serenity_prelude as serenity;
 use std::time::Duration;
 use tracing::debug;
@@ -13,13 +13,39 @@ pub async fn raw_event(_: LavalinkClient, session_id: String, event: &serde_json
 
 #[hook]
 pub async fn ready_event(client: LavalinkClient, session_id: String, event: &events::Ready) {
-    client.delete_all_player_contexts().await.unwrap();
     debug!("{:?} -> {:?}", session_id, event);
+    super::nodes::handle_ready(client, &session_id).await;
+}
+
+#[hook]
+pub async fn stats_event(client: LavalinkClient, session_id: String, event: &events::Stats) {
+    super::nodes::handle_stats(&client, &session_id, event);
 }
 
 #[hook]
 pub async fn track_start(client: LavalinkClient, _session_id: String, event: &events::TrackStart) {
+    let guild_id = serenity::GuildId::new(event.guild_id.0);
+    super::inactivity::handle_track_start(guild_id);
+
+    // The same track resuming on another node isn't a new play
+    if super::nodes::take_migrated(guild_id) {
+        return;
+    }
+
+    // Soundboard clips aren't music, and music coming back after one isn't a new play
+    if crate::soundboard::is_clip(&event.track) {
+        return;
+    }
     let player = client.get_player_context(event.guild_id).unwrap();
+    if let Some(paused) = crate::soundboard::take_resume(guild_id, &event.track) {
+        if paused {
+            let _ = player.set_pause(true).await;
+        }
+        return;
+    }
+
+    record_play(guild_id, &event.track).await;
+
     let data = player
         .data::<(serenity::ChannelId, std::sync::Arc<serenity::Http>)>()
         .unwrap();
@@ -32,15 +58,54 @@ pub async fn track_start(client: LavalinkClient, _session_id: String, event: &ev
         return;
     }
 
-    let track = &event.track;
+    let embed = now_playing_embed(&event.track);
+
+    let _ = channel_id
+        .send_message(http, serenity::CreateMessage::new().embed(embed))
+        .await;
+}
 
+#[hook]
+pub async fn track_end(_client: LavalinkClient, _session_id: String, event: &events::TrackEnd) {
+    let guild_id = serenity::GuildId::new(event.guild_id.0);
+    // Autoplay continues after natural ends and skips, not replacements or cleanup
+    if !crate::soundboard::is_clip(&event.track)
+        && matches!(event.reason, events::TrackEndReason::Finished | events::TrackEndReason::Stopped)
+    {
+        super::autoplay::handle_track_end(guild_id, &event.track).await;
+    }
+    super::inactivity::handle_track_end(guild_id).await;
+}
+
+/// Log a track starting into play history (best-effort).
+pub async fn record_play(guild_id: serenity::GuildId, track: &TrackData) {
+    let requester = track
+        .user_data
+        .as_ref()
+        .and_then(|data| data.get("requester_id"))
+        .and_then(|id| id.as_u64())
+        .map(|id| id as i64)
+        .filter(|_| !super::autoplay::is_autoplayed(track));
+    let _ = moonbot_db::record_play(
+        moonbot_db::get_db().await,
+        guild_id.get() as i64,
+        requester,
+        &track.info.title,
+        &track.info.author,
+        track.info.uri.as_deref(),
+        track.info.length as i64,
+    )
+    .await;
+}
+
+pub fn now_playing_embed(track: &TrackData) -> serenity::CreateEmbed {
     let requester_id = track
         .user_data
         .as_ref()
         .and_then(|data| data.get("requester_id"))
         .unwrap_or(&serde_json::Value::Null);
 
-    let embed = serenity::CreateEmbed::default()
+    serenity::CreateEmbed::default()
         .color(0x2ECC71)
         .author(serenity::CreateEmbedAuthor::new("Now Playing"))
         .description(format!(
@@ -48,16 +113,20 @@ pub async fn track_start(client: LavalinkClient, _session_id: String, event: &ev
             track.info.title,
             track.info.uri.as_ref().unwrap_or(&String::new())
         ))
-        .field("Requested By", format!("<@!{}>", requester_id), true)
+        .field(
+            "Requested By",
+            if super::autoplay::is_autoplayed(track) {
+                "Autoplay".to_string()
+            } else {
+                format!("<@!{}>", requester_id)
+            },
+            true,
+        )
         .field("Author", track.info.author.to_string(), true)
         .field(
             "Duration",
             format_duration(Duration::from_millis(track.info.length)).to_string(),
             true,
         )
-        .image(track.info.artwork_url.as_ref().unwrap_or(&String::new()));
-
-    let _ = channel_id
-        .send_message(http, serenity::CreateMessage::new().embed(embed))
-        .await;
+        .image(track.info.artwork_url.as_ref().unwrap_or(&String::new()))
 }
@@ -0,0 +1,334 @@
+use crate::infractions::{self, Kind, MAX_TIMEOUT};
+use crate::llm_backend::{LlmBackend, OpenAIBackend};
+use crate::{Data, Error};
+use async_openai::types::{CreateModerationRequestArgs, ModerationInput};
+use moonbot_db::{GuildModerationSettings, ModerationAction};
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+// Opt-in moderation assistant.
+//
+// Messages in servers that enabled it are scored by the LLM backend's moderation endpoint,
+// or a local classifier with the same API. Messages with a category over the server's
+// threshold are posted to its mod-log channel with buttons to delete, time out, warn or
+// dismiss. Flags and every button press are recorded in `moderation_actions`, warnings
+// and timeouts also count as infractions.
+struct Moderation {
+    backend: Option<Arc<dyn LlmBackend>>,
+}
+
+static MODERATION: OnceCell<Moderation> = OnceCell::new();
+
+/// Categories of the moderation API, in the order they're shown.
+pub const CATEGORIES: &[&str] = &[
+    "harassment",
+    "harassment/threatening",
+    "hate",
+    "hate/threatening",
+    "illicit",
+    "illicit/violent",
+    "self-harm",
+    "self-harm/intent",
+    "self-harm/instructions",
+    "sexual",
+    "sexual/minors",
+    "violence",
+    "violence/graphic",
+];
+
+const BUTTON_PREFIX: &str = "moderation:";
+
+/// Set up the classifier, call once the client is ready.
+pub fn init(llm: Option<Arc<dyn LlmBackend>>) {
+    let config = &moonbot_config::get_config().moderation;
+    let backend = if config.local_url.is_empty() {
+        llm
+    } else {
+        let client = async_openai::Client::with_config(
+            async_openai::config::OpenAIConfig::new().with_api_base(&config.local_url),
+        );
+        Some(Arc::new(OpenAIBackend::new(client)) as Arc<dyn LlmBackend>)
+    };
+    let _ = MODERATION.set(Moderation { backend });
+}
+
+/// Whether there's a classifier to score messages with.
+pub fn is_available() -> bool {
+    MODERATION.get().is_some_and(|m| m.backend.is_some())
+}
+
+/// The score a category needs in a guild to flag a message.
+pub fn threshold(settings: &GuildModerationSettings, category: &str) -> f64 {
+    settings
+        .thresholds
+        .get(category)
+        .or_else(|| settings.thresholds.get("default"))
+        .copied()
+        .unwrap_or(moonbot_config::get_config().moderation.default_threshold)
+}
+
+/// Score a message in the background if its guild has moderation on.
+pub async fn handle_message(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+) -> Result<(), Error> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot
+        || message.content.chars().count() < framework.user_data.config.moderation.min_length as usize
+    {
+        return Ok(());
+    }
+    let Some(backend) = MODERATION.get().and_then(|m| m.backend.clone()) else {
+        return Ok(());
+    };
+    let settings = moonbot_db::get_moderation_settings(framework.user_data.db, guild_id.get() as i64).await;
+    let Some(log_channel) = settings.log_channel_id.filter(|_| settings.enabled) else {
+        return Ok(());
+    };
+
+    let ctx = ctx.clone();
+    let message = message.clone();
+    tokio::spawn(async move {
+        let log_channel = serenity::ChannelId::new(log_channel as u64);
+        if let Err(e) = check(&ctx, backend.as_ref(), &settings, log_channel, &message).await {
+            warn!("Moderation check failed for message {}: {}", message.id, e);
+        }
+    });
+    Ok(())
+}
+
+async fn check(
+    ctx: &serenity::Context,
+    backend: &dyn LlmBackend,
+    settings: &GuildModerationSettings,
+    log_channel: serenity::ChannelId,
+    message: &serenity::Message,
+) -> Result<(), Error> {
+    let config = &moonbot_config::get_config().moderation;
+    let request = CreateModerationRequestArgs::default()
+        .input(ModerationInput::String(message.content.clone()))
+        .model(config.model.as_str())
+        .build()?;
+    let response = backend.moderations(request).await?;
+    let Some(result) = response.results.first() else {
+        return Ok(());
+    };
+    let scores = serde_json::to_value(&result.category_scores)?;
+    let Some(scores) = scores.as_object() else {
+        return Ok(());
+    };
+
+    let mut flagged = scores
+        .iter()
+        .filter_map(|(category, score)| Some((category.as_str(), score.as_f64()?)))
+        .filter(|(category, score)| *score >= threshold(settings, category))
+        .collect::<Vec<_>>();
+    if flagged.is_empty() {
+        return Ok(());
+    }
+    flagged.sort_by(|a, b| b.1.total_cmp(&a.1));
+    debug!("Message {} flagged: {:?}", message.id, flagged);
+
+    let guild_id = message.guild_id.unwrap();
+    let id = moonbot_db::add_moderation_action(
+        moonbot_db::get_db().await,
+        &ModerationAction {
+            id: 0,
+            guild_id: guild_id.get() as i64,
+            channel_id: Some(message.channel_id.get() as i64),
+            message_id: Some(message.id.get() as i64),
+            target_user_id: message.author.id.get() as i64,
+            moderator_id: None,
+            action: "flag".to_string(),
+            reason: Some(flagged.iter().map(|(c, _)| *c).collect::<Vec<_>>().join(", ")),
+            scores: Some(serde_json::Value::Object(scores.clone()).to_string()),
+            created_at: chrono::Utc::now(),
+        },
+    )
+    .await?;
+
+    let mut content = message.content.clone();
+    if content.chars().count() > 1000 {
+        content = format!("{}…", content.chars().take(999).collect::<String>());
+    }
+    let embed = serenity::CreateEmbed::new()
+        .title("Flagged message")
+        .color(0xFF0000)
+        .author(serenity::CreateEmbedAuthor::new(&message.author.name).icon_url(message.author.face()))
+        .description(content)
+        .field("Author", format!("<@{}>", message.author.id), true)
+        .field(
+            "Channel",
+            format!("<#{}> ([jump]({}))", message.channel_id, message.link()),
+            true,
+        )
+        .field(
+            "Scores",
+            flagged
+                .iter()
+                .map(|(category, score)| format!("{} **{:.0}%**", category, score * 100.0))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false,
+        )
+        .footer(serenity::CreateEmbedFooter::new(format!("#{}", id)))
+        .timestamp(message.timestamp);
+    let buttons = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{}delete:{}", BUTTON_PREFIX, id))
+            .label("Delete")
+            .style(serenity::ButtonStyle::Danger),
+        serenity::CreateButton::new(format!("{}timeout:{}", BUTTON_PREFIX, id))
+            .label(format!("Time out {}m", config.timeout_minutes))
+            .style(serenity::ButtonStyle::Primary),
+        serenity::CreateButton::new(format!("{}warn:{}", BUTTON_PREFIX, id))
+            .label("Warn")
+            .style(serenity::ButtonStyle::Primary),
+        serenity::CreateButton::new(format!("{}dismiss:{}", BUTTON_PREFIX, id))
+            .label("Dismiss")
+            .style(serenity::ButtonStyle::Secondary),
+    ]);
+    log_channel
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .embed(embed)
+                .components(vec![buttons])
+                .allowed_mentions(serenity::CreateAllowedMentions::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn reply_ephemeral(
+    ctx: &serenity::Context,
+    interaction: &serenity::ComponentInteraction,
+    text: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new().content(text).ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Act on a button pressed under a mod-log post.
+pub async fn handle_component(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    interaction: &serenity::ComponentInteraction,
+) -> Result<(), Error> {
+    let Some((action, id)) = interaction
+        .data
+        .custom_id
+        .strip_prefix(BUTTON_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+    else {
+        return Ok(());
+    };
+    let (Some(guild_id), Ok(id)) = (interaction.guild_id, id.parse::<i64>()) else {
+        return Ok(());
+    };
+    let db = framework.user_data.db;
+
+    let (needed, permission) = match action {
+        "delete" | "dismiss" => (serenity::Permissions::MANAGE_MESSAGES, "Manage Messages"),
+        "timeout" | "warn" => (serenity::Permissions::MODERATE_MEMBERS, "Timeout Members"),
+        _ => return Ok(()),
+    };
+    let allowed = interaction
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.contains(needed));
+    if !allowed {
+        return reply_ephemeral(ctx, interaction, &format!("You need the {} permission for that.", permission)).await;
+    }
+    let Some(flag) = moonbot_db::get_moderation_action(db, id)
+        .await?
+        .filter(|f| f.guild_id == guild_id.get() as i64)
+    else {
+        return reply_ephemeral(ctx, interaction, "That flag no longer exists.").await;
+    };
+    let user = serenity::UserId::new(flag.target_user_id as u64);
+    let channel = flag.channel_id.map(|c| serenity::ChannelId::new(c as u64));
+    let message = flag.message_id.map(|m| serenity::MessageId::new(m as u64));
+
+    let done = match action {
+        "delete" => {
+            let (Some(channel), Some(message)) = (channel, message) else {
+                return Ok(());
+            };
+            if let Err(e) = channel.delete_message(ctx, message).await {
+                return reply_ephemeral(ctx, interaction, &format!("Couldn't delete the message: {}", e)).await;
+            }
+            "Deleted"
+        }
+        "timeout" | "warn" => {
+            let kind = if action == "warn" {
+                Kind::Warn
+            } else {
+                let minutes = framework.user_data.config.moderation.timeout_minutes as u64;
+                Kind::Timeout(Duration::from_secs(minutes * 60).min(MAX_TIMEOUT))
+            };
+            let reason = format!("Flagged as {}", flag.reason.as_deref().unwrap_or("inappropriate"));
+            match infractions::punish(ctx, guild_id, user, Some(interaction.user.id), kind, Some(&reason)).await {
+                Err(e) => {
+                    return reply_ephemeral(ctx, interaction, &format!("Couldn't {} them: {}", kind.name(), e)).await;
+                }
+                // The warning stands, the flag only says so
+                Ok(punished) if punished.escalation_error.is_some() => "Warned (escalation failed)",
+                Ok(_) if action == "warn" => "Warned",
+                Ok(_) => "Timed out",
+            }
+        }
+        _ => "Dismissed",
+    };
+
+    moonbot_db::add_moderation_action(
+        db,
+        &ModerationAction {
+            id: 0,
+            guild_id: flag.guild_id,
+            channel_id: flag.channel_id,
+            message_id: flag.message_id,
+            target_user_id: flag.target_user_id,
+            moderator_id: Some(interaction.user.id.get() as i64),
+            action: action.to_string(),
+            reason: Some(format!("From flag #{}", flag.id)),
+            scores: None,
+            created_at: chrono::Utc::now(),
+        },
+    )
+    .await?;
+
+    let mut embed = interaction
+        .message
+        .embeds
+        .first()
+        .cloned()
+        .map(serenity::CreateEmbed::from)
+        .unwrap_or_default()
+        .field(done, format!("by <@{}>", interaction.user.id), true);
+    let mut response = serenity::CreateInteractionResponseMessage::new();
+    if action == "dismiss" {
+        embed = embed.color(0x2ECC71);
+        response = response.components(vec![]);
+    }
+    interaction
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(response.embed(embed)),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,361 @@
+use lavalink_rs::{
+    model::{events, http::UpdatePlayer, http::UpdatePlayerTrack, player::Player, BoxFuture},
+    node::Node,
+    prelude::*,
+};
+use moonbot_config::config::LavalinkNodeConfig;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// Spreads players over the configured Lavalink nodes and keeps them playing when a
+// node goes away.
+//
+// Nodes are picked by health, then the voice channel's region, then priority, then
+// load. A background task polls every node's stats; when one stops answering its
+// players are recreated on another node from the last known state, so the queue
+// (kept client side in the PlayerContext) and position survive.
+struct Nodes {
+    // Indexed like `LavalinkClient::nodes`
+    configs: Vec<LavalinkNodeConfig>,
+    cache: Arc<serenity::Cache>,
+    health: Mutex<Vec<NodeHealth>>,
+    // Guilds whose track was restarted by a migration, so TrackStart isn't a new play
+    migrated: Mutex<HashSet<serenity::GuildId>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeHealth {
+    pub healthy: bool,
+    pub stats: Option<events::Stats>,
+    pub last_error: Option<String>,
+}
+
+/// A node as shown by `/music-nodes`.
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    pub name: String,
+    pub region: String,
+    pub priority: i32,
+    pub connected: bool,
+    pub health: NodeHealth,
+    // Players this bot has on the node
+    pub guilds: usize,
+}
+
+static NODES: OnceCell<Nodes> = OnceCell::new();
+
+/// Set up node tracking, call before creating the Lavalink client.
+pub fn init(configs: Vec<LavalinkNodeConfig>, cache: Arc<serenity::Cache>) {
+    let health = configs
+        .iter()
+        .map(|_| NodeHealth {
+            healthy: true,
+            stats: None,
+            last_error: None,
+        })
+        .collect();
+    let _ = NODES.set(Nodes {
+        configs,
+        cache,
+        health: Mutex::new(health),
+        migrated: Mutex::new(HashSet::new()),
+    });
+}
+
+fn display_name(config: &LavalinkNodeConfig) -> String {
+    if config.name.is_empty() {
+        config.host.clone()
+    } else {
+        config.name.clone()
+    }
+}
+
+fn is_healthy(state: &Nodes, node: &Node) -> bool {
+    node.is_running.load(Ordering::SeqCst)
+        && state
+            .health
+            .lock()
+            .unwrap()
+            .get(node.id)
+            .is_none_or(|h| h.healthy)
+}
+
+fn node_by_session<'a>(client: &'a LavalinkClient, session_id: &str) -> Option<&'a Arc<Node>> {
+    client.nodes.iter().find(|n| n.session_id.load().as_str() == session_id)
+}
+
+/// The Discord voice region of the channel the bot is in, if it's not automatic.
+fn voice_region(cache: &serenity::Cache, guild_id: serenity::GuildId) -> Option<String> {
+    let guild = cache.guild(guild_id)?;
+    let channel_id = guild.voice_states.get(&cache.current_user().id)?.channel_id?;
+    guild.channels.get(&channel_id)?.rtc_region.clone()
+}
+
+/// Pick the best node for a guild, never returning `exclude` if anything else is up.
+fn pick(client: &LavalinkClient, guild_id: serenity::GuildId, exclude: Option<usize>) -> Arc<Node> {
+    let Some(state) = NODES.get() else {
+        return client.nodes[0].clone();
+    };
+    let region = voice_region(&state.cache, guild_id);
+
+    let assigned = |id: usize| client.players.iter().filter(|p| p.1.id == id).count();
+    let score = |node: &Arc<Node>| {
+        let config = state.configs.get(node.id);
+        let region_match = match (&region, config) {
+            (Some(region), Some(config)) => config.region.eq_ignore_ascii_case(region),
+            _ => false,
+        };
+        (
+            !region_match,
+            -config.map(|c| c.priority).unwrap_or(0),
+            assigned(node.id),
+        )
+    };
+
+    let best = client
+        .nodes
+        .iter()
+        .filter(|n| Some(n.id) != exclude && is_healthy(state, n))
+        .min_by(|a, b| {
+            score(a)
+                .cmp(&score(b))
+                .then(a.cpu.load().lavalink_load.total_cmp(&b.cpu.load().lavalink_load))
+        });
+    match best {
+        Some(node) => node.clone(),
+        // Nothing is up, stay put or fall back to the highest priority node
+        None => client
+            .nodes
+            .iter()
+            .find(|n| Some(n.id) == exclude)
+            .or_else(|| client.nodes.iter().min_by_key(|n| score(n)))
+            .unwrap()
+            .clone(),
+    }
+}
+
+/// Node distribution strategy for the Lavalink client.
+pub fn select_node(client: &LavalinkClient, guild_id: GuildId) -> BoxFuture<'_, Arc<Node>> {
+    let node = pick(client, serenity::GuildId::new(guild_id.0), None);
+    debug!("Selected Lavalink node {} for guild {}", node.id, guild_id.0);
+    Box::pin(async move { node })
+}
+
+/// Whether the last TrackStart in a guild came from moving nodes, clearing the flag.
+pub fn take_migrated(guild_id: serenity::GuildId) -> bool {
+    NODES
+        .get()
+        .is_some_and(|state| state.migrated.lock().unwrap().remove(&guild_id))
+}
+
+/// Where playback is now. Player updates only come every few seconds, so account
+/// for the time since the last one.
+pub fn live_position(state: &Player) -> u64 {
+    let mut position = state.state.position;
+    if !state.paused && state.track.is_some() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        position += now.saturating_sub(state.state.time);
+    }
+    if let Some(track) = &state.track {
+        position = position.min(track.info.length);
+    }
+    position
+}
+
+/// Recreate a guild's player on `to`, resuming the current track where it was.
+async fn migrate(
+    client: &LavalinkClient,
+    guild_id: serenity::GuildId,
+    to: Arc<Node>,
+) -> LavalinkResult<()> {
+    let Some(player) = client.get_player_context(guild_id) else {
+        return Ok(());
+    };
+    // The context keeps the last known state, so this works with the old node gone
+    let state = player.get_player().await?;
+
+    let old = client.players.get_mut(&GuildId::from(guild_id)).map(|mut entry| {
+        std::mem::replace(&mut entry.1, to.clone())
+    });
+    if let Some(old) = old.filter(|old| old.id != to.id) {
+        // Don't leave the old node playing too if it's still half alive
+        let _ = tokio::time::timeout(
+            Duration::from_secs(5),
+            old.http.delete_player(guild_id, &old.session_id.load()),
+        )
+        .await;
+    }
+
+    let position = live_position(&state);
+
+    let update = UpdatePlayer {
+        track: state.track.as_ref().map(|track| UpdatePlayerTrack {
+            encoded: Some(track.encoded.clone()),
+            user_data: track.user_data.clone(),
+            ..Default::default()
+        }),
+        position: Some(position),
+        paused: Some(state.paused),
+        volume: Some(state.volume),
+        filters: state.filters.clone(),
+        voice: Some(state.voice.clone()),
+        ..Default::default()
+    };
+
+    if state.track.is_some() {
+        if let Some(nodes) = NODES.get() {
+            nodes.migrated.lock().unwrap().insert(guild_id);
+        }
+    }
+    let result = to
+        .http
+        .update_player(guild_id, &to.session_id.load(), &update, false)
+        .await?;
+    player.update_player_data(result)?;
+    Ok(())
+}
+
+/// Move every player on node `from` to the best other node.
+async fn evacuate(client: &LavalinkClient, from: usize) {
+    let guilds = client
+        .players
+        .iter()
+        .filter(|p| p.1.id == from)
+        .map(|p| serenity::GuildId::new(p.key().0))
+        .collect::<Vec<_>>();
+
+    for guild_id in guilds {
+        let to = pick(client, guild_id, Some(from));
+        if to.id == from {
+            warn!("No healthy Lavalink node to move guild {} to", guild_id);
+            continue;
+        }
+        match migrate(client, guild_id, to.clone()).await {
+            Ok(()) => info!("Moved guild {} from Lavalink node {} to {}", guild_id, from, to.id),
+            Err(e) => warn!("Failed to move guild {} to Lavalink node {}: {}", guild_id, to.id, e),
+        }
+    }
+}
+
+fn set_health(id: usize, healthy: bool, stats: Option<events::Stats>, error: Option<String>) -> bool {
+    let Some(state) = NODES.get() else { return healthy };
+    let mut health = state.health.lock().unwrap();
+    let Some(entry) = health.get_mut(id) else { return healthy };
+    let was_healthy = entry.healthy;
+    entry.healthy = healthy;
+    if stats.is_some() {
+        entry.stats = stats;
+    }
+    entry.last_error = error;
+    was_healthy
+}
+
+/// Record stats pushed over a node's websocket.
+pub fn handle_stats(client: &LavalinkClient, session_id: &str, stats: &events::Stats) {
+    let Some(node) = node_by_session(client, session_id) else { return };
+    node.cpu.store(Arc::new(stats.cpu.clone()));
+    node.memory.store(Arc::new(stats.memory.clone()));
+    set_health(node.id, true, Some(stats.clone()), None);
+}
+
+/// A node (re)connected. Lavalink drops players with the old session, so restore
+/// any that are still assigned to it.
+pub async fn handle_ready(client: LavalinkClient, session_id: &str) {
+    let Some(node) = node_by_session(&client, session_id).cloned() else { return };
+    set_health(node.id, true, None, None);
+
+    let guilds = client
+        .players
+        .iter()
+        .filter(|p| p.1.id == node.id)
+        .map(|p| serenity::GuildId::new(p.key().0))
+        .collect::<Vec<_>>();
+    for guild_id in guilds {
+        let to = pick(&client, guild_id, None);
+        if let Err(e) = migrate(&client, guild_id, to).await {
+            warn!("Failed to restore player for guild {}: {}", guild_id, e);
+        }
+    }
+}
+
+/// Poll every node's stats in the background and evacuate nodes that stop answering.
+pub fn spawn_health_checks(client: LavalinkClient) {
+    let interval = moonbot_config::get_config().lavalink.health_check_interval;
+    if interval == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            for node in client.nodes.iter() {
+                let result = if node.is_running.load(Ordering::SeqCst) {
+                    match tokio::time::timeout(Duration::from_secs(10), node.http.stats()).await {
+                        Ok(Ok(stats)) => Ok(stats),
+                        Ok(Err(e)) => Err(e.to_string()),
+                        Err(_) => Err("timed out".to_string()),
+                    }
+                } else {
+                    Err("websocket disconnected".to_string())
+                };
+
+                match result {
+                    Ok(stats) => {
+                        node.cpu.store(Arc::new(stats.cpu.clone()));
+                        node.memory.store(Arc::new(stats.memory.clone()));
+                        if !set_health(node.id, true, Some(stats), None) {
+                            info!("Lavalink node {} is healthy again", node.id);
+                        }
+                    }
+                    Err(e) => {
+                        if set_health(node.id, false, None, Some(e.clone())) {
+                            warn!("Lavalink node {} is unhealthy: {}", node.id, e);
+                        }
+                        // Also retries players that couldn't move last time
+                        evacuate(&client, node.id).await;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// The current state of every node.
+pub fn status(client: &LavalinkClient) -> Vec<NodeStatus> {
+    let Some(state) = NODES.get() else { return vec![] };
+    let health = state.health.lock().unwrap().clone();
+    client
+        .nodes
+        .iter()
+        .map(|node| {
+            let config = state.configs.get(node.id).cloned().unwrap_or_default();
+            NodeStatus {
+                name: display_name(&config),
+                region: config.region.clone(),
+                priority: config.priority,
+                connected: node.is_running.load(Ordering::SeqCst),
+                health: health.get(node.id).cloned().unwrap_or(NodeHealth {
+                    healthy: false,
+                    stats: None,
+                    last_error: None,
+                }),
+                guilds: client.players.iter().filter(|p| p.1.id == node.id).count(),
+            }
+        })
+        .collect()
+}
+
+/// Name of the node a guild's player is on.
+pub fn node_name_for(client: &LavalinkClient, guild_id: serenity::GuildId) -> Option<String> {
+    let state = NODES.get()?;
+    let id = client.players.get(&GuildId::from(guild_id))?.1.id;
+    state.configs.get(id).map(display_name)
+}
@@ -1,5 +1,6 @@
 use crate::{utils::is_reply_or_mention, Data, Error};
 use crate::context;
+use crate::llm_backend::LlmBackend;
 use async_openai::types::{
     ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessageContentPartImageArgs,
     ChatCompletionRequestMessageContentPartTextArgs, ChatCompletionRequestSystemMessageArgs,
@@ -8,14 +9,10 @@ use async_openai::types::{
 };
 use sea_orm::DatabaseConnection;
 use poise::serenity_prelude as serenity;
-use rand::Rng;
-use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::time::{sleep, Duration};
 use tracing::{error, info};
 use moonbot_db as db;
 
-static LAST_RESPONSE: AtomicU64 = AtomicU64::new(0);
-
 // Generate a response to a message
 pub async fn analyze_and_update(
     db: &DatabaseConnection,
@@ -66,10 +63,172 @@ pub async fn analyze_and_update(
         let _ = moonbot_db::set_bot_disposition(db, moonbot_db::Disposition { mood: "neutral".into(), mood_level: 0, notes: "".into() }).await;
     }
 }
+/// One message of a conversation, as it's sent to the model.
+pub struct Turn {
+    /// Who wrote it, see `chat_name`
+    pub name: String,
+    pub from_bot: bool,
+    pub content: String,
+    pub image_urls: Vec<String>,
+}
+
+/// OpenAI is very strict about the name, it has to match ^[a-zA-Z0-9_-]+$.
+/// Remove any special characters, and replace spaces with underscores.
+pub fn chat_name(name: &str) -> String {
+    name.replace(' ', "_")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+/// The request messages for a conversation, oldest turn first.
+pub fn chat_messages(
+    system: String,
+    instruction: Option<&str>,
+    turns: &[Turn],
+) -> Vec<async_openai::types::ChatCompletionRequestMessage> {
+    let mut chat_messages: Vec<async_openai::types::ChatCompletionRequestMessage> = vec![];
+
+    if !system.is_empty() {
+        chat_messages.push(
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(system)
+                .build()
+                .unwrap()
+                .into(),
+        );
+    }
+
+    // Intents can add a stronger instruction for this reply
+    if let Some(instruction) = instruction {
+        chat_messages.push(
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(instruction)
+                .build()
+                .unwrap()
+                .into(),
+        );
+    }
+
+    for turn in turns {
+        if turn.from_bot {
+            chat_messages.push(
+                ChatCompletionRequestAssistantMessageArgs::default()
+                    .content(turn.content.as_str())
+                    .name(turn.name.as_str())
+                    .build()
+                    .unwrap()
+                    .into(),
+            );
+            continue;
+        }
+
+        let mut user_content: Vec<ChatCompletionRequestUserMessageContentPart> =
+            vec![ChatCompletionRequestMessageContentPartTextArgs::default()
+                .text(turn.content.as_str())
+                .build()
+                .unwrap()
+                .into()];
+        for url in &turn.image_urls {
+            user_content.push(
+                ChatCompletionRequestMessageContentPartImageArgs::default()
+                    .image_url(url.as_str())
+                    .build()
+                    .unwrap()
+                    .into(),
+            );
+        }
+        chat_messages.push(
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(user_content)
+                .name(turn.name.as_str())
+                .build()
+                .unwrap()
+                .into(),
+        );
+    }
+    chat_messages
+}
+
+/// Model and sampling settings for one reply.
+pub struct Generation {
+    pub model: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub frequency_penalty: f32,
+    /// Passed on as the request's user, for abuse monitoring
+    pub user: String,
+}
+
+/// The settings for an auto reply to a user, adjusted by their profile and the persona.
+pub async fn auto_generation(
+    data: &Data,
+    user_id: i64,
+    persona: Option<&moonbot_db::Persona>,
+    user: String,
+) -> Generation {
+    let auto = &data.config.openai.auto;
+    // Adaptive generation params based on profile/preferences
+    let (temperature, frequency_penalty) = crate::context::compute_generation_params(
+        data,
+        user_id,
+        persona.and_then(|p| p.temperature).map(|t| t as f32).unwrap_or(auto.temperature),
+        auto.frequency_penalty,
+    ).await;
+    let model = persona
+        .and_then(|p| p.model.as_deref())
+        .filter(|m| !m.is_empty())
+        .unwrap_or(auto.model.as_str());
+    Generation { model: model.to_string(), max_tokens: auto.max_tokens, temperature, frequency_penalty, user }
+}
+
+/// Ask the model for a reply, with simple retries for transient failures.
+pub async fn complete(
+    llm: &dyn LlmBackend,
+    chat_messages: &[async_openai::types::ChatCompletionRequestMessage],
+    generation: &Generation,
+) -> Result<String, Error> {
+    let mut last_err: Option<String> = None;
+    for (i, delay_ms) in [200u64, 500, 1000].into_iter().enumerate() {
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(generation.model.as_str())
+            .messages(chat_messages.to_vec())
+            .max_tokens(generation.max_tokens)
+            .temperature(generation.temperature)
+            .frequency_penalty(generation.frequency_penalty)
+            .user(generation.user.as_str())
+            .build()?;
+        match llm.chat(request).await {
+            Ok(r) => {
+                return r
+                    .choices
+                    .first()
+                    .and_then(|c| c.message.content.clone())
+                    .ok_or_else(|| "the model sent an empty reply".into());
+            }
+            Err(e) => {
+                last_err = Some(format!("{}", e));
+                if i < 2 { sleep(Duration::from_millis(delay_ms)).await; }
+            }
+        }
+    }
+    Err(std::io::Error::other(last_err.unwrap_or_else(|| "unknown error".into())).into())
+}
+
 pub async fn generate_response(
     ctx: &serenity::Context,
     framework: poise::FrameworkContext<'_, Data, Error>,
     message: &serenity::Message,
+) -> Result<(), Error> {
+    generate_response_with(ctx, framework, message, None).await
+}
+
+// Generate a response with an extra system instruction
+pub async fn generate_response_with(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+    instruction: Option<&str>,
 ) -> Result<(), Error> {
     // Gather some context
     let mut messages = ctx
@@ -83,60 +242,38 @@ pub async fn generate_response(
 
     messages.insert(0, message.clone());
 
-    let mut chat_messages: Vec<async_openai::types::ChatCompletionRequestMessage> = vec![];
-
     // Centralized system prompt
-    let sys_base = context::build_system_prompt(
-        framework.user_data,
-        message.author.id.get() as i64,
-        &message.author.name,
-    ).await;
-    let sys_text = if let Some(guild_id) = message.guild_id {
-        if let Some(persona) = moonbot_db::get_guild_roleplay(framework.user_data.db, guild_id.get() as i64).await {
-            info!("roleplay=guild scope applied");
-            format!("### Roleplay persona (guild-wide)\n{}\n\n### Instruction\nStay in the above persona for this conversation. Reflect its style and diction consistently. Avoid generic chatbot greetings.\n\n{}", persona, sys_base)
-        } else if let Some(persona) = moonbot_db::get_channel_roleplay(framework.user_data.db, message.channel_id.get() as i64).await {
-            info!("roleplay=channel scope applied");
-            format!("### Roleplay persona\n{}\n\n### Instruction\nStay in the above persona for this conversation. Reflect its style and diction consistently. Avoid generic chatbot greetings.\n\n{}", persona, sys_base)
-        } else { sys_base }
-    } else { sys_base };
+    let roleplay = match message.guild_id {
+        Some(guild_id) => {
+            let location = crate::personas::Location::find(ctx, guild_id, message.channel_id).await;
+            crate::personas::resolve(framework.user_data.db, &location).await
+        }
+        None => None,
+    };
+    if let Some((scope, _)) = &roleplay {
+        info!("roleplay={} scope applied", scope.label());
+    }
+    let persona = roleplay.as_ref().and_then(|(_, s)| s.persona());
     // Lightweight retrieval from corpus (guild/channel scoped)
-    let mut retrieved = String::new();
-    if let Ok(hits) = db::search_corpus_fts(
-        framework.user_data.db,
+    let retrieved = context::retrieve(
+        framework.user_data,
         &message.content,
-        6,
         message.guild_id.map(|g| g.get() as i64),
-        Some(message.channel_id.get() as i64),
-    ).await {
-        if !hits.is_empty() {
-            for h in hits.iter().take(6) { retrieved.push_str(&format!("- [{}] {}\n", h.kind, h.content)); }
-        }
-    }
-
-    if !sys_text.is_empty() {
-        chat_messages.push(
-            ChatCompletionRequestSystemMessageArgs::default()
-                .content(if retrieved.is_empty() { sys_text.clone() } else { format!("{}\n\n### Retrieved context\n{}", sys_text, retrieved) })
-                .build()
-                .unwrap()
-                .into(),
-        );
-    }
-
-    // If the triggering message calls the bot "Sunbot", add a stronger, safe instruction.
-    let sunbot_misname = message.content.to_lowercase().contains("sunbot");
-    let directed = is_reply_or_mention(ctx, message, framework.bot_id).await;
-    if sunbot_misname && directed {
-        chat_messages.push(
-            ChatCompletionRequestSystemMessageArgs::default()
-                .content("If the user refers to you as 'Sunbot', respond with exactly one curt sentence that corrects the name to 'Moonbot'. Use 2-6 words. No emojis or flourish. Output only that sentence—nothing else. Keep it PG-13, no profanity or slurs, and do not target or insult any person or group.")
-                .build()
-                .unwrap()
-                .into(),
-        );
-    }
+        message.channel_id.get() as i64,
+    ).await;
+    let scene = context::PromptScene {
+        user_id: message.author.id.get() as i64,
+        user_name: &message.author.name,
+        guild_id: message.guild_id.map(|g| g.get() as i64),
+        guild_name: message.guild_id.and_then(|g| g.name(&ctx.cache)).unwrap_or_default(),
+        channel_name: message.channel_id.name(ctx).await.unwrap_or_default(),
+        roleplay: roleplay.as_ref().map(|(_, s)| s),
+        retrieved,
+        overrides: Default::default(),
+    };
+    let sys_text = context::build_system_prompt(framework.user_data, &scene).await;
 
+    let mut turns = vec![];
     for msg in messages.iter().rev() {
         // If this message is too old ignore it
         let diff = message.timestamp.timestamp() - msg.timestamp.timestamp();
@@ -144,16 +281,9 @@ pub async fn generate_response(
             continue;
         }
 
-        // If this is sent by us use ChatCompletionRequestAssistantMessage
+        // If this is sent by us it's an assistant message
         if msg.author.id == framework.bot_id {
-            chat_messages.push(
-                ChatCompletionRequestAssistantMessageArgs::default()
-                    .content(msg.content.as_str())
-                    .name(msg.author.name.as_str())
-                    .build()
-                    .unwrap()
-                    .into(),
-            );
+            turns.push(Turn { name: msg.author.name.clone(), from_bot: true, content: msg.content.clone(), image_urls: vec![] });
             continue;
         }
         // Otherwise ignore messages from other bots
@@ -162,105 +292,49 @@ pub async fn generate_response(
         }
 
         // Otherwise, this is a user message
-        let mut user_content: Vec<ChatCompletionRequestUserMessageContentPart> =
-            vec![ChatCompletionRequestMessageContentPartTextArgs::default()
-                .text(msg.content.as_str())
-                .build()
-                .unwrap()
-                .into()];
-
+        let mut image_urls = vec![];
         // If we have use_vision enabled
         if framework.user_data.config.openai.auto.use_vision {
             for attachment in msg.attachments.iter() {
                 if let Some(content_type) = attachment.content_type.as_deref() {
                     if content_type.to_lowercase().starts_with("image") {
                         info!("Found image attachment: {}", attachment.url.as_str());
-                        user_content.push(
-                            ChatCompletionRequestMessageContentPartImageArgs::default()
-                                .image_url(attachment.url.as_str())
-                                .build()
-                                .unwrap()
-                                .into(),
-                        );
+                        image_urls.push(attachment.url.clone());
                     }
                 }
             }
         }
 
-        // OpenAI is very strict about the name, we need to make sure it matches ^[a-zA-Z0-9_-]+$
-        // Remove any special characters, and replace spaces with underscores
-        let username = msg
-            .author
-            .name
-            .replace(' ', "_")
-            .chars()
-            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
-            .collect::<String>();
-
+        let username = chat_name(&msg.author.name);
         // Not sure what is causing this, log the changes so we might know more
         if username != msg.author.name {
             info!("Changed username from {} to {}", msg.author.name, username);
         }
 
-        chat_messages.push(
-            ChatCompletionRequestUserMessageArgs::default()
-                .content(user_content)
-                .name(format!("{}__{}", username, msg.author.id))
-                .build()
-                .unwrap()
-                .into(),
-        );
+        turns.push(Turn {
+            name: format!("{}__{}", username, msg.author.id),
+            from_bot: false,
+            content: msg.content.clone(),
+            image_urls,
+        });
     }
+    let chat_messages = chat_messages(sys_text, instruction, &turns);
 
     let openai_tasks = async {
-        let client = framework.user_data.openai_client.as_ref().unwrap();
-
-        // Simple retries for transient failures
-        let mut last_err: Option<String> = None;
-        let resp = {
-            let mut out = None;
-            for (i, delay_ms) in [200u64, 500, 1000].into_iter().enumerate() {
-                // Adaptive generation params based on profile/preferences
-                let (temp, freq_pen) = crate::context::compute_generation_params(
-                    framework.user_data,
-                    message.author.id.get() as i64,
-                    framework.user_data.config.openai.auto.temperature,
-                    framework.user_data.config.openai.auto.frequency_penalty,
-                ).await;
-                let request = CreateChatCompletionRequestArgs::default()
-                    .model(framework.user_data.config.openai.auto.model.as_str())
-                    .messages(chat_messages.clone())
-                    .max_tokens(framework.user_data.config.openai.auto.max_tokens)
-                    .temperature(temp)
-                    .frequency_penalty(freq_pen)
-                    .user(format!("guild:{}|chan:{}|user:{}",
-                        message.guild_id.map(|g| g.get()).unwrap_or_default(),
-                        message.channel_id.get(),
-                        message.author.id.get()
-                    ))
-                    .build()?;
-                match client.chat().create(request).await {
-                    Ok(r) => { out = Some(r); break; },
-                    Err(e) => {
-                        last_err = Some(format!("{}", e));
-                        if i < 2 { sleep(Duration::from_millis(delay_ms)).await; }
-                    }
-                }
-            }
-            if let Some(r) = out { r } else {
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, last_err.unwrap_or_else(|| "unknown error".into())).into());
-            }
-        };
+        let llm = framework.user_data.llm.as_deref().unwrap();
+        let generation = auto_generation(
+            framework.user_data,
+            message.author.id.get() as i64,
+            persona,
+            format!("guild:{}|chan:{}|user:{}",
+                message.guild_id.map(|g| g.get()).unwrap_or_default(),
+                message.channel_id.get(),
+                message.author.id.get()
+            ),
+        ).await;
+        let reply_text = complete(llm, &chat_messages, &generation).await?;
 
         // Send the response
-        let reply_text = resp.choices
-            .first()
-            .unwrap()
-            .message
-            .content
-            .as_ref()
-            .unwrap()
-            .clone();
         message.reply(ctx, &reply_text).await?;
 
         // Fire and forget: naive analysis to update dispositions and user insight
@@ -318,50 +392,9 @@ pub async fn handle_reply(
     }
 
     if is_reply_or_mention(ctx, message, framework.bot_id).await {
-        // Skip replies to low-content/insult-only messages to avoid annoyance
-        let lc = message.content.to_lowercase();
-        let word_count = lc.split_whitespace().count();
-        let insults = ["stupid", "idiot", "dumb", "moron", "oaf"];
-        let insult_only = word_count <= 3 && insults.iter().any(|w| lc.contains(w));
-        if insult_only {
+        if super::intents::route(ctx, framework, message).await? {
             return Ok(());
         }
-
-        // Intent: tag <query> — return mentions based on user directory/RAG
-        // Looks for the first occurrence of the token "tag" and treats the remainder of the message as the query.
-        if let Some(idx) = message
-            .content
-            .split_whitespace()
-            .position(|t| t.eq_ignore_ascii_case("tag"))
-        {
-            let parts: Vec<&str> = message.content.split_whitespace().collect();
-            if parts.len() > idx + 1 {
-                let query = parts[idx + 1..].join(" ");
-                // If the message already mentions users (other than the bot), just echo them (ensures proper tags)
-                let mut mentions: Vec<String> = message
-                    .mentions
-                    .iter()
-                    .filter(|u| u.id != framework.bot_id)
-                    .map(|u| format!("<@{}>", u.id.get()))
-                    .collect();
-
-                if mentions.is_empty() {
-                    // Use FTS first, fallback to LIKE
-                    let list = match db::search_users_fts(framework.user_data.db, &query, 5).await {
-                        Ok(v) => v,
-                        Err(_) => db::search_users_like(framework.user_data.db, &query, 5).await.unwrap_or_default(),
-                    };
-                    for e in list { mentions.push(format!("<@{}>", e.user_id)); }
-                }
-
-                if mentions.is_empty() {
-                    message.reply(ctx, "No matches.").await?;
-                } else {
-                    message.reply(ctx, mentions.join(" ")).await?;
-                }
-                return Ok(());
-            }
-        }
     info!("Triggered Reply on message: {}", message.content);
         return generate_response(ctx, framework, message).await;
     }
@@ -382,53 +415,18 @@ pub async fn handle_random_message(
         return Ok(());
     }
 
-    // Message must be longer than min length
-    if message.content.len() < framework.user_data.config.openai.auto.random.min_length as usize {
+    if !super::auto_reply::should_reply(ctx, framework, message).await {
         return Ok(());
     }
 
-    // Heuristic: only consider random replies if user shows intent (a question or actionable cue)
-    let lc = message.content.to_lowercase();
-    let looks_like_question = lc.contains('?');
-    let has_intent_keyword = [
-        "how", "what", "why", "fix", "explain", "help", "debug", "solve", "idea", "show", "code",
-    ]
-    .iter()
-    .any(|w| lc.contains(w));
-    if !(looks_like_question || has_intent_keyword) {
-        return Ok(());
-    }
-
-    // Check Cooldown
-    let last_response = LAST_RESPONSE.load(Ordering::Relaxed);
-    if last_response + framework.user_data.config.openai.auto.random.cooldown
-        > std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    {
-        return Ok(());
+    info!(
+        "Trigggered Random Reply on random message: {}",
+        message.content
+    );
+    let result = generate_response(ctx, framework, message).await;
+    // If we responded, start the channel's cooldown
+    if result.is_ok() {
+        super::auto_reply::mark_replied(message.channel_id);
     }
-
-    // Roll the dice
-    if rand::rng().random::<f64>() < framework.user_data.config.openai.auto.random.trigger_chance {
-        info!(
-            "Trigggered Random Reply on random message: {}",
-            message.content
-        );
-        let result = generate_response(ctx, framework, message).await;
-        // If we responded, update the last response time
-        if result.is_ok() {
-            LAST_RESPONSE.store(
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                Ordering::Relaxed,
-            );
-        }
-        return result;
-    }
-
-    Ok(())
+    result
 }
@@ -0,0 +1,156 @@
+use crate::{Data, Error};
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub mod configured;
+pub mod dad;
+
+// Passive responders react to any message, not just ones addressed to the bot.
+//
+// Each responder has a matcher, a chance and a cooldown per server (or DM channel).
+// Every matching responder that is enabled, off cooldown and wins its roll gets the
+// message. Servers turn responders on or off with /responders. Besides the built-in
+// ones, simple responders with reply templates and reactions come from `[[responders]]`
+// in the config.
+
+/// How often a responder may fire.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Chance to respond to a match (0.0-1.0)
+    pub chance: f64,
+    /// Time between responses in a server
+    pub cooldown: Duration,
+}
+
+#[async_trait]
+pub trait MessageResponder: Send + Sync {
+    /// Name used in logs and by /responders
+    fn name(&self) -> &str;
+
+    /// What it does, shown in /responders.
+    fn description(&self) -> &str;
+
+    /// Cheap check of the message, before settings are looked up.
+    fn matches(&self, message: &serenity::Message) -> bool;
+
+    /// Whether it runs where nobody turned it on or off, and in DMs.
+    fn default_enabled(&self) -> bool {
+        true
+    }
+
+    async fn enabled(&self, guild_id: serenity::GuildId) -> bool {
+        moonbot_db::get_responder_enabled(moonbot_db::get_db().await, guild_id.get() as i64, self.name())
+            .await
+            .unwrap_or_else(|| self.default_enabled())
+    }
+
+    /// Turn it on or off in a guild, None goes back to the default.
+    async fn set_enabled(&self, guild_id: serenity::GuildId, enabled: Option<bool>) -> Result<(), Error> {
+        moonbot_db::set_responder_enabled(moonbot_db::get_db().await, guild_id.get() as i64, self.name(), enabled)
+            .await?;
+        Ok(())
+    }
+
+    async fn limits(&self, guild_id: Option<serenity::GuildId>) -> Limits;
+
+    /// Respond to a matched message. Returns whether it did, which starts the cooldown.
+    async fn respond(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+    ) -> Result<bool, Error>;
+}
+
+struct Registry {
+    responders: Vec<Box<dyn MessageResponder>>,
+    // Last response per server (or DM channel) and responder
+    last: Mutex<HashMap<(u64, String), Instant>>,
+}
+
+static REGISTRY: OnceCell<Registry> = OnceCell::new();
+
+/// Set up the built-in and configured responders, call once the client is ready.
+pub fn init() {
+    let mut responders: Vec<Box<dyn MessageResponder>> = vec![Box::new(dad::Dad)];
+    for config in &moonbot_config::get_config().responders {
+        if responders.iter().any(|r| r.name() == config.name) {
+            warn!("Skipping responder {}: the name is taken", config.name);
+            continue;
+        }
+        match configured::Configured::new(config.clone()) {
+            Ok(responder) => responders.push(Box::new(responder)),
+            Err(e) => warn!("Skipping responder {}: {}", config.name, e),
+        }
+    }
+    info!(
+        "Responders: {}",
+        responders.iter().map(|r| r.name()).collect::<Vec<_>>().join(", ")
+    );
+    let _ = REGISTRY.set(Registry {
+        responders,
+        last: Mutex::new(HashMap::new()),
+    });
+}
+
+/// Every registered responder.
+pub fn all() -> &'static [Box<dyn MessageResponder>] {
+    REGISTRY.get().map(|r| r.responders.as_slice()).unwrap_or_default()
+}
+
+pub fn find(name: &str) -> Option<&'static dyn MessageResponder> {
+    all().iter().find(|r| r.name() == name).map(|r| r.as_ref())
+}
+
+/// Give a message to every responder that wants it.
+pub async fn handle_message(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    message: &serenity::Message,
+) -> Result<(), Error> {
+    let Some(registry) = REGISTRY.get() else {
+        return Ok(());
+    };
+    if message.author.bot || message.content.is_empty() {
+        return Ok(());
+    }
+
+    let key = message.guild_id.map(|g| g.get()).unwrap_or(message.channel_id.get());
+    for responder in registry.responders.iter().filter(|r| r.matches(message)) {
+        let enabled = match message.guild_id {
+            Some(guild_id) => responder.enabled(guild_id).await,
+            None => responder.default_enabled(),
+        };
+        if !enabled {
+            continue;
+        }
+
+        let limits = responder.limits(message.guild_id).await;
+        let slot = (key, responder.name().to_string());
+        let on_cooldown = registry
+            .last
+            .lock()
+            .unwrap()
+            .get(&slot)
+            .is_some_and(|t| t.elapsed() < limits.cooldown);
+        if on_cooldown || rand::rng().random::<f64>() >= limits.chance {
+            continue;
+        }
+
+        debug!("Responder {} matched message {}", responder.name(), message.id);
+        match responder.respond(ctx, framework, message).await {
+            Ok(true) => {
+                registry.last.lock().unwrap().insert(slot, Instant::now());
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Responder {} failed on message {}: {}", responder.name(), message.id, e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use super::{Limits, MessageResponder};
+use crate::handlers::intents::Matcher;
+use crate::{Data, Error};
+use async_trait::async_trait;
+use moonbot_config::config::ResponderConfig;
+use poise::serenity_prelude as serenity;
+use rand::seq::IndexedRandom;
+use std::time::Duration;
+use tracing::debug;
+
+/// A responder from `[[responders]]`, answering with a reply template and reactions.
+pub struct Configured {
+    config: ResponderConfig,
+    matcher: Matcher,
+    reactions: Vec<serenity::ReactionType>,
+}
+
+impl Configured {
+    pub fn new(config: ResponderConfig) -> Result<Self, String> {
+        if config.name.is_empty() {
+            return Err("it has no name".to_string());
+        }
+        if config.keywords.is_empty() && config.patterns.is_empty() {
+            return Err("it has no keywords or patterns".to_string());
+        }
+        let matcher = Matcher::new(&config.keywords, &config.patterns).map_err(|e| e.to_string())?;
+        let reactions = config
+            .reactions
+            .iter()
+            .map(|r| serenity::ReactionType::try_from(r.as_str()).map_err(|_| format!("`{}` isn't an emoji", r)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Configured {
+            config,
+            matcher,
+            reactions,
+        })
+    }
+}
+
+#[async_trait]
+impl MessageResponder for Configured {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn description(&self) -> &str {
+        &self.config.description
+    }
+
+    fn matches(&self, message: &serenity::Message) -> bool {
+        self.matcher.is_match(&message.content)
+    }
+
+    fn default_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    async fn limits(&self, _guild_id: Option<serenity::GuildId>) -> Limits {
+        Limits {
+            chance: self.config.chance,
+            cooldown: Duration::from_secs(self.config.cooldown),
+        }
+    }
+
+    async fn respond(
+        &self,
+        ctx: &serenity::Context,
+        _framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+    ) -> Result<bool, Error> {
+        for reaction in &self.reactions {
+            if let Err(e) = message.react(ctx, reaction.clone()).await {
+                debug!("Couldn't react to {} with {}: {}", message.id, reaction, e);
+            }
+        }
+
+        let template = self.config.replies.choose(&mut rand::rng());
+        if let (Some(template), Some(caps)) = (template, self.matcher.captures(&message.content)) {
+            let mut reply = String::new();
+            caps.expand(template, &mut reply);
+            let reply = reply.replace("{user}", &format!("<@{}>", message.author.id));
+            if !reply.trim().is_empty() {
+                message.reply(ctx, reply).await?;
+            }
+        }
+        Ok(true)
+    }
+}
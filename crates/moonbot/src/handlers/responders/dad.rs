@@ -0,0 +1,201 @@
+use super::{Limits, MessageResponder};
+use crate::{Data, Error};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use moonbot_db::ScheduledJob;
+use poise::serenity_prelude as serenity;
+use regex::{Regex, RegexBuilder};
+use std::time::Duration;
+use tracing::debug;
+
+lazy_static! {
+    static ref PATTERN: Regex = RegexBuilder::new(r"\bi(?:'| +a|’)?m +([\w\- ]*)")
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+}
+
+/// Job kind that puts a temporary nickname back.
+pub const NICK_REVERT: &str = "nick_revert";
+
+/// The [dad] config with a guild's overrides applied.
+#[derive(Debug, Clone)]
+pub struct DadSettings {
+    pub enabled: bool,
+    pub chance: f64,
+    pub cooldown: u64,
+    pub nickname_mode: String,
+    pub nickname_minutes: u64,
+}
+
+pub async fn settings(guild_id: Option<serenity::GuildId>) -> DadSettings {
+    let config = &moonbot_config::get_config().dad;
+    let overrides = match guild_id {
+        Some(guild_id) => moonbot_db::get_dad_settings(moonbot_db::get_db().await, guild_id.get() as i64).await,
+        None => Default::default(),
+    };
+    DadSettings {
+        enabled: overrides.enabled.unwrap_or(config.enabled),
+        chance: overrides.chance.unwrap_or(config.chance),
+        cooldown: overrides.cooldown_secs.map(|s| s.max(0) as u64).unwrap_or(config.cooldown),
+        nickname_mode: overrides.nickname_mode.unwrap_or_else(|| config.nickname_mode.clone()),
+        nickname_minutes: overrides.nickname_minutes.map(|m| m.max(1) as u64).unwrap_or(config.nickname_minutes),
+    }
+}
+
+/// Name captured from an "I'm X" message, if it makes a usable nickname.
+fn captured_name(content: &str) -> Option<&str> {
+    let name = PATTERN.captures(content)?.get(1)?.as_str().trim();
+    (!name.is_empty() && name.len() <= 32).then_some(name)
+}
+
+/// Answers "I'm X" with "Hi X, I'm Moonbot" and may rename the author to X.
+pub struct Dad;
+
+#[async_trait]
+impl MessageResponder for Dad {
+    fn name(&self) -> &str {
+        "dad"
+    }
+
+    fn description(&self) -> &str {
+        "Answers \"I'm X\" with \"Hi X, I'm ...\", configure it with /dad"
+    }
+
+    fn matches(&self, message: &serenity::Message) -> bool {
+        captured_name(&message.content).is_some()
+    }
+
+    fn default_enabled(&self) -> bool {
+        moonbot_config::get_config().dad.enabled
+    }
+
+    async fn enabled(&self, guild_id: serenity::GuildId) -> bool {
+        settings(Some(guild_id)).await.enabled
+    }
+
+    async fn set_enabled(&self, guild_id: serenity::GuildId, enabled: Option<bool>) -> Result<(), Error> {
+        let db = moonbot_db::get_db().await;
+        let mut overrides = moonbot_db::get_dad_settings(db, guild_id.get() as i64).await;
+        overrides.enabled = enabled;
+        moonbot_db::set_dad_settings(db, guild_id.get() as i64, overrides).await?;
+        Ok(())
+    }
+
+    async fn limits(&self, guild_id: Option<serenity::GuildId>) -> Limits {
+        let settings = settings(guild_id).await;
+        Limits {
+            chance: settings.chance,
+            cooldown: Duration::from_secs(settings.cooldown),
+        }
+    }
+
+    async fn respond(
+        &self,
+        ctx: &serenity::Context,
+        framework: poise::FrameworkContext<'_, Data, Error>,
+        message: &serenity::Message,
+    ) -> Result<bool, Error> {
+        let Some(name) = captured_name(&message.content) else {
+            return Ok(false);
+        };
+        if moonbot_db::get_dad_opt_out(framework.user_data.db, message.author.id.get() as i64).await {
+            return Ok(false);
+        }
+
+        let bot_name = ctx.cache.current_user().name.clone();
+        if let Err(e) = message
+            .reply_ping(&ctx.http, format!("Hi {}, I'm {}", name, bot_name))
+            .await
+        {
+            debug!("Couldn't send dad joke in {}: {}", message.channel_id, e);
+            return Ok(false);
+        }
+
+        if let Some(guild_id) = message.guild_id {
+            let settings = settings(Some(guild_id)).await;
+            if settings.nickname_mode != "off" {
+                rename(ctx, guild_id, message, name, &settings).await;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Give the author the nickname, and schedule putting the old one back if temporary.
+async fn rename(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    message: &serenity::Message,
+    name: &str,
+    settings: &DadSettings,
+) {
+    // Nobody can rename the owner
+    let owner = ctx.cache.guild(guild_id).map(|g| g.owner_id);
+    if owner == Some(message.author.id) {
+        return;
+    }
+
+    let builder = serenity::EditMember::new().nickname(name);
+    if let Err(e) = guild_id.edit_member(&ctx.http, message.author.id, builder).await {
+        // Usually missing Manage Nicknames or a higher role, nothing to do about it
+        debug!("Couldn't rename {} in {}: {}", message.author.id, guild_id, e);
+        return;
+    }
+    if settings.nickname_mode != "temporary" {
+        return;
+    }
+
+    // If a revert is already pending, keep the nickname from before that one
+    let db = moonbot_db::get_db().await;
+    let pending = moonbot_db::find_user_job(db, NICK_REVERT, guild_id.get() as i64, message.author.id.get() as i64)
+        .await
+        .ok()
+        .flatten();
+    let previous = match &pending {
+        Some(job) => serde_json::from_str::<serde_json::Value>(&job.message)
+            .ok()
+            .and_then(|v| v["previous"].as_str().map(String::from)),
+        None => message.member.as_ref().and_then(|m| m.nick.clone()),
+    };
+    if let Some(job) = pending {
+        let _ = moonbot_db::delete_scheduled_job(db, job.id).await;
+    }
+
+    let job = ScheduledJob {
+        id: 0,
+        kind: NICK_REVERT.to_string(),
+        guild_id: Some(guild_id.get() as i64),
+        channel_id: message.channel_id.get() as i64,
+        user_id: message.author.id.get() as i64,
+        message: serde_json::json!({ "previous": previous, "set": name }).to_string(),
+        cron: None,
+        timezone: "UTC".to_string(),
+        next_run: chrono::Utc::now() + chrono::Duration::minutes(settings.nickname_minutes as i64),
+        last_run: None,
+    };
+    if let Err(e) = moonbot_db::add_scheduled_job(db, &job).await {
+        debug!("Couldn't schedule nickname revert for {}: {}", message.author.id, e);
+    }
+    crate::handlers::scheduler::wake();
+}
+
+/// Put a temporary nickname back, unless the member changed it since.
+pub async fn revert_nickname(http: &serenity::Http, job: &ScheduledJob) {
+    let Some(guild_id) = job.guild_id.map(|g| serenity::GuildId::new(g as u64)) else {
+        return;
+    };
+    let user_id = serenity::UserId::new(job.user_id as u64);
+    let data = serde_json::from_str::<serde_json::Value>(&job.message).unwrap_or_default();
+    let Ok(member) = guild_id.member(http, user_id).await else {
+        return;
+    };
+    if member.nick.as_deref() != data["set"].as_str() {
+        return;
+    }
+    let previous = data["previous"].as_str().unwrap_or_default();
+    let builder = serenity::EditMember::new().nickname(previous);
+    if let Err(e) = guild_id.edit_member(http, user_id, builder).await {
+        debug!("Couldn't revert nickname of {} in {}: {}", user_id, guild_id, e);
+    }
+}
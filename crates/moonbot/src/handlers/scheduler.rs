@@ -0,0 +1,472 @@
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use moonbot_db::ScheduledJob;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+// Delivers /remind reminders, posts /schedule messages and reverts temporary nicknames.
+//
+// Jobs live in the `scheduled_jobs` table with their next run time, so nothing is lost
+// on restart. One task sleeps until the soonest job (or a minute at most, or until a
+// command wakes it), runs everything that is due and moves schedules to their next
+// occurrence. Runs missed while offline happen once when the bot is back: reminders
+// always, schedules only within `scheduler.catch_up_hours`.
+struct Scheduler {
+    http: Arc<serenity::Http>,
+    wake: Notify,
+}
+
+static SCHEDULER: OnceCell<Scheduler> = OnceCell::new();
+
+// Longest sleep between checks, in case a wake-up is missed
+const MAX_IDLE: Duration = Duration::from_secs(60);
+// Reminders later than this mention that they're late
+const LATE_AFTER: ChronoDuration = ChronoDuration::minutes(2);
+
+/// Start the scheduler task, call once the client is ready.
+pub fn spawn(http: Arc<serenity::Http>) {
+    if SCHEDULER
+        .set(Scheduler {
+            http,
+            wake: Notify::new(),
+        })
+        .is_err()
+    {
+        return;
+    }
+    tokio::spawn(async move {
+        let state = SCHEDULER.get().unwrap();
+        let db = moonbot_db::get_db().await;
+        loop {
+            match moonbot_db::due_scheduled_jobs(db, Utc::now()).await {
+                Ok(jobs) => {
+                    for job in jobs {
+                        run(state, job).await;
+                    }
+                }
+                Err(e) => warn!("Failed to load due jobs: {}", e),
+            }
+
+            let wait = match moonbot_db::next_scheduled_run(db).await {
+                Ok(Some(next)) => (next - Utc::now()).to_std().unwrap_or_default().min(MAX_IDLE),
+                _ => MAX_IDLE,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = state.wake.notified() => {}
+            }
+        }
+    });
+}
+
+/// Recheck the next run time after jobs are added or removed.
+pub fn wake() {
+    if let Some(state) = SCHEDULER.get() {
+        state.wake.notify_one();
+    }
+}
+
+async fn run(state: &Scheduler, job: ScheduledJob) {
+    let db = moonbot_db::get_db().await;
+    let now = Utc::now();
+    match job.kind.as_str() {
+        "reminder" => {
+            deliver_reminder(state, &job, now).await;
+            if let Err(e) = moonbot_db::delete_scheduled_job(db, job.id).await {
+                warn!("Failed to delete reminder {}: {}", job.id, e);
+            }
+        }
+        "schedule" => {
+            let catch_up = ChronoDuration::hours(moonbot_config::get_config().scheduler.catch_up_hours as i64);
+            if now - job.next_run <= catch_up {
+                let message = serenity::CreateMessage::new().content(&job.message);
+                let channel = serenity::ChannelId::new(job.channel_id as u64);
+                if let Err(e) = channel.send_message(&state.http, message).await {
+                    warn!("Failed to post schedule {} in channel {}: {}", job.id, job.channel_id, e);
+                }
+            } else {
+                info!("Skipping schedule {}, it was due {}", job.id, job.next_run);
+            }
+
+            // Missed runs are folded into this one, carry on from now
+            let next = job
+                .cron
+                .as_deref()
+                .and_then(|expr| parse_cron(expr).ok())
+                .zip(parse_timezone(&job.timezone).ok())
+                .and_then(|(schedule, tz)| next_after(&schedule, tz, now));
+            let result = match next {
+                Some(next) => moonbot_db::reschedule_job(db, job.id, next, now).await,
+                None => {
+                    info!("Schedule {} has no future runs, removing it", job.id);
+                    moonbot_db::delete_scheduled_job(db, job.id).await.map(|_| ())
+                }
+            };
+            if let Err(e) = result {
+                warn!("Failed to update schedule {}: {}", job.id, e);
+            }
+        }
+        super::responders::dad::NICK_REVERT => {
+            super::responders::dad::revert_nickname(&state.http, &job).await;
+            if let Err(e) = moonbot_db::delete_scheduled_job(db, job.id).await {
+                warn!("Failed to delete nickname revert {}: {}", job.id, e);
+            }
+        }
+        other => {
+            warn!("Unknown job kind {} for job {}, removing it", other, job.id);
+            let _ = moonbot_db::delete_scheduled_job(db, job.id).await;
+        }
+    }
+}
+
+/// Post a reminder where it was set, or DM it if that channel is gone.
+async fn deliver_reminder(state: &Scheduler, job: &ScheduledJob, now: DateTime<Utc>) {
+    let user = serenity::UserId::new(job.user_id as u64);
+    let mut content = format!("⏰ <@{}> {}", user, job.message);
+    if now - job.next_run > LATE_AFTER {
+        content.push_str(&format!("\n-# This was due <t:{}:R>, sorry I'm late.", job.next_run.timestamp()));
+    }
+    let message = serenity::CreateMessage::new()
+        .content(&content)
+        .allowed_mentions(serenity::CreateAllowedMentions::new().users([user]));
+
+    let channel = serenity::ChannelId::new(job.channel_id as u64);
+    if channel.send_message(&state.http, message.clone()).await.is_ok() {
+        return;
+    }
+    debug!("Couldn't post reminder {} in channel {}, trying DMs", job.id, job.channel_id);
+    let sent = match user.create_dm_channel(&state.http).await {
+        Ok(dm) => dm.id.send_message(&state.http, message).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        warn!("Failed to deliver reminder {} to {}: {}", job.id, user, e);
+    }
+}
+
+/// Check and store a reminder for `user_id`, to be posted in `channel_id`. The error
+/// explains to the user what's wrong.
+pub async fn add_reminder(
+    user_id: serenity::UserId,
+    guild_id: Option<serenity::GuildId>,
+    channel_id: serenity::ChannelId,
+    at: DateTime<Utc>,
+    text: &str,
+    tz: Tz,
+) -> Result<i64, String> {
+    let db = moonbot_db::get_db().await;
+    let config = &moonbot_config::get_config().scheduler;
+    let now = Utc::now();
+    if at <= now {
+        return Err("That's in the past.".to_string());
+    }
+    if at - now > ChronoDuration::days(config.max_reminder_days as i64) {
+        return Err(format!("Reminders can be at most {} days ahead.", config.max_reminder_days));
+    }
+    let pending = moonbot_db::user_reminders(db, user_id.get() as i64)
+        .await
+        .map_err(|e| {
+            warn!("Failed to count reminders for {}: {}", user_id, e);
+            "Couldn't save the reminder, try again later.".to_string()
+        })?;
+    if config.max_reminders_per_user > 0 && pending.len() >= config.max_reminders_per_user as usize {
+        return Err(format!(
+            "You already have {} reminders, cancel some with `/reminders`.",
+            pending.len()
+        ));
+    }
+
+    let id = moonbot_db::add_scheduled_job(
+        db,
+        &ScheduledJob {
+            id: 0,
+            kind: "reminder".to_string(),
+            guild_id: guild_id.map(|g| g.get() as i64),
+            channel_id: channel_id.get() as i64,
+            user_id: user_id.get() as i64,
+            message: text.to_string(),
+            cron: None,
+            timezone: tz.name().to_string(),
+            next_run: at,
+            last_run: None,
+        },
+    )
+    .await
+    .map_err(|e| {
+        warn!("Failed to save reminder for {}: {}", user_id, e);
+        "Couldn't save the reminder, try again later.".to_string()
+    })?;
+    wake();
+    Ok(id)
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    Tz::from_str(name.trim()).map_err(|_| format!("`{}` isn't a timezone, use a name like `Europe/Berlin`.", name))
+}
+
+/// A user's timezone, falling back to the configured default.
+pub async fn user_timezone(user_id: serenity::UserId) -> Tz {
+    let db = moonbot_db::get_db().await;
+    moonbot_db::get_user_timezone(db, user_id.get() as i64)
+        .await
+        .and_then(|tz| parse_timezone(&tz).ok())
+        .or_else(|| parse_timezone(&moonbot_config::get_config().scheduler.default_timezone).ok())
+        .unwrap_or(Tz::UTC)
+}
+
+fn localize(tz: Tz, naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} doesn't exist in {} (the clocks skip it).", naive, tz))
+}
+
+/// Parse when a reminder should go off: a duration from now (`in 2h`, `90 min`),
+/// a date and/or time in `tz` (`2025-12-24 18:00`, `tomorrow 9:30`, `17:00`), or an
+/// RFC 3339 timestamp.
+pub fn parse_when(input: &str, tz: Tz, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let input = input.trim().to_lowercase();
+    let relative = input.strip_prefix("in ").unwrap_or(&input);
+    if let Ok(duration) = humantime::parse_duration(relative) {
+        let duration = ChronoDuration::from_std(duration).map_err(|_| "That's too far away.".to_string())?;
+        return now.checked_add_signed(duration).ok_or_else(|| "That's too far away.".to_string());
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(&input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    const DATE_TIMES: &[&str] = &["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dt%H:%M", "%Y-%m-%d %I:%M%p"];
+    for format in DATE_TIMES {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&input, format) {
+            return localize(tz, naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        // A bare date means the morning of that day
+        return localize(tz, date.and_hms_opt(9, 0, 0).unwrap());
+    }
+
+    let today = now.with_timezone(&tz).date_naive();
+    let (day, time_part) = if let Some(rest) = input.strip_prefix("tomorrow") {
+        (Some(today.succ_opt().unwrap_or(today)), rest.trim().trim_start_matches("at ").trim())
+    } else if let Some(rest) = input.strip_prefix("today") {
+        (Some(today), rest.trim().trim_start_matches("at ").trim())
+    } else {
+        (None, input.trim_start_matches("at ").trim())
+    };
+    let mut time_part = time_part.replace(' ', "");
+    // chrono wants minutes, so `9am` and `17` become `9:00am` and `17:00`
+    if !time_part.is_empty() && !time_part.contains(':') {
+        let split = time_part.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(time_part.len());
+        time_part.insert_str(split, ":00");
+    }
+    const TIMES: &[&str] = &["%H:%M", "%I:%M%p"];
+    let time = TIMES
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(&time_part, format).ok())
+        .or_else(|| (time_part.is_empty() && day.is_some()).then(|| NaiveTime::from_hms_opt(9, 0, 0).unwrap()));
+    let Some(time) = time else {
+        return Err(format!(
+            "I don't understand `{}`. Try `in 2h`, `tomorrow 9:30`, `17:00` or `2025-12-24 18:00`.",
+            input
+        ));
+    };
+
+    let at = localize(tz, day.unwrap_or(today).and_time(time))?;
+    // A time without a day that already passed today means tomorrow
+    if day.is_none() && at <= now {
+        return localize(tz, today.succ_opt().unwrap_or(today).and_time(time));
+    }
+    Ok(at)
+}
+
+/// Parse a cron expression. The usual five fields (minute hour day month weekday) are
+/// accepted as well as the six or seven field form with seconds. Weekdays are numbered
+/// like standard cron, 0 or 7 for Sunday through 6 for Saturday, or named (`MON`).
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let mut fields: Vec<String> = expr.split_whitespace().map(String::from).collect();
+    if fields.len() == 5 {
+        fields.insert(0, "0".into());
+    }
+    if let Some(weekdays) = fields.get_mut(5) {
+        *weekdays = cron_weekdays(weekdays);
+    }
+    Schedule::from_str(&fields.join(" ")).map_err(|e| format!("That isn't a valid cron expression: {}", e))
+}
+
+// The cron crate counts weekdays from 1 for Sunday to 7 for Saturday, so numbered days,
+// ranges and steps are spelled out as its numbers. Names and anything it would reject
+// anyway are left alone.
+fn cron_weekdays(field: &str) -> String {
+    if field == "*" || field == "?" {
+        return field.to_string();
+    }
+    field
+        .split(',')
+        .map(|item| cron_weekday_item(item).unwrap_or_else(|| item.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn cron_weekday_item(item: &str) -> Option<String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, step.parse::<usize>().ok().filter(|s| *s > 0)?),
+        None => (item, 1),
+    };
+    let (start, end) = match range.split_once('-') {
+        _ if range == "*" => (0, 6),
+        Some((start, end)) => (start.parse::<u32>().ok()?, end.parse::<u32>().ok()?),
+        // `n/step` runs from n to the end of the week
+        None => {
+            let day = range.parse::<u32>().ok()?;
+            (day, if item.contains('/') { 6 } else { day })
+        }
+    };
+    if start > end || end > 7 {
+        return None;
+    }
+    let mut days: Vec<u32> = (start..=end).step_by(step).map(|day| day % 7 + 1).collect();
+    days.sort_unstable();
+    days.dedup();
+    Some(days.iter().map(|day| day.to_string()).collect::<Vec<_>>().join(","))
+}
+
+/// The first run of `schedule` in `tz` strictly after `after`.
+pub fn next_after(schedule: &Schedule, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn berlin() -> Tz {
+        parse_timezone("Europe/Berlin").unwrap()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    // Weekday names of the next `n` runs, starting from Wednesday 2025-06-04
+    fn weekdays(expr: &str, n: usize) -> Vec<String> {
+        let schedule = parse_cron(expr).unwrap();
+        let mut at = utc(2025, 6, 4, 12, 0);
+        (0..n)
+            .map(|_| {
+                at = next_after(&schedule, Tz::UTC, at).unwrap();
+                at.format("%a").to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cron_weekdays_count_from_sunday() {
+        assert_eq!(weekdays("0 9 * * 0", 2), ["Sun", "Sun"]);
+        assert_eq!(weekdays("0 9 * * 7", 2), ["Sun", "Sun"]);
+        assert_eq!(weekdays("0 9 * * 1", 2), ["Mon", "Mon"]);
+        assert_eq!(weekdays("0 9 * * 6", 2), ["Sat", "Sat"]);
+        assert_eq!(weekdays("0 9 * * MON", 2), ["Mon", "Mon"]);
+        assert_eq!(weekdays("0 9 * * SUN", 2), ["Sun", "Sun"]);
+    }
+
+    #[test]
+    fn cron_weekday_ranges_lists_and_steps() {
+        assert_eq!(weekdays("0 9 * * 1-5", 6), ["Thu", "Fri", "Mon", "Tue", "Wed", "Thu"]);
+        assert_eq!(weekdays("0 9 * * 5-7", 4), ["Fri", "Sat", "Sun", "Fri"]);
+        assert_eq!(weekdays("0 9 * * 0,3", 3), ["Sun", "Wed", "Sun"]);
+        assert_eq!(weekdays("0 9 * * 1,SAT", 3), ["Sat", "Mon", "Sat"]);
+        assert_eq!(weekdays("0 9 * * */2", 4), ["Thu", "Sat", "Sun", "Tue"]);
+        assert_eq!(weekdays("0 9 * * 1/2", 4), ["Fri", "Mon", "Wed", "Fri"]);
+        assert_eq!(weekdays("0 9 * * *", 2), ["Thu", "Fri"]);
+    }
+
+    #[test]
+    fn cron_with_seconds() {
+        let schedule = parse_cron("30 15 9 * * 0").unwrap();
+        let next = next_after(&schedule, Tz::UTC, utc(2025, 6, 4, 12, 0)).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2025, 6, 8, 9, 15, 30).unwrap());
+    }
+
+    #[test]
+    fn cron_rejects_invalid() {
+        assert!(parse_cron("0 9 * * 8").is_err());
+        assert!(parse_cron("0 9 * * 6-1").is_err());
+        assert!(parse_cron("61 9 * * *").is_err());
+        assert!(parse_cron("every day").is_err());
+    }
+
+    #[test]
+    fn next_after_follows_the_timezone_across_dst() {
+        let schedule = parse_cron("0 9 * * *").unwrap();
+        // 9:00 in Berlin is 8:00 UTC in winter and 7:00 UTC in summer
+        let before = next_after(&schedule, berlin(), utc(2025, 3, 28, 12, 0)).unwrap();
+        assert_eq!(before, utc(2025, 3, 29, 8, 0));
+        let after = next_after(&schedule, berlin(), before).unwrap();
+        assert_eq!(after, utc(2025, 3, 30, 7, 0));
+    }
+
+    #[test]
+    fn next_after_skips_times_the_clocks_skip() {
+        let schedule = parse_cron("30 2 * * *").unwrap();
+        // 2:30 doesn't exist in Berlin on 2025-03-30
+        let next = next_after(&schedule, berlin(), utc(2025, 3, 29, 12, 0)).unwrap();
+        assert_eq!(next, utc(2025, 3, 31, 0, 30));
+        // and happens twice on 2025-10-26, the job runs once
+        let first = next_after(&schedule, berlin(), utc(2025, 10, 25, 12, 0)).unwrap();
+        assert_eq!(first, utc(2025, 10, 26, 0, 30));
+        assert_eq!(next_after(&schedule, berlin(), first).unwrap(), utc(2025, 10, 27, 1, 30));
+    }
+
+    #[test]
+    fn when_relative() {
+        let now = utc(2025, 6, 4, 12, 0);
+        assert_eq!(parse_when("in 2h", Tz::UTC, now).unwrap(), utc(2025, 6, 4, 14, 0));
+        assert_eq!(parse_when("90 min", Tz::UTC, now).unwrap(), utc(2025, 6, 4, 13, 30));
+    }
+
+    #[test]
+    fn when_times_of_day() {
+        // 12:00 UTC is 14:00 in Berlin
+        let now = utc(2025, 6, 4, 12, 0);
+        assert_eq!(parse_when("17:00", berlin(), now).unwrap(), utc(2025, 6, 4, 15, 0));
+        assert_eq!(parse_when("at 5pm", berlin(), now).unwrap(), utc(2025, 6, 4, 15, 0));
+        // Already passed today, so tomorrow
+        assert_eq!(parse_when("9am", berlin(), now).unwrap(), utc(2025, 6, 5, 7, 0));
+        assert_eq!(parse_when("tomorrow 9:30", berlin(), now).unwrap(), utc(2025, 6, 5, 7, 30));
+        assert_eq!(parse_when("tomorrow", berlin(), now).unwrap(), utc(2025, 6, 5, 7, 0));
+        assert_eq!(parse_when("today at 20:15", berlin(), now).unwrap(), utc(2025, 6, 4, 18, 15));
+    }
+
+    #[test]
+    fn when_dates() {
+        let now = utc(2025, 6, 4, 12, 0);
+        assert_eq!(parse_when("2025-12-24 18:00", berlin(), now).unwrap(), utc(2025, 12, 24, 17, 0));
+        assert_eq!(parse_when("2025-12-24", berlin(), now).unwrap(), utc(2025, 12, 24, 8, 0));
+        assert_eq!(parse_when("2025-12-24T18:00:00Z", berlin(), now).unwrap(), utc(2025, 12, 24, 18, 0));
+    }
+
+    #[test]
+    fn when_across_dst() {
+        // The night the clocks go forward, tomorrow morning is an hour closer in UTC
+        let now = utc(2025, 3, 29, 20, 0);
+        assert_eq!(parse_when("tomorrow 9:00", berlin(), now).unwrap(), utc(2025, 3, 30, 7, 0));
+        assert!(parse_when("2025-03-30 02:30", berlin(), now).is_err());
+        // A repeated time means the first one
+        assert_eq!(parse_when("2025-10-26 02:30", berlin(), now).unwrap(), utc(2025, 10, 26, 0, 30));
+    }
+
+    #[test]
+    fn when_rejects_nonsense() {
+        assert!(parse_when("next blue moon", Tz::UTC, utc(2025, 6, 4, 12, 0)).is_err());
+    }
+}
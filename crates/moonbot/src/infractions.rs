@@ -0,0 +1,321 @@
+use crate::Error;
+use chrono::{Duration as ChronoDuration, Utc};
+use moonbot_db::Infraction;
+use poise::serenity_prelude as serenity;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// Warnings, timeouts, kicks and bans, shared by the moderation commands and the mod-log.
+//
+// Every punishment is recorded in the `infractions` table. Warnings can escalate: the
+// warning that brings a user to exactly the number of active warnings an
+// `infractions.escalation` rule asks for applies its action automatically. Rules are steps
+// on a ladder, so warnings past a rule don't repeat it, and one that a pardon skipped
+// over only fires when the count reaches it again. With `infractions.affect_trust` each infraction also
+// lowers the user's trust level, and a pardon gives it back.
+
+/// Longest timeout Discord allows.
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Warn,
+    Timeout(Duration),
+    Kick,
+    /// With how many days of messages to delete
+    Ban(u8),
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Warn => "warn",
+            Kind::Timeout(_) => "timeout",
+            Kind::Kick => "kick",
+            Kind::Ban(_) => "ban",
+        }
+    }
+
+    fn past_tense(&self) -> &'static str {
+        match self {
+            Kind::Warn => "warned",
+            Kind::Timeout(_) => "timed out",
+            Kind::Kick => "kicked",
+            Kind::Ban(_) => "banned",
+        }
+    }
+}
+
+/// How much an infraction of a kind lowers the user's trust level.
+fn trust_cost(kind: &str) -> i32 {
+    match kind {
+        "warn" => 1,
+        "timeout" => 2,
+        "kick" => 3,
+        "ban" => 5,
+        _ => 0,
+    }
+}
+
+/// One infraction that was applied, for the reply.
+#[derive(Debug, Clone)]
+pub struct Applied {
+    pub id: i64,
+    pub kind: Kind,
+    /// Whether an escalation rule applied it
+    pub automatic: bool,
+}
+
+impl Applied {
+    pub fn describe(&self) -> String {
+        let what = match self.kind {
+            Kind::Timeout(d) => format!("timed out for {}", humantime::format_duration(d)),
+            kind => kind.past_tense().to_string(),
+        };
+        if self.automatic {
+            format!("**#{}** {} automatically", self.id, what)
+        } else {
+            format!("**#{}** {}", self.id, what)
+        }
+    }
+}
+
+async fn adjust_trust(user_id: serenity::UserId, delta: i32) {
+    if !moonbot_config::get_config().infractions.affect_trust || delta == 0 {
+        return;
+    }
+    let db = moonbot_db::get_db().await;
+    let uid = user_id.get() as i64;
+    let mut profile = moonbot_db::get_user_profile(db, uid).await.unwrap_or_default();
+    profile.trust_level = (profile.trust_level + delta).clamp(-5, 5);
+    let _ = moonbot_db::upsert_user_profile(db, uid, profile).await;
+}
+
+/// Carry out one punishment and record it.
+async fn enforce(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    moderator: Option<serenity::UserId>,
+    kind: Kind,
+    reason: Option<&str>,
+) -> Result<i64, Error> {
+    let audit_reason = reason.unwrap_or("No reason given");
+
+    let guild_name = guild_id.name(&ctx.cache).unwrap_or_else(|| "a server".to_string());
+    let mut text = match kind {
+        Kind::Timeout(d) => format!(
+            "You were timed out in **{}** for {}.",
+            guild_name,
+            humantime::format_duration(d)
+        ),
+        _ => format!("You were {} in **{}**.", kind.past_tense(), guild_name),
+    };
+    if let Some(reason) = reason {
+        text.push_str(&format!("\nReason: {}", reason));
+    }
+
+    // After a kick or ban we may no longer share a server, so they hear about those first
+    // and get a correction if it doesn't go through. Everything else waits until it did.
+    let early = matches!(kind, Kind::Kick | Kind::Ban(_));
+    let dm = if early { notify(ctx, user_id, kind, &text).await } else { None };
+
+    let done: Result<(), Error> = async {
+        match kind {
+            Kind::Warn => {}
+            Kind::Timeout(d) => {
+                let until =
+                    serenity::Timestamp::from_unix_timestamp(Utc::now().timestamp() + d.as_secs() as i64)?;
+                let edit = serenity::EditMember::new()
+                    .disable_communication_until_datetime(until)
+                    .audit_log_reason(audit_reason);
+                guild_id.edit_member(ctx, user_id, edit).await?;
+            }
+            Kind::Kick => guild_id.kick_with_reason(ctx, user_id, audit_reason).await?,
+            Kind::Ban(days) => guild_id.ban_with_reason(ctx, user_id, days, audit_reason).await?,
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = done {
+        if let Some(dm) = dm {
+            let correction = format!("Never mind, you weren't {} in **{}** after all.", kind.past_tense(), guild_name);
+            let _ = dm.say(ctx, correction).await;
+        }
+        return Err(e);
+    }
+
+    let id = moonbot_db::add_infraction(
+        moonbot_db::get_db().await,
+        &Infraction {
+            id: 0,
+            guild_id: guild_id.get() as i64,
+            user_id: user_id.get() as i64,
+            moderator_id: moderator.map(|m| m.get() as i64),
+            kind: kind.name().to_string(),
+            reason: reason.map(String::from),
+            duration_secs: match kind {
+                Kind::Timeout(d) => Some(d.as_secs() as i64),
+                _ => None,
+            },
+            active: true,
+            pardoned_by: None,
+            pardoned_at: None,
+            created_at: Utc::now(),
+        },
+    )
+    .await?;
+    adjust_trust(user_id, -trust_cost(kind.name())).await;
+    if !early {
+        notify(ctx, user_id, kind, &text).await;
+    }
+    info!("{} {} in {} (#{})", user_id, kind.past_tense(), guild_id, id);
+    Ok(id)
+}
+
+/// DM a user about an infraction, returning the DM channel if it went through.
+async fn notify(
+    ctx: &serenity::Context,
+    user_id: serenity::UserId,
+    kind: Kind,
+    text: &str,
+) -> Option<serenity::ChannelId> {
+    let sent = match user_id.create_dm_channel(ctx).await {
+        Ok(dm) => dm.id.say(ctx, text).await.map(|_| dm.id),
+        Err(e) => Err(e),
+    };
+    match sent {
+        Ok(channel) => Some(channel),
+        Err(e) => {
+            debug!("Couldn't DM {} about their {}: {}", user_id, kind.name(), e);
+            None
+        }
+    }
+}
+
+/// What `punish` did.
+#[derive(Debug)]
+pub struct Punished {
+    pub applied: Vec<Applied>,
+    /// Why the escalation a warning triggered couldn't be applied, the warning itself stands
+    pub escalation_error: Option<Error>,
+}
+
+/// Punish a user, then apply any escalation rule a warning triggers.
+pub async fn punish(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    moderator: Option<serenity::UserId>,
+    kind: Kind,
+    reason: Option<&str>,
+) -> Result<Punished, Error> {
+    let id = enforce(ctx, guild_id, user_id, moderator, kind, reason).await?;
+    let mut punished = Punished {
+        applied: vec![Applied {
+            id,
+            kind,
+            automatic: false,
+        }],
+        escalation_error: None,
+    };
+    if kind == Kind::Warn {
+        match escalate(ctx, guild_id, user_id).await {
+            Ok(Some(applied)) => punished.applied.push(applied),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Couldn't escalate the warnings of {} in {}: {}", user_id, guild_id, e);
+                punished.escalation_error = Some(e);
+            }
+        }
+    }
+    Ok(punished)
+}
+
+/// Apply the escalation rule the user's active warnings call for, if any.
+async fn escalate(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+) -> Result<Option<Applied>, Error> {
+    let config = &moonbot_config::get_config().infractions;
+    let since = (config.escalation_window_days > 0)
+        .then(|| Utc::now() - ChronoDuration::days(config.escalation_window_days as i64));
+    let warnings = moonbot_db::count_active_warnings(
+        moonbot_db::get_db().await,
+        guild_id.get() as i64,
+        user_id.get() as i64,
+        since,
+    )
+    .await?;
+    // Only the exact count, see the top of this file
+    let Some(rule) = config.escalation.iter().find(|r| r.warnings as u64 == warnings) else {
+        return Ok(None);
+    };
+    let kind = match rule.action.as_str() {
+        "timeout" => Kind::Timeout(Duration::from_secs(rule.duration_minutes * 60).min(MAX_TIMEOUT)),
+        "kick" => Kind::Kick,
+        "ban" => Kind::Ban(0),
+        other => {
+            info!("Ignoring escalation with unknown action {}", other);
+            return Ok(None);
+        }
+    };
+    let reason = format!("{} warnings", warnings);
+    let id = enforce(ctx, guild_id, user_id, None, kind, Some(&reason)).await?;
+    Ok(Some(Applied {
+        id,
+        kind,
+        automatic: true,
+    }))
+}
+
+/// Whether a request failed because the member or ban it was about is already gone.
+fn already_gone(e: &serenity::Error) -> bool {
+    // Unknown Member and Unknown Ban
+    matches!(
+        e,
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(r)) if matches!(r.error.code, 10007 | 10026)
+    )
+}
+
+/// Pardon an infraction: a running timeout or a ban is lifted, then it stops counting.
+/// Returns false if it was already pardoned, and the error if the punishment couldn't be
+/// lifted, in which case it isn't pardoned either.
+pub async fn pardon(
+    ctx: &serenity::Context,
+    infraction: &Infraction,
+    moderator: serenity::UserId,
+) -> Result<bool, Error> {
+    if !infraction.active {
+        return Ok(false);
+    }
+    let guild_id = serenity::GuildId::new(infraction.guild_id as u64);
+    let user_id = serenity::UserId::new(infraction.user_id as u64);
+    let reason = format!("Pardoned infraction #{}", infraction.id);
+    let lifted = match infraction.kind.as_str() {
+        "timeout" => {
+            let ends = infraction.created_at + ChronoDuration::seconds(infraction.duration_secs.unwrap_or(0));
+            if ends > Utc::now() {
+                let edit = serenity::EditMember::new().enable_communication().audit_log_reason(&reason);
+                guild_id.edit_member(ctx, user_id, edit).await.map(|_| ())
+            } else {
+                Ok(())
+            }
+        }
+        "ban" => guild_id.unban(ctx, user_id).await,
+        _ => Ok(()),
+    };
+    match lifted {
+        Err(e) if already_gone(&e) => debug!("Nothing to lift for {}: {}", user_id, e),
+        Err(e) => return Err(e.into()),
+        Ok(()) => {}
+    }
+
+    let db = moonbot_db::get_db().await;
+    if !moonbot_db::pardon_infraction(db, infraction.id, moderator.get() as i64).await? {
+        return Ok(false);
+    }
+    adjust_trust(user_id, trust_cost(&infraction.kind)).await;
+    Ok(true)
+}
@@ -0,0 +1,68 @@
+use crate::Error;
+use async_openai::types::{
+    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateEmbeddingRequest, CreateEmbeddingResponse,
+    CreateImageRequest, CreateModerationRequest, CreateModerationResponse, ImagesResponse,
+};
+use async_trait::async_trait;
+use moonbot_config::config::SunbotConfig;
+use std::sync::Arc;
+
+mod mock;
+mod openai;
+
+pub use self::mock::MockBackend;
+pub use self::openai::OpenAIBackend;
+
+// Chat, image, embedding and moderation requests go through a backend instead of async-openai's client
+// directly, so the bot can run without an API: `backend = "mock"` answers from
+// `[openai.mock]` instead.
+//
+// Requests and responses are async-openai's types everywhere, since the commands already
+// build them with its builders; the mock fills in what a real API would send back.
+
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Short name for logs and status
+    fn name(&self) -> &'static str;
+
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Error>;
+
+    async fn images(&self, request: CreateImageRequest) -> Result<ImagesResponse, Error>;
+
+    // Nothing embeds text yet, retrieval is full text search
+    #[allow(dead_code)]
+    async fn embeddings(&self, request: CreateEmbeddingRequest) -> Result<CreateEmbeddingResponse, Error>;
+
+    async fn moderations(&self, request: CreateModerationRequest) -> Result<CreateModerationResponse, Error>;
+}
+
+/// The async-openai client for the configured API, if there's a key or base and the
+/// backend isn't the mock.
+fn openai_client(config: &SunbotConfig) -> Option<async_openai::Client<async_openai::config::OpenAIConfig>> {
+    if config.openai.backend == "mock" || (config.openai.api_key.is_empty() && config.openai.api_base.is_empty()) {
+        return None;
+    }
+    let mut cfg = async_openai::config::OpenAIConfig::new();
+    if !config.openai.api_key.is_empty() {
+        cfg = cfg.with_api_key(config.openai.api_key.as_str());
+    }
+    if !config.openai.api_base.is_empty() {
+        cfg = cfg.with_api_base(config.openai.api_base.as_str());
+    }
+    Some(async_openai::Client::with_config(cfg))
+}
+
+/// The configured backend, none when OpenAI isn't set up.
+pub fn from_config(config: &SunbotConfig) -> Option<Arc<dyn LlmBackend>> {
+    match config.openai.backend.as_str() {
+        "mock" => {
+            tracing::info!("Using the mock LLM backend, nothing is sent to an API");
+            Some(Arc::new(MockBackend::new(&config.openai.mock)))
+        }
+        "" | "openai" => Some(Arc::new(OpenAIBackend::new(openai_client(config)?))),
+        other => {
+            tracing::warn!("Unknown LLM backend \"{}\", OpenAI features are disabled", other);
+            None
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use super::LlmBackend;
+use crate::handlers::moderation::CATEGORIES;
+use crate::Error;
+use async_openai::types::{
+    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageContent,
+    ChatCompletionRequestSystemMessageContentPart, ChatCompletionRequestUserMessageContent,
+    ChatCompletionRequestUserMessageContentPart, CreateChatCompletionRequest, CreateChatCompletionResponse,
+    CreateEmbeddingRequest, CreateEmbeddingResponse, CreateImageRequest, CreateModerationRequest,
+    CreateModerationResponse, EmbeddingInput, Image, ImageResponseFormat, ImagesResponse, ModerationContentPart,
+    ModerationInput,
+};
+use async_trait::async_trait;
+use moonbot_config::config::{MockReply, OpenAIMock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A 1x1 transparent PNG, the mock's every image.
+const PIXEL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+/// Answers without any API, the same way every time: scripted replies, an echo of the last
+/// message, embeddings derived from the text, moderation that only flags `flag_on`, or
+/// failures and latency on purpose.
+pub struct MockBackend {
+    echo: bool,
+    default_reply: String,
+    replies: Vec<MockReply>,
+    latency: Duration,
+    fail_every: u32,
+    fail_on: String,
+    dimensions: u32,
+    flag_on: String,
+    requests: AtomicU32,
+}
+
+impl MockBackend {
+    pub fn new(config: &OpenAIMock) -> Self {
+        MockBackend {
+            echo: config.echo,
+            default_reply: config.default_reply.clone(),
+            replies: config.replies.clone(),
+            latency: Duration::from_millis(config.latency_ms),
+            fail_every: config.fail_every,
+            fail_on: config.fail_on.clone(),
+            dimensions: config.dimensions.max(1),
+            flag_on: config.flag_on.to_lowercase(),
+            requests: AtomicU32::new(0),
+        }
+    }
+
+    /// Wait and maybe fail, like every request to a real API might.
+    async fn request(&self, text: &str) -> Result<(), Error> {
+        let n = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        if self.fail_every > 0 && n.is_multiple_of(self.fail_every) {
+            return Err(format!("mock failure, request {} of every {}", n, self.fail_every).into());
+        }
+        if !self.fail_on.is_empty() && text.contains(&self.fail_on) {
+            return Err(format!("mock failure, the request mentions \"{}\"", self.fail_on).into());
+        }
+        Ok(())
+    }
+
+    fn reply(&self, system: &str, last: &str) -> String {
+        let lower = last.to_lowercase();
+        if let Some(scripted) = self.replies.iter().find(|r| lower.contains(&r.contains.to_lowercase())) {
+            return scripted.reply.clone();
+        }
+        if !self.echo {
+            return self.default_reply.clone();
+        }
+        // The prompt's hash tells apart replies to the same message under different prompts
+        if system.is_empty() {
+            format!("[mock] {}", last)
+        } else {
+            format!("[mock] {} (prompt {:08x})", last, fnv1a(system) as u32)
+        }
+    }
+}
+
+/// A hash that stays the same across builds, unlike std's.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// A unit vector that only depends on the text.
+fn embedding(text: &str, dimensions: u32) -> Vec<f32> {
+    let mut state = fnv1a(text) | 1;
+    let values: Vec<f32> = (0..dimensions)
+        .map(|_| {
+            // xorshift
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+        })
+        .collect();
+    let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt().max(f32::EPSILON);
+    values.into_iter().map(|v| v / norm).collect()
+}
+
+fn system_text(messages: &[ChatCompletionRequestMessage]) -> String {
+    let mut out = vec![];
+    for message in messages {
+        if let ChatCompletionRequestMessage::System(message) = message {
+            match &message.content {
+                ChatCompletionRequestSystemMessageContent::Text(text) => out.push(text.clone()),
+                ChatCompletionRequestSystemMessageContent::Array(parts) => {
+                    for ChatCompletionRequestSystemMessageContentPart::Text(part) in parts {
+                        out.push(part.text.clone());
+                    }
+                }
+            }
+        }
+    }
+    out.join("\n")
+}
+
+fn last_user_text(messages: &[ChatCompletionRequestMessage]) -> String {
+    messages
+        .iter()
+        .rev()
+        .find_map(|message| match message {
+            ChatCompletionRequestMessage::User(message) => Some(match &message.content {
+                ChatCompletionRequestUserMessageContent::Text(text) => text.clone(),
+                ChatCompletionRequestUserMessageContent::Array(parts) => parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ChatCompletionRequestUserMessageContentPart::Text(part) => Some(part.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn now() -> u32 {
+    chrono::Utc::now().timestamp() as u32
+}
+
+#[async_trait]
+impl LlmBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Error> {
+        let last = last_user_text(&request.messages);
+        self.request(&last).await?;
+        let reply = self.reply(&system_text(&request.messages), &last);
+        let response = serde_json::json!({
+            "id": format!("mock-{}", self.requests.load(Ordering::SeqCst)),
+            "object": "chat.completion",
+            "created": now(),
+            "model": request.model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": reply },
+                "finish_reason": "stop",
+            }],
+        });
+        Ok(serde_json::from_value(response)?)
+    }
+
+    async fn images(&self, request: CreateImageRequest) -> Result<ImagesResponse, Error> {
+        self.request(&request.prompt).await?;
+        let image = match request.response_format {
+            Some(ImageResponseFormat::B64Json) => {
+                Image::B64Json { b64_json: Arc::new(PIXEL_PNG.to_string()), revised_prompt: None }
+            }
+            _ => Image::Url { url: format!("data:image/png;base64,{}", PIXEL_PNG), revised_prompt: None },
+        };
+        let data = (0..request.n.unwrap_or(1).max(1)).map(|_| Arc::new(image.clone())).collect();
+        Ok(ImagesResponse { created: now(), data })
+    }
+
+    async fn embeddings(&self, request: CreateEmbeddingRequest) -> Result<CreateEmbeddingResponse, Error> {
+        let inputs: Vec<String> = match &request.input {
+            EmbeddingInput::String(text) => vec![text.clone()],
+            EmbeddingInput::StringArray(texts) => texts.clone(),
+            EmbeddingInput::IntegerArray(tokens) => vec![format!("{:?}", tokens)],
+            EmbeddingInput::ArrayOfIntegerArray(tokens) => tokens.iter().map(|t| format!("{:?}", t)).collect(),
+        };
+        self.request(&inputs.join("\n")).await?;
+        let dimensions = request.dimensions.unwrap_or(self.dimensions);
+        let data: Vec<serde_json::Value> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                serde_json::json!({ "index": i, "object": "embedding", "embedding": embedding(text, dimensions) })
+            })
+            .collect();
+        let response = serde_json::json!({
+            "object": "list",
+            "model": request.model,
+            "data": data,
+            "usage": { "prompt_tokens": 0, "total_tokens": 0 },
+        });
+        Ok(serde_json::from_value(response)?)
+    }
+
+    async fn moderations(&self, request: CreateModerationRequest) -> Result<CreateModerationResponse, Error> {
+        let inputs: Vec<String> = match &request.input {
+            ModerationInput::String(text) => vec![text.clone()],
+            ModerationInput::StringArray(texts) => texts.clone(),
+            // One result for all the parts, images are never flagged
+            ModerationInput::MultiModal(parts) => vec![parts
+                .iter()
+                .filter_map(|part| match part {
+                    ModerationContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ")],
+        };
+        self.request(&inputs.join("\n")).await?;
+        let results: Vec<serde_json::Value> = inputs
+            .iter()
+            .map(|text| {
+                let flagged = !self.flag_on.is_empty() && text.to_lowercase().contains(&self.flag_on);
+                let mut categories = serde_json::Map::new();
+                let mut scores = serde_json::Map::new();
+                let mut input_types = serde_json::Map::new();
+                for &category in CATEGORIES {
+                    let hit = flagged && category == "harassment";
+                    categories.insert(category.to_string(), hit.into());
+                    scores.insert(category.to_string(), if hit { 0.99 } else { 0.0 }.into());
+                    let applied: &[&str] = if hit { &["text"] } else { &[] };
+                    input_types.insert(category.to_string(), applied.into());
+                }
+                serde_json::json!({
+                    "flagged": flagged,
+                    "categories": categories,
+                    "category_scores": scores,
+                    "category_applied_input_types": input_types,
+                })
+            })
+            .collect();
+        let response = serde_json::json!({
+            "id": format!("modr-mock-{}", self.requests.load(Ordering::SeqCst)),
+            "model": request.model.as_deref().unwrap_or("mock"),
+            "results": results,
+        });
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::openai::{self as generation, chat_messages, Generation, Turn};
+    use async_openai::types::{
+        CreateChatCompletionRequestArgs, CreateEmbeddingRequestArgs, CreateModerationRequestArgs,
+    };
+
+    fn backend(configure: impl FnOnce(&mut OpenAIMock)) -> MockBackend {
+        let mut config = OpenAIMock::default();
+        configure(&mut config);
+        MockBackend::new(&config)
+    }
+
+    fn messages(system: &str, text: &str) -> Vec<ChatCompletionRequestMessage> {
+        let turn = Turn { name: "tester".to_string(), from_bot: false, content: text.to_string(), image_urls: vec![] };
+        chat_messages(system.to_string(), None, &[turn])
+    }
+
+    async fn ask(mock: &MockBackend, system: &str, text: &str) -> Result<String, Error> {
+        let request =
+            CreateChatCompletionRequestArgs::default().model("test").messages(messages(system, text)).build()?;
+        let response = mock.chat(request).await?;
+        Ok(response.choices[0].message.content.clone().unwrap_or_default())
+    }
+
+    fn generation() -> Generation {
+        Generation {
+            model: "test".to_string(),
+            max_tokens: 100,
+            temperature: 0.0,
+            frequency_penalty: 0.0,
+            user: "test".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_replies_come_first() {
+        let mock = backend(|c| {
+            c.replies = vec![
+                MockReply { contains: "weather".to_string(), reply: "Sunny all week.".to_string() },
+                MockReply { contains: String::new(), reply: "Anything else.".to_string() },
+            ];
+        });
+        assert_eq!(ask(&mock, "", "How's the WEATHER?").await.unwrap(), "Sunny all week.");
+        // An empty `contains` matches anything
+        assert_eq!(ask(&mock, "", "hello").await.unwrap(), "Anything else.");
+    }
+
+    #[tokio::test]
+    async fn echoes_with_the_prompt_hash() {
+        let mock = backend(|_| {});
+        assert_eq!(ask(&mock, "", "hello").await.unwrap(), "[mock] hello");
+
+        let first = ask(&mock, "Be nice.", "hello").await.unwrap();
+        assert_eq!(first, format!("[mock] hello (prompt {:08x})", fnv1a("Be nice.") as u32));
+        assert_eq!(ask(&mock, "Be nice.", "hello").await.unwrap(), first);
+        assert_ne!(ask(&mock, "Be mean.", "hello").await.unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn default_reply_without_echo() {
+        let mock = backend(|c| {
+            c.echo = false;
+            c.default_reply = "No idea.".to_string();
+        });
+        assert_eq!(ask(&mock, "Be nice.", "hello").await.unwrap(), "No idea.");
+    }
+
+    #[tokio::test]
+    async fn fails_every_nth_request() {
+        let mock = backend(|c| c.fail_every = 3);
+        let mut failed = vec![];
+        for _ in 0..6 {
+            failed.push(ask(&mock, "", "hello").await.is_err());
+        }
+        assert_eq!(failed, [false, false, true, false, false, true]);
+    }
+
+    #[tokio::test]
+    async fn fails_on_matching_requests() {
+        let mock = backend(|c| c.fail_on = "explode".to_string());
+        assert!(ask(&mock, "", "please explode").await.is_err());
+        assert!(ask(&mock, "", "please don't").await.is_ok());
+        // Only the last user message counts, not the prompt
+        assert!(ask(&mock, "never explode", "hi").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn complete_through_the_mock() {
+        let mock = backend(|_| {});
+        let reply = generation::complete(&mock, &messages("", "hi there"), &generation()).await.unwrap();
+        assert_eq!(reply, "[mock] hi there");
+    }
+
+    #[tokio::test]
+    async fn complete_retries_mock_failures() {
+        // The second request fails and the retry gets the third
+        let mock = backend(|c| c.fail_every = 2);
+        ask(&mock, "", "warm up").await.unwrap();
+        let reply = generation::complete(&mock, &messages("", "hi"), &generation()).await.unwrap();
+        assert_eq!(reply, "[mock] hi");
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 3);
+
+        // Failing every time gives up after three tries
+        let mock = backend(|c| c.fail_every = 1);
+        assert!(generation::complete(&mock, &messages("", "hi"), &generation()).await.is_err());
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 3);
+    }
+
+    async fn embed(mock: &MockBackend, texts: &[&str], dimensions: Option<u32>) -> Vec<Vec<f32>> {
+        let mut request = CreateEmbeddingRequestArgs::default();
+        request.model("test").input(texts.iter().map(|t| t.to_string()).collect::<Vec<_>>());
+        if let Some(dimensions) = dimensions {
+            request.dimensions(dimensions);
+        }
+        let response = mock.embeddings(request.build().unwrap()).await.unwrap();
+        response.data.into_iter().map(|e| e.embedding).collect()
+    }
+
+    #[tokio::test]
+    async fn embeddings_only_depend_on_the_text() {
+        let mock = backend(|_| {});
+        let first = embed(&mock, &["hello", "world", "hello"], None).await;
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[0].len(), 256);
+        assert_eq!(first[0], first[2]);
+        assert_ne!(first[0], first[1]);
+        assert_eq!(embed(&backend(|_| {}), &["hello"], None).await[0], first[0]);
+        let norm = first[1].iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-4);
+
+        assert_eq!(embed(&mock, &["hello"], Some(8)).await[0].len(), 8);
+        assert_eq!(embed(&backend(|c| c.dimensions = 16), &["hello"], None).await[0].len(), 16);
+    }
+
+    #[tokio::test]
+    async fn moderation_only_flags_flag_on() {
+        let request = |texts: &[&str]| {
+            CreateModerationRequestArgs::default()
+                .input(texts.iter().map(|t| t.to_string()).collect::<Vec<_>>())
+                .build()
+                .unwrap()
+        };
+        let response = backend(|_| {}).moderations(request(&["you are terrible"])).await.unwrap();
+        assert!(!response.results[0].flagged);
+        assert_eq!(response.results[0].category_scores.harassment, 0.0);
+
+        let mock = backend(|c| c.flag_on = "Terrible".to_string());
+        let response = mock.moderations(request(&["you are TERRIBLE", "you are nice"])).await.unwrap();
+        assert!(response.results[0].flagged);
+        assert!(response.results[0].categories.harassment);
+        assert!(response.results[0].category_scores.harassment > 0.9);
+        assert!(!response.results[0].categories.hate);
+        assert!(!response.results[1].flagged);
+    }
+}
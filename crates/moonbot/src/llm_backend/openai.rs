@@ -0,0 +1,42 @@
+use super::LlmBackend;
+use crate::Error;
+use async_openai::config::OpenAIConfig;
+use async_openai::types::{
+    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateEmbeddingRequest, CreateEmbeddingResponse,
+    CreateImageRequest, CreateModerationRequest, CreateModerationResponse, ImagesResponse,
+};
+use async_trait::async_trait;
+
+/// Sends requests to OpenAI or a compatible API.
+pub struct OpenAIBackend {
+    client: async_openai::Client<OpenAIConfig>,
+}
+
+impl OpenAIBackend {
+    pub fn new(client: async_openai::Client<OpenAIConfig>) -> Self {
+        OpenAIBackend { client }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAIBackend {
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse, Error> {
+        Ok(self.client.chat().create(request).await?)
+    }
+
+    async fn images(&self, request: CreateImageRequest) -> Result<ImagesResponse, Error> {
+        Ok(self.client.images().create(request).await?)
+    }
+
+    async fn embeddings(&self, request: CreateEmbeddingRequest) -> Result<CreateEmbeddingResponse, Error> {
+        Ok(self.client.embeddings().create(request).await?)
+    }
+
+    async fn moderations(&self, request: CreateModerationRequest) -> Result<CreateModerationResponse, Error> {
+        Ok(self.client.moderations().create(request).await?)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::music_backend::MusicBackend;
+use lavalink_rs::{model::track::TrackData, prelude::*};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// A single timed line, `timestamp` in milliseconds from the start of the track.
+#[derive(Debug, Clone)]
+pub struct LyricsLine {
+    pub timestamp: u64,
+    pub line: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lyrics {
+    pub source: String,
+    pub text: String,
+    /// Present when the source has timed lyrics
+    pub lines: Option<Vec<LyricsLine>>,
+}
+
+// Response of the LavaLyrics plugin
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LavaLyricsResponse {
+    source_name: String,
+    provider: Option<String>,
+    text: Option<String>,
+    lines: Option<Vec<LavaLyricsLine>>,
+}
+
+#[derive(Deserialize)]
+struct LavaLyricsLine {
+    timestamp: u64,
+    line: String,
+}
+
+// Response of an LRCLIB compatible provider
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibResponse {
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+/// Look up lyrics for the track currently playing in a guild. The LavaLyrics plugin is
+/// only asked when the music plays through Lavalink.
+pub async fn fetch(
+    music: &dyn MusicBackend,
+    guild_id: impl Into<GuildId>,
+    track: &TrackData,
+) -> Option<Lyrics> {
+    let guild_id = guild_id.into();
+    let config = &moonbot_config::get_config().music.lyrics;
+    if let Some(lavalink) = music.lavalink().filter(|_| config.use_lavalink) {
+        if let Some(lyrics) = fetch_lavalink(lavalink, guild_id).await {
+            return Some(lyrics);
+        }
+    }
+    if !config.provider_url.is_empty() {
+        return fetch_provider(&config.provider_url, track).await;
+    }
+    None
+}
+
+async fn fetch_lavalink(lavalink: &LavalinkClient, guild_id: GuildId) -> Option<Lyrics> {
+    let node = lavalink.get_node_for_guild(guild_id).await;
+    let session_id = node.session_id.load_full();
+    let path = format!("/sessions/{}/players/{}/track/lyrics", session_id, guild_id.0);
+    let uri = node.http.path_to_uri(&path, true).ok()?;
+
+    // Without the plugin this is a 404 error body, and a 204 with no lyrics
+    let body = match node.http.raw_request(reqwest::Method::GET, uri, None::<&()>).await {
+        Ok(body) => body,
+        Err(e) => {
+            debug!("LavaLyrics request failed: {}", e);
+            return None;
+        }
+    };
+    let resp: LavaLyricsResponse = serde_json::from_str(&body).ok()?;
+
+    let lines = resp.lines.filter(|l| !l.is_empty()).map(|l| {
+        l.into_iter()
+            .map(|x| LyricsLine { timestamp: x.timestamp, line: x.line })
+            .collect::<Vec<_>>()
+    });
+    let text = match resp.text {
+        Some(text) if !text.is_empty() => text,
+        _ => lines
+            .as_ref()?
+            .iter()
+            .map(|l| l.line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    Some(Lyrics {
+        source: resp.provider.unwrap_or(resp.source_name),
+        text,
+        lines,
+    })
+}
+
+async fn fetch_provider(url: &str, track: &TrackData) -> Option<Lyrics> {
+    let mut query = vec![
+        ("track_name", track.info.title.clone()),
+        ("artist_name", track.info.author.clone()),
+    ];
+    if !track.info.is_stream {
+        query.push(("duration", (track.info.length / 1000).to_string()));
+    }
+
+    let resp = match reqwest::Client::new().get(url).query(&query).send().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            debug!("Lyrics provider returned {}", resp.status());
+            return None;
+        }
+        Err(e) => {
+            warn!("Lyrics provider request failed: {}", e);
+            return None;
+        }
+    };
+    let resp: LrclibResponse = resp.json().await.ok()?;
+
+    let lines = resp.synced_lyrics.as_deref().map(parse_lrc).filter(|l| !l.is_empty());
+    let text = match resp.plain_lyrics {
+        Some(text) if !text.is_empty() => text,
+        _ => lines
+            .as_ref()?
+            .iter()
+            .map(|l| l.line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    let source = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_else(|| "lyrics provider".into());
+    Some(Lyrics { source, text, lines })
+}
+
+/// Parse `[mm:ss.xx] line` LRC text into timed lines.
+fn parse_lrc(lrc: &str) -> Vec<LyricsLine> {
+    let mut out = Vec::new();
+    for raw in lrc.lines() {
+        let Some(rest) = raw.strip_prefix('[') else { continue };
+        let Some((stamp, line)) = rest.split_once(']') else { continue };
+        let Some((min, sec)) = stamp.split_once(':') else { continue };
+        let (Ok(min), Ok(sec)) = (min.parse::<u64>(), sec.parse::<f64>()) else { continue };
+        out.push(LyricsLine {
+            timestamp: min * 60_000 + (sec * 1000.0) as u64,
+            line: line.trim().to_string(),
+        });
+    }
+    out
+}
+
+/// Split lyrics into pages that fit in an embed, breaking on line boundaries.
+pub fn paginate(text: &str, max_len: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in text.lines() {
+        if !page.is_empty() && page.len() + line.len() + 1 > max_len {
+            pages.push(std::mem::take(&mut page));
+        }
+        page.push_str(line);
+        page.push('\n');
+    }
+    if !page.trim().is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// The index of the line being sung at `position` milliseconds.
+pub fn current_line(lines: &[LyricsLine], position: u64) -> Option<usize> {
+    lines.iter().rposition(|l| l.timestamp <= position)
+}
@@ -11,6 +11,17 @@ mod commands;
 mod handlers;
 mod utils;
 mod context;
+mod lyrics;
+mod queue_policy;
+mod music_backend;
+mod soundboard;
+mod infractions;
+mod custom_commands;
+mod personas;
+mod prompts;
+mod prompt_history;
+mod eval;
+mod llm_backend;
 
 pub mod built_info {
     // The file has been placed there by the build script.
@@ -19,8 +30,10 @@ pub mod built_info {
 
 pub struct Data {
     config: &'static SunbotConfig,
-    openai_client: Option<async_openai::Client<async_openai::config::OpenAIConfig>>,
-    lavalink: Option<LavalinkClient>,
+    // OpenAI or the offline mock, see [openai] backend
+    llm: Option<std::sync::Arc<dyn llm_backend::LlmBackend>>,
+    // Lavalink when configured, otherwise songbird if built with it
+    music: Option<std::sync::Arc<dyn music_backend::MusicBackend>>,
     db: &'static DatabaseConnection,
 }
 
@@ -39,50 +52,58 @@ async fn on_ready(
     init_db(&config.database.url).await;
 
     // Configure OpenAI
-    let openai_client = if !config.openai.api_key.is_empty() || !config.openai.api_base.is_empty() {
-        let mut cfg = async_openai::config::OpenAIConfig::new();
-        if !config.openai.api_key.is_empty() {
-            cfg = cfg.with_api_key(config.openai.api_key.as_str());
-        }
-        if !config.openai.api_base.is_empty() {
-            cfg = cfg.with_api_base(config.openai.api_base.as_str());
-        }
-        Some(async_openai::Client::with_config(cfg))
-    } else {
-        None
-    };
+    let llm = llm_backend::from_config(config);
 
     // Setup Lavalink
-    let lavalink_client = if !config.lavalink.host.is_empty() {
+    let lavalink_nodes = config.lavalink.all_nodes();
+    let lavalink_client = if !lavalink_nodes.is_empty() {
         let events = events::Events {
             raw: Some(handlers::lavalink::raw_event),
             ready: Some(handlers::lavalink::ready_event),
+            stats: Some(handlers::lavalink::stats_event),
             track_start: Some(handlers::lavalink::track_start),
+            track_end: Some(handlers::lavalink::track_end),
             ..Default::default()
         };
 
-        let lavalink_host = format!("{}:{}", config.lavalink.host, config.lavalink.port);
-
-        let node_local = NodeBuilder {
-            hostname: lavalink_host,
-            is_ssl: config.lavalink.use_ssl,
-            events: events::Events::default(),
-            password: config.lavalink.password.clone(),
-            user_id: ctx.cache.current_user().id.into(),
-            session_id: None,
-        };
+        let nodes = lavalink_nodes
+            .iter()
+            .map(|node| NodeBuilder {
+                hostname: format!("{}:{}", node.host, node.port),
+                is_ssl: node.use_ssl,
+                events: events::Events::default(),
+                password: node.password.clone(),
+                user_id: ctx.cache.current_user().id.into(),
+                session_id: None,
+            })
+            .collect();
+        handlers::nodes::init(lavalink_nodes, ctx.cache.clone());
 
-        Some(
-            LavalinkClient::new(
-                events,
-                vec![node_local],
-                NodeDistributionStrategy::round_robin(),
-            )
-            .await,
+        let client = LavalinkClient::new(
+            events,
+            nodes,
+            NodeDistributionStrategy::custom(handlers::nodes::select_node),
         )
+        .await;
+        handlers::nodes::spawn_health_checks(client.clone());
+        Some(client)
     } else {
         None
     };
+    let music: Option<std::sync::Arc<dyn music_backend::MusicBackend>> = match lavalink_client {
+        Some(client) => Some(std::sync::Arc::new(music_backend::LavalinkBackend::new(client))),
+        None => music_backend::fallback(ctx).await,
+    };
+    handlers::scheduler::spawn(ctx.http.clone());
+    handlers::intents::init(llm.clone());
+    handlers::responders::init();
+    handlers::moderation::init(llm.clone());
+    if let Some(music) = music.clone() {
+        handlers::autoplay::init(music, llm.clone(), ctx.cache.current_user().id);
+    }
+    if let (Some(manager), Some(music)) = (songbird::get(ctx).await, music.clone()) {
+        handlers::inactivity::init(manager, ctx.cache.clone(), music);
+    }
 
     // Ensure no global commands to avoid duplicates alongside guild-scoped commands
     // Use serenity API to set an empty global command set
@@ -100,8 +121,8 @@ async fn on_ready(
 
     Ok(Data {
         config,
-        openai_client,
-        lavalink: lavalink_client,
+        llm,
+        music,
         db: get_db().await,
     })
 }
@@ -119,6 +140,12 @@ async fn bot_entrypoint() {
     commands::openai::prompt_set(),
     commands::openai::prompt_add(),
     commands::openai::prompt_clear(),
+    commands::prompt::prompt_template(),
+    commands::prompt::prompt_preview(),
+    commands::prompt::prompt_history(),
+    commands::prompt::prompt_diff(),
+    commands::prompt::prompt_rollback(),
+    commands::prompt::prompt_eval(),
     commands::openai::status(),
     commands::openai::mood(),
     commands::openai::profile(),
@@ -126,7 +153,8 @@ async fn bot_entrypoint() {
     // Public RAG commands disabled; bot-only usage retained internally
     commands::userdir_admin::command(),
     commands::profile_admin::command(),
-    commands::roleplay::command(),
+    commands::persona::persona(),
+    commands::roleplay::roleplay(),
         commands::music::join(),
         commands::music::leave(),
         commands::music::play(),
@@ -134,6 +162,34 @@ async fn bot_entrypoint() {
         commands::music::resume(),
         commands::music::skip(),
         commands::music::queue(),
+        commands::music::music_stats(),
+        commands::music::music_history(),
+        commands::music::stay_connected(),
+        commands::music::dj(),
+        commands::music::autoplay(),
+        commands::music::lyrics(),
+        commands::music::nodes(),
+        commands::music::limits(),
+        commands::music::blocklist(),
+        commands::soundboard::sound(),
+        commands::reminders::remind(),
+        commands::reminders::reminders(),
+        commands::reminders::timezone(),
+        commands::reminders::schedule(),
+        commands::autoreply::auto_reply(),
+        commands::autoreply::auto_reply_triggers(),
+        commands::dad::dad(),
+        commands::dad::dad_optout(),
+        commands::responders::responders(),
+        commands::custom::cc(),
+        commands::moderation::moderation(),
+        commands::moderation::moderation_log(),
+        commands::infractions::warn(),
+        commands::infractions::timeout(),
+        commands::infractions::kick(),
+        commands::infractions::ban(),
+        commands::infractions::infractions(),
+        commands::infractions::pardon(),
     ];
 
     let options = poise::FrameworkOptions {
@@ -170,6 +226,27 @@ async fn bot_entrypoint() {
 fn main() {
     let config = moonbot_config::load_config();
 
+    // `moonbot eval <fixture>` replays test conversations instead of running the bot
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("eval") {
+        let stderr_log = tracing_subscriber::fmt::layer()
+            .compact()
+            .with_writer(std::io::stderr)
+            .with_filter(filter::LevelFilter::from(Level::WARN));
+        tracing_subscriber::registry().with(stderr_log).init();
+
+        let result = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(eval::cli(&args[1..]));
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if config.discord.token.is_empty() {
         panic!("Discord token is not set in the configuration file");
     }
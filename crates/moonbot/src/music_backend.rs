@@ -0,0 +1,107 @@
+use crate::Error;
+use async_trait::async_trait;
+use lavalink_rs::{model::track::TrackData, prelude::LavalinkClient};
+use poise::serenity_prelude as serenity;
+use std::path::Path;
+use std::sync::Arc;
+
+mod lavalink;
+#[cfg(feature = "songbird-backend")]
+mod songbird;
+
+pub use self::lavalink::LavalinkBackend;
+#[cfg(feature = "songbird-backend")]
+pub use self::songbird::SongbirdBackend;
+
+// Music commands talk to a backend instead of Lavalink directly, so playback can fall
+// back to songbird when no Lavalink server is configured.
+//
+// Tracks are described with Lavalink's `TrackData` everywhere since the rest of the bot
+// (queue limits, history, embeds) already speaks it; other backends fill in what they know.
+
+/// What a search term or link resolved to.
+#[derive(Debug, Clone, Default)]
+pub struct Resolved {
+    pub tracks: Vec<TrackData>,
+    /// Name of the playlist, when the link was one
+    pub playlist: Option<String>,
+}
+
+/// The track playing in a guild and how far along it is.
+#[derive(Debug, Clone)]
+pub struct NowPlaying {
+    pub track: TrackData,
+    /// Milliseconds from the start of the track
+    pub position: u64,
+    pub paused: bool,
+}
+
+#[async_trait]
+pub trait MusicBackend: Send + Sync {
+    /// Short name shown to users, e.g. in errors
+    fn name(&self) -> &'static str;
+
+    /// Whether the bot has a player in the guild.
+    fn is_connected(&self, guild_id: serenity::GuildId) -> bool;
+
+    /// Where now playing messages for the guild go, as given to `join`.
+    fn text_channel(&self, guild_id: serenity::GuildId) -> Option<(serenity::ChannelId, Arc<serenity::Http>)>;
+
+    /// Connect to a voice channel. Now playing messages go to `text_channel`.
+    async fn join(
+        &self,
+        ctx: &serenity::Context,
+        guild_id: serenity::GuildId,
+        channel_id: serenity::ChannelId,
+        text_channel: serenity::ChannelId,
+    ) -> Result<(), Error>;
+
+    /// Stop playing and disconnect.
+    async fn leave(&self, guild_id: serenity::GuildId) -> Result<(), Error>;
+
+    /// Turn a search term or link into tracks. Errors are shown to the user.
+    async fn resolve(&self, guild_id: serenity::GuildId, term: &str) -> Result<Resolved, Error>;
+
+    async fn now_playing(&self, guild_id: serenity::GuildId) -> Result<Option<NowPlaying>, Error>;
+
+    /// Tracks waiting after the current one.
+    async fn queue(&self, guild_id: serenity::GuildId) -> Result<Vec<TrackData>, Error>;
+
+    /// Add tracks to the end of the queue, starting playback if nothing is playing.
+    async fn enqueue(&self, guild_id: serenity::GuildId, tracks: Vec<TrackData>) -> Result<(), Error>;
+
+    async fn set_pause(&self, guild_id: serenity::GuildId, paused: bool) -> Result<(), Error>;
+
+    async fn skip(&self, guild_id: serenity::GuildId) -> Result<(), Error>;
+
+    /// Load a short audio file from disk, to check its length and to pass to `play_clip`.
+    async fn load_clip(&self, guild_id: serenity::GuildId, path: &Path) -> Result<TrackData, Error>;
+
+    /// Play a clip right away, ducking or interrupting the music, which carries on
+    /// where it was once the clip is over.
+    async fn play_clip(&self, guild_id: serenity::GuildId, clip: TrackData) -> Result<(), Error>;
+
+    /// The Lavalink client playing the music, for what only Lavalink has: node status
+    /// and its lyrics plugin.
+    fn lavalink(&self) -> Option<&LavalinkClient> {
+        None
+    }
+}
+
+/// The backend to use when Lavalink isn't configured, if this build has one.
+#[cfg(feature = "songbird-backend")]
+pub async fn fallback(ctx: &serenity::Context) -> Option<Arc<dyn MusicBackend>> {
+    let manager = ::songbird::get(ctx).await?;
+    tracing::info!("Lavalink is not configured, playing music through songbird");
+    Some(Arc::new(SongbirdBackend::new(
+        manager,
+        &moonbot_config::get_config().music.local_dir,
+    )))
+}
+
+/// The backend to use when Lavalink isn't configured, if this build has one.
+#[cfg(not(feature = "songbird-backend"))]
+pub async fn fallback(_ctx: &serenity::Context) -> Option<Arc<dyn MusicBackend>> {
+    tracing::info!("Lavalink is not configured and songbird playback isn't built in, music is disabled");
+    None
+}
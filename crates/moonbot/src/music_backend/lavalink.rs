@@ -0,0 +1,184 @@
+use super::{MusicBackend, NowPlaying, Resolved};
+use crate::Error;
+use async_trait::async_trait;
+use lavalink_rs::{model::track::TrackData, prelude::*};
+use poise::serenity_prelude as serenity;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Plays through a Lavalink server, with songbird only handling the voice gateway.
+pub struct LavalinkBackend {
+    client: LavalinkClient,
+}
+
+impl LavalinkBackend {
+    pub fn new(client: LavalinkClient) -> Self {
+        LavalinkBackend { client }
+    }
+
+    fn player(&self, guild_id: serenity::GuildId) -> Result<PlayerContext, Error> {
+        self.client
+            .get_player_context(guild_id)
+            .ok_or_else(|| "Join the bot to a voice channel first.".into())
+    }
+}
+
+#[async_trait]
+impl MusicBackend for LavalinkBackend {
+    fn name(&self) -> &'static str {
+        "Lavalink"
+    }
+
+    fn is_connected(&self, guild_id: serenity::GuildId) -> bool {
+        self.client.get_player_context(guild_id).is_some()
+    }
+
+    fn lavalink(&self) -> Option<&LavalinkClient> {
+        Some(&self.client)
+    }
+
+    fn text_channel(&self, guild_id: serenity::GuildId) -> Option<(serenity::ChannelId, Arc<serenity::Http>)> {
+        let data = self
+            .client
+            .get_player_context(guild_id)?
+            .data::<(serenity::ChannelId, Arc<serenity::Http>)>()
+            .ok()?;
+        Some((data.0, data.1.clone()))
+    }
+
+    async fn join(
+        &self,
+        ctx: &serenity::Context,
+        guild_id: serenity::GuildId,
+        channel_id: serenity::ChannelId,
+        text_channel: serenity::ChannelId,
+    ) -> Result<(), Error> {
+        let manager = songbird::get(ctx).await.unwrap().clone();
+        let (connection_info, _) = manager.join_gateway(guild_id, channel_id).await?;
+
+        self.client
+            // The turbofish here is Optional, but it helps to figure out what type to
+            // provide in `PlayerContext::data()`
+            //
+            // While a tuple is used here as an example, you are free to use a custom
+            // public structure with whatever data you wish.
+            // This custom data is also present in the Client if you wish to have the
+            // shared data be more global, rather than centralized to each player.
+            .create_player_context_with_data::<(serenity::ChannelId, std::sync::Arc<serenity::Http>)>(
+                guild_id,
+                connection_info,
+                std::sync::Arc::new((text_channel, ctx.http.clone())),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn leave(&self, guild_id: serenity::GuildId) -> Result<(), Error> {
+        let _ = self.client.delete_player(guild_id).await;
+        Ok(())
+    }
+
+    async fn resolve(&self, guild_id: serenity::GuildId, term: &str) -> Result<Resolved, Error> {
+        let query = if term.starts_with("http") {
+            term.to_string()
+        } else {
+            SearchEngines::YouTube.to_query(term)?
+        };
+
+        let loaded_tracks = self.client.load_tracks(guild_id, &query).await?;
+        match loaded_tracks.data {
+            Some(TrackLoadData::Track(x)) => Ok(Resolved {
+                tracks: vec![x],
+                playlist: None,
+            }),
+            Some(TrackLoadData::Search(x)) => Ok(Resolved {
+                tracks: x.into_iter().take(1).collect(),
+                playlist: None,
+            }),
+            Some(TrackLoadData::Playlist(x)) => Ok(Resolved {
+                tracks: x.tracks,
+                playlist: Some(x.info.name),
+            }),
+            Some(TrackLoadData::Error(x)) => Err(x.message.into()),
+            _ => Err(format!("{:?}", loaded_tracks).into()),
+        }
+    }
+
+    async fn now_playing(&self, guild_id: serenity::GuildId) -> Result<Option<NowPlaying>, Error> {
+        let player = self.player(guild_id)?.get_player().await?;
+        Ok(player.track.map(|track| NowPlaying {
+            track,
+            position: player.state.position,
+            paused: player.paused,
+        }))
+    }
+
+    async fn queue(&self, guild_id: serenity::GuildId) -> Result<Vec<TrackData>, Error> {
+        let queue = self.player(guild_id)?.get_queue().get_queue().await?;
+        Ok(queue.into_iter().map(|t| t.track).collect())
+    }
+
+    async fn enqueue(&self, guild_id: serenity::GuildId, mut tracks: Vec<TrackData>) -> Result<(), Error> {
+        let player = self.player(guild_id)?;
+        if tracks.is_empty() {
+            return Ok(());
+        }
+
+        // If there is no track playing, just play the first track
+        if player.get_player().await?.track.is_none() {
+            let track = tracks.remove(0);
+            player.play(&track).await?;
+        }
+
+        // Add the rest of the tracks to the queue
+        player
+            .get_queue()
+            .append(tracks.into_iter().map(TrackInQueue::from).collect())?;
+        Ok(())
+    }
+
+    async fn set_pause(&self, guild_id: serenity::GuildId, paused: bool) -> Result<(), Error> {
+        self.player(guild_id)?.set_pause(paused).await?;
+        Ok(())
+    }
+
+    async fn skip(&self, guild_id: serenity::GuildId) -> Result<(), Error> {
+        self.player(guild_id)?.skip()?;
+        Ok(())
+    }
+
+    async fn load_clip(&self, guild_id: serenity::GuildId, path: &Path) -> Result<TrackData, Error> {
+        let identifier = crate::soundboard::lavalink_path(path);
+        let loaded = self.client.load_tracks(guild_id, &identifier).await?;
+        match loaded.data {
+            Some(TrackLoadData::Track(x)) => Ok(x),
+            Some(TrackLoadData::Error(x)) => Err(x.message.into()),
+            _ => Err("Lavalink couldn't load the clip. Check that its local source is enabled and it can read the soundboard directory.".into()),
+        }
+    }
+
+    async fn play_clip(&self, guild_id: serenity::GuildId, clip: TrackData) -> Result<(), Error> {
+        let player = self.player(guild_id)?;
+        let state = player.get_player().await?;
+
+        // A Lavalink player plays one track at a time, so the music is replaced and
+        // queued again to start where it left off once the clip finishes
+        if let Some(track) = state.track.as_ref().filter(|t| !crate::soundboard::is_clip(t)) {
+            let position = crate::handlers::nodes::live_position(&state);
+            player.get_queue().push_to_front(TrackInQueue {
+                track: track.clone(),
+                start_time: Some(Duration::from_millis(position)),
+                end_time: None,
+                volume: None,
+                filters: None,
+            })?;
+            crate::soundboard::expect_resume(guild_id, track, state.paused);
+        }
+        if state.paused {
+            player.set_pause(false).await?;
+        }
+        player.play_now(&clip).await?;
+        Ok(())
+    }
+}
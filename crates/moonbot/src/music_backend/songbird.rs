@@ -0,0 +1,407 @@
+use super::{MusicBackend, NowPlaying, Resolved};
+use crate::Error;
+use async_trait::async_trait;
+use lavalink_rs::model::track::{TrackData, TrackInfo};
+use poise::serenity_prelude as serenity;
+use songbird::{
+    input::{
+        codecs::{CODEC_REGISTRY, PROBE},
+        core::meta::{MetadataRevision, StandardTagKey},
+        File, HttpRequest, Input,
+    },
+    tracks::{PlayMode, Track, TrackHandle},
+    Call, Event, EventContext, EventHandler as VoiceEventHandler, Songbird, TrackEvent,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Mutex as AsyncMutex;
+
+// Plays direct HTTP(S) audio links and files from `music.local_dir` through songbird's
+// own driver, for when there is no Lavalink server. There's no searching, so anything
+// else is refused with an explanation.
+
+// What each queued songbird track is, by track UUID, and whether it has started
+type GuildTracks = HashMap<u128, (TrackData, bool)>;
+
+#[derive(Default)]
+struct Shared {
+    tracks: Mutex<HashMap<serenity::GuildId, GuildTracks>>,
+    // Where to post now playing messages
+    channels: Mutex<HashMap<serenity::GuildId, (serenity::ChannelId, Arc<serenity::Http>)>>,
+}
+
+pub struct SongbirdBackend {
+    manager: Arc<Songbird>,
+    http_client: songbird_reqwest::Client,
+    local_dir: Option<PathBuf>,
+    shared: Arc<Shared>,
+}
+
+impl SongbirdBackend {
+    pub fn new(manager: Arc<Songbird>, local_dir: &str) -> Self {
+        SongbirdBackend {
+            manager,
+            http_client: songbird_reqwest::Client::new(),
+            local_dir: (!local_dir.is_empty()).then(|| PathBuf::from(local_dir)),
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    fn call(&self, guild_id: serenity::GuildId) -> Result<Arc<AsyncMutex<Call>>, Error> {
+        self.manager
+            .get(guild_id)
+            .ok_or_else(|| "Join the bot to a voice channel first.".into())
+    }
+
+    fn track(&self, guild_id: serenity::GuildId, uuid: u128) -> Option<TrackData> {
+        self.shared
+            .tracks
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|tracks| tracks.get(&uuid))
+            .map(|(track, _)| track.clone())
+    }
+
+    /// A file in the local directory, by exact name or by name without the extension.
+    fn local_file(&self, term: &str) -> Option<PathBuf> {
+        let dir = self.local_dir.as_ref()?.canonicalize().ok()?;
+        if let Ok(path) = dir.join(term).canonicalize() {
+            // Don't let `../` escape the directory
+            if path.starts_with(&dir) && path.is_file() {
+                return Some(path);
+            }
+        }
+        std::fs::read_dir(&dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .find(|path| {
+                path.is_file()
+                    && path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .is_some_and(|stem| stem.eq_ignore_ascii_case(term.trim()))
+            })
+    }
+
+    fn input_for(&self, track: &TrackData) -> Result<Input, Error> {
+        match track.info.source_name.as_str() {
+            "http" => Ok(HttpRequest::new(self.http_client.clone(), track.info.identifier.clone()).into()),
+            "local" => Ok(File::new(PathBuf::from(&track.info.identifier)).into()),
+            _ => Err(format!("{} tracks need Lavalink to play.", track.info.source_name).into()),
+        }
+    }
+}
+
+fn base_track(source: &str, identifier: String, uri: Option<String>, title: String, author: String) -> TrackData {
+    TrackData {
+        encoded: format!("{}:{}", source, identifier),
+        info: TrackInfo {
+            identifier,
+            author,
+            // Unknown until probed, treated like a stream
+            is_stream: true,
+            title,
+            uri,
+            source_name: source.to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Fill in title, artist and length from the file's tags and headers.
+fn describe(input: &mut Input, info: &mut TrackInfo) {
+    let mut title = None;
+    let mut artist = None;
+    let mut read_tags = |rev: &MetadataRevision| {
+        for tag in rev.tags() {
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) if title.is_none() => title = Some(tag.value.to_string()),
+                Some(StandardTagKey::Artist) if artist.is_none() => artist = Some(tag.value.to_string()),
+                _ => {}
+            }
+        }
+    };
+    if let Ok(meta) = input.metadata() {
+        if let Some(probed) = meta.probe.get() {
+            if let Some(rev) = probed.current() {
+                read_tags(rev);
+            }
+        }
+        if let Some(rev) = meta.format.current() {
+            read_tags(rev);
+        }
+    }
+    info.title = title.unwrap_or(std::mem::take(&mut info.title));
+    info.author = artist.unwrap_or(std::mem::take(&mut info.author));
+
+    if let Some(parsed) = input.parsed() {
+        let length = parsed
+            .format
+            .tracks()
+            .iter()
+            .find(|t| t.id == parsed.track_id)
+            .and_then(|t| Some(t.codec_params.time_base?.calc_time(t.codec_params.n_frames?)));
+        if let Some(time) = length {
+            info.length = time.seconds * 1000 + (time.frac * 1000.0) as u64;
+            info.is_stream = false;
+        }
+        info.is_seekable = parsed.supports_backseek;
+    }
+}
+
+// Posts now playing messages and logs plays, like the Lavalink track_start hook
+struct TrackEvents {
+    guild_id: serenity::GuildId,
+    kind: TrackEvent,
+    shared: Arc<Shared>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackEvents {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+        for (_, handle) in tracks.iter() {
+            let uuid = handle.uuid().as_u128();
+            if self.kind == TrackEvent::End {
+                // The queue ran out when the last known track ends
+                let last = match self.shared.tracks.lock().unwrap().get_mut(&self.guild_id) {
+                    Some(tracks) => tracks.remove(&uuid).filter(|_| tracks.is_empty()),
+                    None => None,
+                };
+                if let Some((track, _)) = last {
+                    crate::handlers::autoplay::handle_track_end(self.guild_id, &track).await;
+                    crate::handlers::inactivity::handle_track_end(self.guild_id).await;
+                }
+                continue;
+            }
+
+            // Play also fires when resuming, only announce the first start
+            let track = {
+                let mut all = self.shared.tracks.lock().unwrap();
+                match all.get_mut(&self.guild_id).and_then(|tracks| tracks.get_mut(&uuid)) {
+                    Some((track, started)) if !*started => {
+                        *started = true;
+                        track.clone()
+                    }
+                    _ => continue,
+                }
+            };
+            crate::handlers::inactivity::handle_track_start(self.guild_id);
+            crate::handlers::lavalink::record_play(self.guild_id, &track).await;
+
+            let channel = self.shared.channels.lock().unwrap().get(&self.guild_id).cloned();
+            if let Some((channel_id, http)) = channel {
+                let embed = crate::handlers::lavalink::now_playing_embed(&track);
+                let _ = channel_id
+                    .send_message(&http, serenity::CreateMessage::new().embed(embed))
+                    .await;
+            }
+        }
+        None
+    }
+}
+
+// Brings the music back to full volume when a clip over it ends
+struct Unduck {
+    music: TrackHandle,
+}
+
+#[async_trait]
+impl VoiceEventHandler for Unduck {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let _ = self.music.set_volume(1.0);
+        None
+    }
+}
+
+#[async_trait]
+impl MusicBackend for SongbirdBackend {
+    fn name(&self) -> &'static str {
+        "Songbird"
+    }
+
+    fn is_connected(&self, guild_id: serenity::GuildId) -> bool {
+        self.manager.get(guild_id).is_some()
+    }
+
+    fn text_channel(&self, guild_id: serenity::GuildId) -> Option<(serenity::ChannelId, Arc<serenity::Http>)> {
+        self.shared.channels.lock().unwrap().get(&guild_id).cloned()
+    }
+
+    async fn join(
+        &self,
+        ctx: &serenity::Context,
+        guild_id: serenity::GuildId,
+        channel_id: serenity::ChannelId,
+        text_channel: serenity::ChannelId,
+    ) -> Result<(), Error> {
+        let call = self.manager.join(guild_id, channel_id).await?;
+        {
+            let mut call = call.lock().await;
+            call.remove_all_global_events();
+            for kind in [TrackEvent::Play, TrackEvent::End] {
+                call.add_global_event(
+                    Event::Track(kind),
+                    TrackEvents {
+                        guild_id,
+                        kind,
+                        shared: self.shared.clone(),
+                    },
+                );
+            }
+        }
+        self.shared
+            .channels
+            .lock()
+            .unwrap()
+            .insert(guild_id, (text_channel, ctx.http.clone()));
+        Ok(())
+    }
+
+    async fn leave(&self, guild_id: serenity::GuildId) -> Result<(), Error> {
+        if let Some(call) = self.manager.get(guild_id) {
+            call.lock().await.queue().stop();
+            self.manager.remove(guild_id).await?;
+        }
+        self.shared.tracks.lock().unwrap().remove(&guild_id);
+        self.shared.channels.lock().unwrap().remove(&guild_id);
+        Ok(())
+    }
+
+    async fn resolve(&self, _guild_id: serenity::GuildId, term: &str) -> Result<Resolved, Error> {
+        let term = term.trim();
+        let (input, mut track): (Input, _) = if term.starts_with("http://") || term.starts_with("https://") {
+            let name = term
+                .split(['?', '#'])
+                .next()
+                .and_then(|t| t.rsplit('/').next())
+                .filter(|n| !n.is_empty())
+                .unwrap_or(term)
+                .to_string();
+            let host = songbird_reqwest::Url::parse(term)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| h.to_string()))
+                .unwrap_or_default();
+            (
+                HttpRequest::new(self.http_client.clone(), term.to_string()).into(),
+                base_track("http", term.to_string(), Some(term.to_string()), name, host),
+            )
+        } else if let Some(path) = self.local_file(term) {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (
+                File::new(path.clone()).into(),
+                base_track("local", path.to_string_lossy().to_string(), None, name, "Local file".into()),
+            )
+        } else {
+            return Err("Without Lavalink only direct audio links and files from the local library can be played.".into());
+        };
+
+        // Probing checks the link actually is audio, and gives us tags and length
+        let mut input = input
+            .make_playable_async(&CODEC_REGISTRY, &PROBE)
+            .await
+            .map_err(|e| format!("Couldn't play that: {}", e))?;
+        describe(&mut input, &mut track.info);
+
+        Ok(Resolved {
+            tracks: vec![track],
+            playlist: None,
+        })
+    }
+
+    async fn now_playing(&self, guild_id: serenity::GuildId) -> Result<Option<NowPlaying>, Error> {
+        let Some(handle) = self.call(guild_id)?.lock().await.queue().current() else {
+            return Ok(None);
+        };
+        let Some(track) = self.track(guild_id, handle.uuid().as_u128()) else {
+            return Ok(None);
+        };
+        let state = handle.get_info().await?;
+        Ok(Some(NowPlaying {
+            track,
+            position: state.position.as_millis() as u64,
+            paused: matches!(state.playing, PlayMode::Pause),
+        }))
+    }
+
+    async fn queue(&self, guild_id: serenity::GuildId) -> Result<Vec<TrackData>, Error> {
+        let handles = self.call(guild_id)?.lock().await.queue().current_queue();
+        Ok(handles
+            .iter()
+            .skip(1)
+            .filter_map(|h| self.track(guild_id, h.uuid().as_u128()))
+            .collect())
+    }
+
+    async fn enqueue(&self, guild_id: serenity::GuildId, tracks: Vec<TrackData>) -> Result<(), Error> {
+        let call = self.call(guild_id)?;
+        for data in tracks {
+            let track = Track::from(self.input_for(&data)?);
+            // Registered before queueing so the Play event can find it
+            self.shared
+                .tracks
+                .lock()
+                .unwrap()
+                .entry(guild_id)
+                .or_default()
+                .insert(track.uuid.as_u128(), (data, false));
+            call.lock().await.enqueue(track).await;
+        }
+        Ok(())
+    }
+
+    async fn set_pause(&self, guild_id: serenity::GuildId, paused: bool) -> Result<(), Error> {
+        let call = self.call(guild_id)?;
+        let call = call.lock().await;
+        if paused {
+            call.queue().pause()?;
+        } else {
+            call.queue().resume()?;
+        }
+        Ok(())
+    }
+
+    async fn skip(&self, guild_id: serenity::GuildId) -> Result<(), Error> {
+        self.call(guild_id)?.lock().await.queue().skip()?;
+        Ok(())
+    }
+
+    async fn load_clip(&self, _guild_id: serenity::GuildId, path: &Path) -> Result<TrackData, Error> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut track = base_track("local", path.to_string_lossy().to_string(), None, name, "Soundboard".into());
+        let mut input = Input::from(File::new(PathBuf::from(path)))
+            .make_playable_async(&CODEC_REGISTRY, &PROBE)
+            .await
+            .map_err(|e| format!("Couldn't play that: {}", e))?;
+        describe(&mut input, &mut track.info);
+        Ok(track)
+    }
+
+    async fn play_clip(&self, guild_id: serenity::GuildId, clip: TrackData) -> Result<(), Error> {
+        let input = self.input_for(&clip)?;
+        let call = self.call(guild_id)?;
+        let mut call = call.lock().await;
+
+        // Songbird mixes tracks, so the clip plays over the music turned down
+        let music = call.queue().current();
+        if let Some(music) = &music {
+            let _ = music.set_volume(moonbot_config::get_config().music.soundboard.duck_volume);
+        }
+        let handle = call.play_input(input);
+        if let Some(music) = music {
+            handle.add_event(Event::Track(TrackEvent::End), Unduck { music })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use base64::prelude::*;
+use moonbot_db::Persona;
+use poise::serenity_prelude as serenity;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+// The persona library behind /persona.
+//
+// Each guild keeps named personas in the `personas` table: a description, the system
+// text, example dialogues and optionally their own model and temperature. A persona is
+// made active with /roleplay for the whole server, a channel or a thread in
+// `persona_activations` (channel 0 is the server), the most specific scope wins. Channels
+// and threads can also have plain text from `channel_roleplay` instead. Personas travel
+// between servers as TOML or JSON files, see `PersonaFile`, and can be imported from
+// TavernAI/SillyTavern character cards (JSON, or PNG with the card in a `chara` text
+// chunk).
+
+/// Longest system text, example dialogues and other character fields.
+pub const MAX_TEXT: usize = 16000;
+
+/// Persona names are typed in commands, keep them short and simple.
+pub fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// A persona as it is imported and exported.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonaFile {
+    pub name: String,
+    pub description: String,
+    pub system: String,
+    pub examples: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub personality: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub scenario: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub first_message: String,
+}
+
+impl From<&Persona> for PersonaFile {
+    fn from(p: &Persona) -> Self {
+        PersonaFile {
+            name: p.name.clone(),
+            description: p.description.clone(),
+            system: p.system_text.clone(),
+            examples: p.example_dialogues.clone(),
+            model: p.model.clone(),
+            temperature: p.temperature,
+            display_name: p.display_name.clone(),
+            personality: p.personality.clone(),
+            scenario: p.scenario.clone(),
+            first_message: p.first_message.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    /// Character card, as JSON or embedded in a PNG
+    Card,
+}
+
+impl Format {
+    /// Guess from the file name and, for JSON, whether it looks like a character card.
+    pub fn detect(name: &str, data: &[u8]) -> Self {
+        let name = name.to_lowercase();
+        if name.ends_with(".png") || data.starts_with(PNG_SIGNATURE) {
+            return Format::Card;
+        }
+        if !name.ends_with(".json") {
+            return Format::Toml;
+        }
+        let card = serde_json::from_slice::<serde_json::Value>(data)
+            .ok()
+            .is_some_and(|v| v.get("spec").is_some() || v.get("first_mes").is_some());
+        if card { Format::Card } else { Format::Json }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json | Format::Card => "json",
+        }
+    }
+}
+
+pub fn export(persona: &Persona, format: Format) -> Result<String, String> {
+    let file = PersonaFile::from(persona);
+    match format {
+        Format::Toml => toml::to_string_pretty(&file).map_err(|e| e.to_string()),
+        Format::Json | Format::Card => serde_json::to_string_pretty(&file).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn parse(data: &[u8], format: Format) -> Result<PersonaFile, String> {
+    let text = || std::str::from_utf8(data).map_err(|_| "the file isn't text".to_string());
+    match format {
+        Format::Toml => toml::from_str(text()?).map_err(|e| e.message().to_string()),
+        Format::Json => serde_json::from_str(text()?).map_err(|e| e.to_string()),
+        Format::Card => parse_card(data),
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The text of a PNG `tEXt` chunk with the given keyword.
+fn png_text(data: &[u8], keyword: &str) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(PNG_SIGNATURE)?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[0..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let body = rest.get(8..8 + len)?;
+        if kind == b"tEXt" {
+            if let Some((key, value)) = body.iter().position(|b| *b == 0).map(|i| (&body[..i], &body[i + 1..])) {
+                if key == keyword.as_bytes() {
+                    return Some(value.to_vec());
+                }
+            }
+        }
+        if kind == b"IEND" {
+            break;
+        }
+        // Length, type, data and CRC
+        rest = rest.get(12 + len..)?;
+    }
+    None
+}
+
+/// Turn a character name into a persona name.
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-').chars().take(32).collect::<String>();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() { "character".to_string() } else { slug.to_string() }
+}
+
+/// Read a TavernAI/SillyTavern character card, V2 (fields under `data`) or V1.
+fn parse_card(data: &[u8]) -> Result<PersonaFile, String> {
+    let json = if data.starts_with(PNG_SIGNATURE) {
+        let encoded = png_text(data, "chara").ok_or("that image has no character card in it")?;
+        BASE64_STANDARD
+            .decode(encoded.trim_ascii())
+            .map_err(|_| "the character card in that image is damaged".to_string())?
+    } else {
+        data.to_vec()
+    };
+    let card = serde_json::from_slice::<serde_json::Value>(&json).map_err(|e| e.to_string())?;
+    let fields = card.get("data").filter(|d| d.is_object()).unwrap_or(&card);
+    let field = |key: &str| fields.get(key).and_then(|v| v.as_str()).unwrap_or_default().trim().to_string();
+
+    let display_name = field("name");
+    if display_name.is_empty() {
+        return Err("the character card has no name".to_string());
+    }
+    let notes = field("creator_notes");
+    let description = match notes.lines().find(|l| !l.trim().is_empty()) {
+        Some(line) if line.chars().count() <= 200 => line.trim().to_string(),
+        Some(line) => format!("{}…", line.chars().take(199).collect::<String>()),
+        None => format!("Character card for {}", display_name),
+    };
+    Ok(PersonaFile {
+        name: slug(&display_name),
+        description,
+        system: field("description"),
+        examples: field("mes_example"),
+        model: None,
+        temperature: None,
+        display_name,
+        personality: field("personality"),
+        scenario: field("scenario"),
+        first_message: field("first_mes"),
+    })
+}
+
+/// Copy a file's fields onto a persona.
+pub fn apply(persona: &mut Persona, file: PersonaFile) {
+    persona.name = file.name;
+    persona.description = file.description;
+    persona.system_text = file.system;
+    persona.example_dialogues = file.examples;
+    persona.model = file.model;
+    persona.temperature = file.temperature;
+    persona.display_name = file.display_name;
+    persona.personality = file.personality;
+    persona.scenario = file.scenario;
+    persona.first_message = file.first_message;
+}
+
+/// A persona from a file, not saved yet.
+pub fn from_file(guild_id: i64, file: PersonaFile, created_by: Option<i64>) -> Persona {
+    let mut persona = Persona {
+        id: 0,
+        guild_id,
+        name: String::new(),
+        description: String::new(),
+        system_text: String::new(),
+        example_dialogues: String::new(),
+        model: None,
+        temperature: None,
+        display_name: String::new(),
+        personality: String::new(),
+        scenario: String::new(),
+        first_message: String::new(),
+        created_by,
+    };
+    apply(&mut persona, file);
+    persona
+}
+
+/// Save a persona file, replacing the guild's persona with its name if there is one.
+pub async fn save(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    file: PersonaFile,
+    created_by: Option<i64>,
+) -> Result<Persona, sea_orm::DbErr> {
+    if let Some(mut persona) = moonbot_db::get_persona(db, guild_id, &file.name).await? {
+        apply(&mut persona, file);
+        moonbot_db::update_persona(db, &persona).await?;
+        return Ok(persona);
+    }
+    let mut persona = from_file(guild_id, file, created_by);
+    persona.id = moonbot_db::add_persona(db, &persona).await?;
+    Ok(persona)
+}
+
+/// Check a persona before saving it, or say what's wrong.
+pub fn validate(file: &PersonaFile) -> Option<String> {
+    if !valid_name(&file.name) {
+        return Some(format!(
+            "`{}` isn't a valid name, use up to 32 characters of a-z, 0-9, - and _.",
+            file.name
+        ));
+    }
+    if file.system.trim().is_empty() && file.personality.trim().is_empty() {
+        return Some("The system text can't be empty.".to_string());
+    }
+    let texts = [&file.system, &file.examples, &file.personality, &file.scenario, &file.first_message];
+    if texts.iter().any(|t| t.chars().count() > MAX_TEXT) {
+        return Some(format!("Each text can be at most {} characters.", MAX_TEXT));
+    }
+    if file.display_name.chars().count() > 100 {
+        return Some("The display name can be at most 100 characters.".to_string());
+    }
+    if file.description.chars().count() > 200 {
+        return Some("The description can be at most 200 characters.".to_string());
+    }
+    if file.temperature.is_some_and(|t| !(0.0..=2.0).contains(&t)) {
+        return Some("The temperature goes from 0 to 2.".to_string());
+    }
+    None
+}
+
+/// Where roleplay can be set, from most to least specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Scope {
+    Thread,
+    Channel,
+    Guild,
+}
+
+impl Scope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Scope::Thread => "thread",
+            Scope::Channel => "channel",
+            Scope::Guild => "server",
+        }
+    }
+}
+
+/// Where a message was sent: its channel, and the thread inside it if there is one.
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub thread_id: Option<i64>,
+}
+
+impl Location {
+    /// Look up whether a channel is a thread, and its parent if so.
+    pub async fn find(
+        cache_http: impl serenity::CacheHttp,
+        guild_id: serenity::GuildId,
+        channel_id: serenity::ChannelId,
+    ) -> Self {
+        let thread_parent = match channel_id.to_channel(cache_http).await {
+            Ok(serenity::Channel::Guild(channel)) => match channel.kind {
+                serenity::ChannelType::PublicThread
+                | serenity::ChannelType::PrivateThread
+                | serenity::ChannelType::NewsThread => channel.parent_id,
+                _ => None,
+            },
+            _ => None,
+        };
+        match thread_parent {
+            Some(parent) => Location {
+                guild_id: guild_id.get() as i64,
+                channel_id: parent.get() as i64,
+                thread_id: Some(channel_id.get() as i64),
+            },
+            None => Location { guild_id: guild_id.get() as i64, channel_id: channel_id.get() as i64, thread_id: None },
+        }
+    }
+
+    /// The key for a scope in `persona_activations` and `channel_roleplay`, None for a
+    /// thread outside of one.
+    pub fn key(&self, scope: Scope) -> Option<i64> {
+        match scope {
+            Scope::Thread => self.thread_id,
+            Scope::Channel => Some(self.channel_id),
+            Scope::Guild => Some(0),
+        }
+    }
+}
+
+/// What a scope is set to.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Persona(Box<Persona>),
+    /// Plain text set for a channel or thread
+    Text(String),
+}
+
+impl Source {
+    pub fn persona(&self) -> Option<&Persona> {
+        match self {
+            Source::Persona(persona) => Some(persona.as_ref()),
+            Source::Text(_) => None,
+        }
+    }
+}
+
+/// What is set at exactly one scope, a persona winning over text.
+pub async fn lookup(db: &DatabaseConnection, location: &Location, scope: Scope) -> Option<Source> {
+    let key = location.key(scope)?;
+    if let Some(persona) = moonbot_db::get_active_persona(db, location.guild_id, key).await {
+        return Some(Source::Persona(Box::new(persona)));
+    }
+    if scope == Scope::Guild {
+        return None;
+    }
+    moonbot_db::get_channel_roleplay(db, key)
+        .await
+        .filter(|text| !text.trim().is_empty())
+        .map(Source::Text)
+}
+
+/// The roleplay in effect: thread, then channel, then server.
+pub async fn resolve(db: &DatabaseConnection, location: &Location) -> Option<(Scope, Source)> {
+    for scope in [Scope::Thread, Scope::Channel, Scope::Guild] {
+        if let Some(source) = lookup(db, location, scope).await {
+            return Some((scope, source));
+        }
+    }
+    None
+}
+
+/// The name the persona speaks as.
+pub fn character_name(persona: &Persona) -> &str {
+    if persona.display_name.is_empty() { &persona.name } else { &persona.display_name }
+}
+
+/// A persona's fields for the persona prompt template. Card placeholders (`{{char}}`,
+/// `{{user}}` and the older `<BOT>`, `<USER>`) are filled in.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PromptFields {
+    pub name: String,
+    pub description: String,
+    pub system: String,
+    pub personality: String,
+    pub scenario: String,
+    pub examples: String,
+    pub first_message: String,
+}
+
+pub fn prompt_fields(source: &Source, user_name: &str) -> PromptFields {
+    let persona = match source {
+        Source::Persona(persona) => persona,
+        Source::Text(text) => return PromptFields { system: text.trim().to_string(), ..Default::default() },
+    };
+    let name = character_name(persona);
+    let fill = |text: &str| {
+        text.trim()
+            .replace("{{char}}", name)
+            .replace("<BOT>", name)
+            .replace("{{user}}", user_name)
+            .replace("<USER>", user_name)
+    };
+    PromptFields {
+        name: name.to_string(),
+        description: persona.description.clone(),
+        system: fill(&persona.system_text),
+        personality: fill(&persona.personality),
+        scenario: fill(&persona.scenario),
+        examples: fill(&persona.example_dialogues),
+        first_message: fill(&persona.first_message),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    context,
+    personas::{self, Format},
+    prompts::Template,
+    Data, Error,
+};
+use moonbot_db::PromptVersion;
+use tracing::warn;
+
+// Versions of the prompts that are edited from Discord.
+//
+// Every change to the global system context, a channel's roleplay text, a persona or a
+// prompt template stores the whole prompt as it is afterwards in `prompt_versions`, with who
+// changed it. The first change to a prompt also keeps what it was before, so there's always
+// something to go back to. /prompt-rollback puts an old version back, which is a change too.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Kind {
+    Global,
+    Channel,
+    Persona,
+    Template,
+}
+
+impl Kind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Kind::Global => "global",
+            Kind::Channel => "channel",
+            Kind::Persona => "persona",
+            Kind::Template => "template",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        [Kind::Global, Kind::Channel, Kind::Persona, Kind::Template]
+            .into_iter()
+            .find(|kind| kind.key() == key)
+    }
+}
+
+/// One prompt that has versions.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub kind: Kind,
+    pub guild_id: i64,
+    /// Channel id, persona or template name, empty for the global prompt
+    pub key: String,
+}
+
+impl Target {
+    pub fn global() -> Self {
+        Target { kind: Kind::Global, guild_id: 0, key: String::new() }
+    }
+
+    pub fn channel(guild_id: i64, channel_id: i64) -> Self {
+        Target { kind: Kind::Channel, guild_id, key: channel_id.to_string() }
+    }
+
+    pub fn persona(guild_id: i64, name: &str) -> Self {
+        Target { kind: Kind::Persona, guild_id, key: name.to_string() }
+    }
+
+    pub fn template(guild_id: i64, template: Template) -> Self {
+        Target { kind: Kind::Template, guild_id, key: template.key().to_string() }
+    }
+
+    pub fn of(version: &PromptVersion) -> Option<Self> {
+        Some(Target { kind: Kind::from_key(&version.kind)?, guild_id: version.guild_id, key: version.target.clone() })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.kind {
+            Kind::Global => "Global prompt".to_string(),
+            Kind::Channel => format!("Roleplay in <#{}>", self.key),
+            Kind::Persona => format!("Persona `{}`", self.key),
+            Kind::Template => format!("Template `{}`", self.key),
+        }
+    }
+}
+
+/// The prompt as it is now, empty if there's none. Personas are kept as TOML.
+pub async fn current(data: &Data, target: &Target) -> Result<String, Error> {
+    let db = data.db;
+    Ok(match target.kind {
+        Kind::Global => moonbot_db::get_global_system_context(db).await.unwrap_or_default().join("\n"),
+        Kind::Channel => moonbot_db::get_channel_roleplay(db, target.key.parse()?).await.unwrap_or_default(),
+        Kind::Persona => match moonbot_db::get_persona(db, target.guild_id, &target.key).await? {
+            Some(persona) => personas::export(&persona, Format::Toml)?,
+            None => String::new(),
+        },
+        Kind::Template => moonbot_db::get_prompt_template(db, target.guild_id, &target.key)
+            .await
+            .unwrap_or_default(),
+    })
+}
+
+async fn try_record(data: &Data, target: &Target, before: String, author: Option<i64>) -> Result<(), Error> {
+    let db = data.db;
+    let kind = target.kind.key();
+    let previous = match moonbot_db::latest_prompt_version(db, target.guild_id, kind, &target.key).await? {
+        Some(version) => version.content,
+        None => {
+            if !before.is_empty() {
+                moonbot_db::add_prompt_version(db, target.guild_id, kind, &target.key, before.clone(), None).await?;
+            }
+            before
+        }
+    };
+    let after = current(data, target).await?;
+    if after != previous {
+        moonbot_db::add_prompt_version(db, target.guild_id, kind, &target.key, after, author).await?;
+    }
+    Ok(())
+}
+
+/// Record a change to a prompt, given what it was before. Failing to record doesn't undo
+/// the change, so it's only logged.
+pub async fn record(data: &Data, target: &Target, before: String, author: Option<i64>) {
+    if let Err(e) = try_record(data, target, before, author).await {
+        warn!("Couldn't record a version of {}: {}", target.describe(), e);
+    }
+}
+
+/// Put an old version of a prompt back.
+pub async fn restore(data: &Data, version: &PromptVersion, author: Option<i64>) -> Result<(), Error> {
+    let db = data.db;
+    let target = Target::of(version).ok_or("unknown kind of prompt")?;
+    let before = current(data, &target).await?;
+    let content = version.content.clone();
+
+    match target.kind {
+        Kind::Global => {
+            let lines = content.lines().map(|l| l.to_string()).filter(|l| !l.trim().is_empty()).collect();
+            moonbot_db::set_global_system_context(db, lines).await?;
+            context::invalidate_global_context().await;
+        }
+        Kind::Channel => {
+            let channel_id = target.key.parse()?;
+            if content.is_empty() {
+                moonbot_db::clear_channel_roleplay(db, channel_id).await?;
+            } else {
+                // Like /roleplay set, the text replaces a persona active there
+                moonbot_db::set_active_persona(db, target.guild_id, channel_id, None).await?;
+                moonbot_db::set_channel_roleplay(db, channel_id, content).await?;
+            }
+        }
+        Kind::Persona => {
+            if content.is_empty() {
+                if let Some(persona) = moonbot_db::get_persona(db, target.guild_id, &target.key).await? {
+                    moonbot_db::delete_persona(db, persona.id).await?;
+                }
+            } else {
+                let mut file = personas::parse(content.as_bytes(), Format::Toml)?;
+                file.name = target.key.clone();
+                personas::save(db, target.guild_id, file, author).await?;
+            }
+        }
+        Kind::Template => {
+            let source = if content.is_empty() { None } else { Some(content) };
+            moonbot_db::set_prompt_template(db, target.guild_id, &target.key, source, author).await?;
+        }
+    }
+
+    record(data, &target, before, author).await;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::Data;
+use minijinja::{Environment, Value};
+use tracing::warn;
+
+// Prompt templates, rendered with MiniJinja.
+//
+// The system prompt and the roleplay persona part of it come from templates. Servers can
+// keep their own in the `prompt_templates` table (/prompt-template), otherwise the ones in
+// `[openai.prompts]` are used, which default to the built-in ones. A template that fails to
+// render falls back to the built-in one so replies keep working.
+
+/// Longest template source.
+pub const MAX_SOURCE: usize = 16000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Template {
+    System,
+    Persona,
+}
+
+impl Template {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Template::System => "system",
+            Template::Persona => "persona",
+        }
+    }
+
+    /// The config's template, used by servers without their own.
+    pub fn config_source<'a>(&self, data: &'a Data) -> &'a str {
+        match self {
+            Template::System => &data.config.openai.prompts.system,
+            Template::Persona => &data.config.openai.prompts.persona,
+        }
+    }
+
+    fn builtin_source(&self) -> &'static str {
+        match self {
+            Template::System => moonbot_config::config::DEFAULT_SYSTEM_TEMPLATE,
+            Template::Persona => moonbot_config::config::DEFAULT_PERSONA_TEMPLATE,
+        }
+    }
+
+    /// The variables a template can use, for /prompt-template.
+    pub fn variables(&self) -> &'static str {
+        match self {
+            Template::System => {
+                "`user` (id, name), `profile` (traits, trust_level, preferences), `summary`, `mood` (mood, level, \
+                 notes), `persona`, `persona_name`, `global_context`, `guild`, `channel`, `time`, `context`"
+            }
+            Template::Persona => {
+                "`persona` (name, description, system, personality, scenario, examples, first_message), `user` \
+                 (id, name)"
+            }
+        }
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    // Block tags on their own line don't leave blank lines behind
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env
+}
+
+/// Compile a template to catch syntax errors before it's saved.
+pub fn check(source: &str) -> Result<(), String> {
+    environment().template_from_str(source).map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub fn render(source: &str, vars: &Value) -> Result<String, String> {
+    environment()
+        .render_str(source, vars)
+        .map(|text| text.trim().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// The source of a template in a guild, or the config's.
+pub async fn source(data: &Data, guild_id: Option<i64>, template: Template) -> String {
+    if let Some(guild_id) = guild_id {
+        if let Some(source) = moonbot_db::get_prompt_template(data.db, guild_id, template.key()).await {
+            return source;
+        }
+    }
+    template.config_source(data).to_string()
+}
+
+/// Render a template, falling back to the built-in one if it's broken.
+pub fn render_or_builtin(template: Template, source: &str, vars: &Value) -> String {
+    match render(source, vars) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("{} prompt template failed, using the built-in one: {}", template.key(), e);
+            render(template.builtin_source(), vars).unwrap_or_default()
+        }
+    }
+}
+
+/// Render a guild's template, or `source` instead when given.
+pub async fn render_for(
+    data: &Data,
+    guild_id: Option<i64>,
+    template: Template,
+    source_override: Option<&str>,
+    vars: &Value,
+) -> String {
+    let source = match source_override {
+        Some(source) => source.to_string(),
+        None => source(data, guild_id, template).await,
+    };
+    render_or_builtin(template, &source, vars)
+}
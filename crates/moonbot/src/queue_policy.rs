@@ -0,0 +1,360 @@
+use lavalink_rs::model::track::TrackData;
+use moonbot_db::{GuildMusicSettings, MusicBlock};
+use poise::serenity_prelude as serenity;
+use sea_orm::DatabaseConnection;
+use std::collections::HashSet;
+use std::fmt;
+
+/// The limits and blocklist that apply to a guild's queue, with config defaults
+/// filled in. A limit of 0 means unlimited.
+#[derive(Debug, Clone)]
+pub struct QueuePolicy {
+    pub max_queue_length: u32,
+    pub max_user_tracks: u32,
+    pub max_track_seconds: u64,
+    pub max_playlist_size: u32,
+    pub prevent_duplicates: bool,
+    pub blocklist: Vec<MusicBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    Blocked(String),
+    TooLong,
+    Duplicate,
+    PlaylistLimit,
+    UserLimit,
+    QueueFull,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Blocked(entry) => write!(f, "blocked ({})", entry),
+            Rejection::TooLong => write!(f, "too long"),
+            Rejection::Duplicate => write!(f, "already queued"),
+            Rejection::PlaylistLimit => write!(f, "playlist limit reached"),
+            Rejection::UserLimit => write!(f, "you have too many tracks queued"),
+            Rejection::QueueFull => write!(f, "queue is full"),
+        }
+    }
+}
+
+fn track_key(track: &TrackData) -> String {
+    track
+        .info
+        .uri
+        .clone()
+        .unwrap_or_else(|| format!("{}:{}", track.info.source_name, track.info.identifier))
+}
+
+fn requester(track: &TrackData) -> Option<u64> {
+    track
+        .user_data
+        .as_ref()
+        .and_then(|data| data.get("requester_id"))
+        .and_then(|id| id.as_u64())
+}
+
+impl QueuePolicy {
+    pub fn new(settings: &GuildMusicSettings, blocklist: Vec<MusicBlock>) -> Self {
+        let limits = &moonbot_config::get_config().music.limits;
+        QueuePolicy {
+            max_queue_length: settings
+                .max_queue_length
+                .map(|v| v.max(0) as u32)
+                .unwrap_or(limits.max_queue_length),
+            max_user_tracks: settings
+                .max_user_tracks
+                .map(|v| v.max(0) as u32)
+                .unwrap_or(limits.max_user_tracks),
+            max_track_seconds: settings
+                .max_track_seconds
+                .map(|v| v.max(0) as u64)
+                .unwrap_or(limits.max_track_seconds),
+            max_playlist_size: settings
+                .max_playlist_size
+                .map(|v| v.max(0) as u32)
+                .unwrap_or(limits.max_playlist_size),
+            prevent_duplicates: settings.prevent_duplicates.unwrap_or(limits.prevent_duplicates),
+            blocklist,
+        }
+    }
+
+    pub async fn load(db: &DatabaseConnection, guild_id: serenity::GuildId) -> Self {
+        let settings = moonbot_db::get_music_settings(db, guild_id.get() as i64).await;
+        let blocklist = moonbot_db::music_blocklist(db, guild_id.get() as i64)
+            .await
+            .unwrap_or_default();
+        Self::new(&settings, blocklist)
+    }
+
+    /// Checks that only depend on the track itself: the blocklist and length.
+    pub fn check_content(&self, track: &TrackData) -> Option<Rejection> {
+        let uri = track.info.uri.as_deref().unwrap_or_default().to_lowercase();
+        let text = format!("{} {}", track.info.title, track.info.author).to_lowercase();
+        for entry in &self.blocklist {
+            let value = entry.value.to_lowercase();
+            let hit = match entry.kind.as_str() {
+                "uri" => (!uri.is_empty() && uri.starts_with(&value)) || track.info.identifier == entry.value,
+                _ => text.contains(&value),
+            };
+            if hit {
+                return Some(Rejection::Blocked(entry.value.clone()));
+            }
+        }
+        if self.max_track_seconds > 0
+            && !track.info.is_stream
+            && track.info.length / 1000 > self.max_track_seconds
+        {
+            return Some(Rejection::TooLong);
+        }
+        None
+    }
+
+    /// Split tracks someone wants to queue into the ones allowed and the ones
+    /// rejected, given what's already playing and queued.
+    pub fn apply(
+        &self,
+        tracks: Vec<TrackData>,
+        current: Option<&TrackData>,
+        queue: &[TrackData],
+        user_id: serenity::UserId,
+        from_playlist: bool,
+    ) -> (Vec<TrackData>, Vec<(TrackData, Rejection)>) {
+        let mut seen: HashSet<String> = if self.prevent_duplicates {
+            queue
+                .iter()
+                .map(track_key)
+                .chain(current.map(track_key))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        let mut queued = queue.len();
+        let mut user_queued = queue
+            .iter()
+            .chain(current)
+            .filter(|t| requester(t) == Some(user_id.get()))
+            .count();
+
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+        for track in tracks {
+            let rejection = self.check_content(&track).or_else(|| {
+                if self.prevent_duplicates && seen.contains(&track_key(&track)) {
+                    Some(Rejection::Duplicate)
+                } else if from_playlist
+                    && self.max_playlist_size > 0
+                    && accepted.len() >= self.max_playlist_size as usize
+                {
+                    Some(Rejection::PlaylistLimit)
+                } else if self.max_user_tracks > 0 && user_queued >= self.max_user_tracks as usize {
+                    Some(Rejection::UserLimit)
+                } else if self.max_queue_length > 0 && queued >= self.max_queue_length as usize {
+                    Some(Rejection::QueueFull)
+                } else {
+                    None
+                }
+            });
+            match rejection {
+                Some(reason) => rejected.push((track, reason)),
+                None => {
+                    if self.prevent_duplicates {
+                        seen.insert(track_key(&track));
+                    }
+                    queued += 1;
+                    user_queued += 1;
+                    accepted.push(track);
+                }
+            }
+        }
+        (accepted, rejected)
+    }
+}
+
+/// Describe rejected tracks for a reply, stopping before `max_len` characters.
+pub fn describe_rejections(rejected: &[(TrackData, Rejection)], max_len: usize) -> String {
+    let mut out = String::new();
+    for (idx, (track, reason)) in rejected.iter().enumerate() {
+        let line = format!("{} - {}: *{}*\n", track.info.author, track.info.title, reason);
+        let more = format!("...and {} more", rejected.len() - idx);
+        if out.len() + line.len() + more.len() > max_len {
+            out.push_str(&more);
+            return out;
+        }
+        out.push_str(&line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lavalink_rs::model::track::TrackInfo;
+
+    fn policy() -> QueuePolicy {
+        QueuePolicy {
+            max_queue_length: 0,
+            max_user_tracks: 0,
+            max_track_seconds: 0,
+            max_playlist_size: 0,
+            prevent_duplicates: false,
+            blocklist: vec![],
+        }
+    }
+
+    fn track(id: &str, requester: u64) -> TrackData {
+        TrackData {
+            encoded: String::new(),
+            info: TrackInfo {
+                identifier: id.to_string(),
+                author: "Artist".to_string(),
+                length: 180_000,
+                title: format!("Song {}", id),
+                uri: Some(format!("https://youtube.com/watch?v={}", id)),
+                source_name: "youtube".to_string(),
+                ..Default::default()
+            },
+            plugin_info: None,
+            user_data: Some(serde_json::json!({ "requester_id": requester })),
+        }
+    }
+
+    fn block(kind: &str, value: &str) -> MusicBlock {
+        MusicBlock {
+            kind: kind.to_string(),
+            value: value.to_string(),
+            added_by: None,
+        }
+    }
+
+    const USER: serenity::UserId = serenity::UserId::new(1);
+
+    #[test]
+    fn blocks_keywords_and_uris() {
+        let mut policy = policy();
+        policy.blocklist = vec![
+            block("keyword", "SONG A"),
+            block("uri", "https://youtube.com/watch?v=b"),
+        ];
+        assert_eq!(
+            policy.check_content(&track("a", 1)),
+            Some(Rejection::Blocked("SONG A".into()))
+        );
+        assert_eq!(
+            policy.check_content(&track("b", 1)),
+            Some(Rejection::Blocked("https://youtube.com/watch?v=b".into()))
+        );
+        assert_eq!(policy.check_content(&track("c", 1)), None);
+
+        // A bare identifier matches tracks without a URI
+        policy.blocklist = vec![block("uri", "d")];
+        let mut no_uri = track("d", 1);
+        no_uri.info.uri = None;
+        assert_eq!(policy.check_content(&no_uri), Some(Rejection::Blocked("d".into())));
+    }
+
+    #[test]
+    fn rejects_long_tracks_but_not_streams() {
+        let mut policy = policy();
+        policy.max_track_seconds = 120;
+        assert_eq!(policy.check_content(&track("a", 1)), Some(Rejection::TooLong));
+        let mut stream = track("b", 1);
+        stream.info.is_stream = true;
+        assert_eq!(policy.check_content(&stream), None);
+        policy.max_track_seconds = 180;
+        assert_eq!(policy.check_content(&track("a", 1)), None);
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let mut policy = policy();
+        policy.prevent_duplicates = true;
+        let current = track("a", 2);
+        let queue = vec![track("b", 2)];
+        let (accepted, rejected) = policy.apply(
+            vec![track("a", 1), track("b", 1), track("c", 1), track("c", 1)],
+            Some(&current),
+            &queue,
+            USER,
+            false,
+        );
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(accepted[0].info.identifier, "c");
+        let reasons: Vec<_> = rejected.into_iter().map(|(_, r)| r).collect();
+        assert_eq!(reasons, vec![Rejection::Duplicate; 3]);
+
+        policy.prevent_duplicates = false;
+        let (accepted, _) = policy.apply(vec![track("a", 1)], Some(&current), &queue, USER, false);
+        assert_eq!(accepted.len(), 1);
+    }
+
+    #[test]
+    fn caps_playlists_only() {
+        let mut policy = policy();
+        policy.max_playlist_size = 2;
+        let tracks = || vec![track("a", 1), track("b", 1), track("c", 1)];
+        let (accepted, rejected) = policy.apply(tracks(), None, &[], USER, true);
+        assert_eq!(accepted.len(), 2);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].1, Rejection::PlaylistLimit);
+
+        let (accepted, rejected) = policy.apply(tracks(), None, &[], USER, false);
+        assert_eq!(accepted.len(), 3);
+        assert!(rejected.is_empty());
+    }
+
+    #[test]
+    fn limits_tracks_per_user() {
+        let mut policy = policy();
+        policy.max_user_tracks = 2;
+        let current = track("a", 1);
+        let queue = vec![track("b", 2), track("c", 2)];
+        let (accepted, rejected) =
+            policy.apply(vec![track("d", 1), track("e", 1)], Some(&current), &queue, USER, false);
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(rejected[0].1, Rejection::UserLimit);
+    }
+
+    #[test]
+    fn rejects_when_queue_full() {
+        let mut policy = policy();
+        policy.max_queue_length = 2;
+        let queue = vec![track("a", 2)];
+        let (accepted, rejected) = policy.apply(vec![track("b", 1), track("c", 1)], None, &queue, USER, false);
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(rejected[0].1, Rejection::QueueFull);
+    }
+
+    #[test]
+    fn content_checks_come_first() {
+        let mut policy = policy();
+        policy.max_queue_length = 1;
+        policy.max_track_seconds = 60;
+        let queue = vec![track("a", 2)];
+        let (_, rejected) = policy.apply(vec![track("b", 1)], None, &queue, USER, false);
+        assert_eq!(rejected[0].1, Rejection::TooLong);
+    }
+
+    #[test]
+    fn describes_rejections_within_length() {
+        let rejected = vec![
+            (track("a", 1), Rejection::Duplicate),
+            (track("b", 1), Rejection::QueueFull),
+            (track("c", 1), Rejection::Blocked("c".into())),
+        ];
+        let all = describe_rejections(&rejected, 1000);
+        assert_eq!(
+            all,
+            "Artist - Song a: *already queued*\nArtist - Song b: *queue is full*\nArtist - Song c: *blocked (c)*\n"
+        );
+
+        let short = describe_rejections(&rejected, 60);
+        assert_eq!(short, "Artist - Song a: *already queued*\n...and 2 more");
+        assert!(short.len() <= 60);
+
+        assert_eq!(describe_rejections(&rejected, 10), "...and 3 more");
+        assert_eq!(describe_rejections(&[], 10), "");
+    }
+}
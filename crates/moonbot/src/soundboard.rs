@@ -0,0 +1,77 @@
+use lavalink_rs::model::track::TrackData;
+use moonbot_db::SoundClip;
+use once_cell::sync::OnceCell;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Storage for /sound clips and the bookkeeping that lets music carry on after one.
+//
+// Clips live on disk as `<soundboard.dir>/<guild id>/<name>.<ext>` with their details in
+// the `sound_clips` table. Backends play them from there: songbird mixes the clip over
+// the ducked music, Lavalink replaces the track and queues it again at the same position.
+
+/// Audio formats accepted for upload.
+pub const EXTENSIONS: &[&str] = &["mp3", "ogg", "opus", "wav", "flac", "m4a"];
+
+// Track that will resume after a clip on Lavalink, by guild: (encoded, was paused)
+static RESUMING: OnceCell<Mutex<HashMap<serenity::GuildId, (String, bool)>>> = OnceCell::new();
+
+fn resuming() -> &'static Mutex<HashMap<serenity::GuildId, (String, bool)>> {
+    RESUMING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Clip names are what people type, keep them short and simple.
+pub fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+pub fn guild_dir(guild_id: serenity::GuildId) -> PathBuf {
+    let dir = &moonbot_config::get_config().music.soundboard.dir;
+    let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir));
+    dir.join(guild_id.to_string())
+}
+
+pub fn clip_path(guild_id: serenity::GuildId, clip: &SoundClip) -> PathBuf {
+    guild_dir(guild_id).join(&clip.file_name)
+}
+
+/// Where the Lavalink server finds a clip, which differs when it runs elsewhere
+/// (e.g. in another container with the directory mounted).
+pub fn lavalink_path(path: &Path) -> String {
+    let config = &moonbot_config::get_config().music.soundboard;
+    if config.lavalink_dir.is_empty() {
+        return path.to_string_lossy().to_string();
+    }
+    let base = std::fs::canonicalize(&config.dir).unwrap_or_else(|_| PathBuf::from(&config.dir));
+    match path.strip_prefix(&base) {
+        Ok(relative) => Path::new(&config.lavalink_dir).join(relative).to_string_lossy().to_string(),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Whether a track is a soundboard clip rather than music.
+pub fn is_clip(track: &TrackData) -> bool {
+    track
+        .user_data
+        .as_ref()
+        .is_some_and(|data| data.get("soundboard").is_some())
+}
+
+/// Remember that `track` was interrupted by a clip and will start again afterwards.
+pub fn expect_resume(guild_id: serenity::GuildId, track: &TrackData, paused: bool) {
+    resuming()
+        .lock()
+        .unwrap()
+        .insert(guild_id, (track.encoded.clone(), paused));
+}
+
+/// If `track` starting is music coming back after a clip, whether it was paused.
+pub fn take_resume(guild_id: serenity::GuildId, track: &TrackData) -> Option<bool> {
+    let (encoded, paused) = resuming().lock().unwrap().remove(&guild_id)?;
+    (encoded == track.encoded).then_some(paused)
+}
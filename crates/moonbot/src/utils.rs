@@ -1,4 +1,4 @@
-use crate::Context;
+use crate::{Context, Error};
 use poise::serenity_prelude as serenity;
 use tracing::info;
 
@@ -31,6 +31,32 @@ pub async fn is_reply_or_mention(
     false
 }
 
+/// Non-bot users in the bot's voice channel, None if the bot isn't in one.
+pub fn voice_listeners(
+    cache: &serenity::Cache,
+    guild_id: serenity::GuildId,
+) -> Option<Vec<serenity::UserId>> {
+    let bot_id = cache.current_user().id;
+    let guild = cache.guild(guild_id)?;
+    let channel_id = guild.voice_states.get(&bot_id)?.channel_id?;
+    Some(
+        guild
+            .voice_states
+            .values()
+            .filter(|vs| vs.channel_id == Some(channel_id) && vs.user_id != bot_id)
+            .filter(|vs| {
+                let is_bot = vs
+                    .member
+                    .as_ref()
+                    .map(|m| m.user.bot)
+                    .or_else(|| guild.members.get(&vs.user_id).map(|m| m.user.bot));
+                !is_bot.unwrap_or(false)
+            })
+            .map(|vs| vs.user_id)
+            .collect(),
+    )
+}
+
 /// Reply with an error message
 pub async fn send_err_msg(ctx: Context<'_>, title: &str, description: &str) {
     let embed = serenity::CreateEmbed::default()
@@ -41,3 +67,17 @@ pub async fn send_err_msg(ctx: Context<'_>, title: &str, description: &str) {
         .send(poise::CreateReply::default().embed(embed.clone()))
         .await;
 }
+
+/// Check for admin-only subcommands. Discord applies `default_member_permissions` to
+/// top-level commands only, so a group with commands for everyone checks here as well.
+pub async fn admin_only(ctx: Context<'_>) -> Result<bool, Error> {
+    let admin = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.administrator());
+    if !admin {
+        send_err_msg(ctx, "Error", "Only administrators can do that.").await;
+    }
+    Ok(admin)
+}
@@ -5,6 +5,13 @@ use serde::Deserialize;
 pub struct SunbotConfig {
     pub discord: DiscordConfig,
     pub lavalink: LavalinkConfig,
+    pub music: MusicConfig,
+    pub scheduler: SchedulerConfig,
+    pub moderation: ModerationConfig,
+    pub infractions: InfractionsConfig,
+    pub dad: DadConfig,
+    // Passive message responders defined in config, as `[[responders]]`
+    pub responders: Vec<ResponderConfig>,
     pub database: DatabaseConfig,
     pub openai: OpenAIConfig,
     pub sentry: SentryConfig,
@@ -28,6 +35,10 @@ pub struct LavalinkConfig {
     pub port: i16,
     // Whether to use SSL to connect to Lavalink
     pub use_ssl: bool,
+    // Multiple nodes as `[[lavalink.nodes]]`, when set the single node above is ignored
+    pub nodes: Vec<LavalinkNodeConfig>,
+    // Seconds between node health checks
+    pub health_check_interval: u64,
 }
 
 impl Default for LavalinkConfig {
@@ -37,6 +48,373 @@ impl Default for LavalinkConfig {
             password: String::from(""),
             port: 2333,
             use_ssl: false,
+            nodes: vec![],
+            health_check_interval: 30,
+        }
+    }
+}
+
+impl LavalinkConfig {
+    /// The nodes to connect to, falling back to the single `host` node.
+    pub fn all_nodes(&self) -> Vec<LavalinkNodeConfig> {
+        if !self.nodes.is_empty() {
+            return self.nodes.iter().filter(|n| !n.host.is_empty()).cloned().collect();
+        }
+        if self.host.is_empty() {
+            return vec![];
+        }
+        vec![LavalinkNodeConfig {
+            name: String::from("main"),
+            host: self.host.clone(),
+            password: self.password.clone(),
+            port: self.port,
+            use_ssl: self.use_ssl,
+            ..Default::default()
+        }]
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LavalinkNodeConfig {
+    // Display name for the node, defaults to the host
+    pub name: String,
+    // Lavalink Host to connect to
+    pub host: String,
+    // Lavalink password to connect with
+    pub password: String,
+    // Lavalink port to connect to, default is 2333
+    pub port: i16,
+    // Whether to use SSL to connect to Lavalink
+    pub use_ssl: bool,
+    // Discord voice region this node is close to (e.g. "rotterdam", "us-east"),
+    // preferred for voice channels set to that region
+    pub region: String,
+    // Nodes with a higher priority are used first, equal priorities share the load
+    pub priority: i32,
+}
+
+impl Default for LavalinkNodeConfig {
+    fn default() -> Self {
+        LavalinkNodeConfig {
+            name: String::from(""),
+            host: String::from(""),
+            password: String::from(""),
+            port: 2333,
+            use_ssl: false,
+            region: String::from(""),
+            priority: 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MusicConfig {
+    // Seconds to wait before leaving when the voice channel is empty or nothing
+    // is playing, 0 disables auto-disconnect
+    pub inactivity_timeout: u64,
+    // Whether to pause playback while nobody is listening
+    pub pause_when_empty: bool,
+    // Default fraction of listeners needed to vote-skip a track
+    pub vote_skip_ratio: f64,
+    // Configuration for autoplay / radio mode
+    pub autoplay: MusicAutoplay,
+    // Configuration for /music-lyrics
+    pub lyrics: MusicLyrics,
+    // Default queue limits, per-guild overrides with /music-limits
+    pub limits: MusicLimits,
+    // Configuration for /sound
+    pub soundboard: MusicSoundboard,
+    // Directory of audio files that can be played by name without Lavalink, empty
+    // disables
+    pub local_dir: String,
+}
+
+impl Default for MusicConfig {
+    fn default() -> Self {
+        MusicConfig {
+            inactivity_timeout: 300,
+            pause_when_empty: true,
+            vote_skip_ratio: 0.5,
+            autoplay: MusicAutoplay::default(),
+            lyrics: MusicLyrics::default(),
+            limits: MusicLimits::default(),
+            soundboard: MusicSoundboard::default(),
+            local_dir: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MusicAutoplay {
+    // How many tracks autoplay may queue in a row before stopping,
+    // the count resets whenever someone queues a track
+    pub max_tracks: u32,
+    // How many tracks to queue each time the queue runs out
+    pub batch_size: u32,
+    // How many recent plays to avoid repeating
+    pub history_window: u64,
+    // The chat model for DJ mode, empty uses openai.auto.model
+    pub model: String,
+}
+
+impl Default for MusicAutoplay {
+    fn default() -> Self {
+        MusicAutoplay {
+            max_tracks: 25,
+            batch_size: 3,
+            history_window: 50,
+            model: String::from(""),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MusicLyrics {
+    // Try the Lavalink LavaLyrics plugin first
+    pub use_lavalink: bool,
+    // An LRCLIB compatible lyrics endpoint, empty disables it
+    pub provider_url: String,
+    // Seconds between message edits in live-synced mode
+    pub sync_interval: u64,
+}
+
+impl Default for MusicLyrics {
+    fn default() -> Self {
+        MusicLyrics {
+            use_lavalink: true,
+            provider_url: String::from("https://lrclib.net/api/get"),
+            sync_interval: 3,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MusicLimits {
+    // Most tracks waiting in the queue, 0 is unlimited
+    pub max_queue_length: u32,
+    // Most queued tracks one user may have, 0 is unlimited
+    pub max_user_tracks: u32,
+    // Longest track in seconds that may be queued, 0 is unlimited. Streams are exempt
+    pub max_track_seconds: u64,
+    // Most tracks taken from one playlist, 0 is unlimited
+    pub max_playlist_size: u32,
+    // Refuse tracks that are already playing or queued
+    pub prevent_duplicates: bool,
+}
+
+impl Default for MusicLimits {
+    fn default() -> Self {
+        MusicLimits {
+            max_queue_length: 500,
+            max_user_tracks: 0,
+            max_track_seconds: 3 * 60 * 60,
+            max_playlist_size: 100,
+            prevent_duplicates: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MusicSoundboard {
+    // Where uploaded clips are kept, one directory per guild
+    pub dir: String,
+    // The same directory as seen by the Lavalink server, empty if it's the same path.
+    // Lavalink plays clips from disk, so its local source must be enabled
+    pub lavalink_dir: String,
+    // Largest clip upload in bytes
+    pub max_bytes: u64,
+    // Longest clip in seconds
+    pub max_seconds: u64,
+    // Most clips per guild, 0 is unlimited
+    pub max_clips: u32,
+    // Music volume while a clip plays over it, where the backend can mix (songbird).
+    // Lavalink pauses the music instead
+    pub duck_volume: f32,
+}
+
+impl Default for MusicSoundboard {
+    fn default() -> Self {
+        MusicSoundboard {
+            dir: "sounds".to_string(),
+            lavalink_dir: String::new(),
+            max_bytes: 1024 * 1024,
+            max_seconds: 10,
+            max_clips: 50,
+            duck_volume: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    // Timezone for users who haven't set one with /timezone
+    pub default_timezone: String,
+    // Pending reminders one user may have, 0 is unlimited
+    pub max_reminders_per_user: u32,
+    // Recurring schedules per guild, 0 is unlimited
+    pub max_schedules_per_guild: u32,
+    // Furthest ahead a reminder may be set, in days
+    pub max_reminder_days: u64,
+    // Shortest gap between runs of a schedule, in minutes
+    pub min_interval_minutes: u64,
+    // Schedules missed (e.g. while offline) by more than this many hours are skipped
+    // instead of posted late. Reminders are always delivered
+    pub catch_up_hours: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            default_timezone: "UTC".to_string(),
+            max_reminders_per_user: 25,
+            max_schedules_per_guild: 25,
+            max_reminder_days: 365,
+            min_interval_minutes: 10,
+            catch_up_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ModerationConfig {
+    // Moderation model, used with the OpenAI key or the local classifier
+    pub model: String,
+    // Base URL of a local classifier that speaks the OpenAI moderation API
+    // (e.g. http://localhost:8080/v1), empty uses OpenAI
+    pub local_url: String,
+    // Score (0.0-1.0) a category needs to flag a message, servers can override it
+    pub default_threshold: f64,
+    // Messages shorter than this are not checked
+    pub min_length: u32,
+    // Length of a timeout from the mod-log, in minutes
+    pub timeout_minutes: u32,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        ModerationConfig {
+            model: "omni-moderation-latest".to_string(),
+            local_url: String::new(),
+            default_threshold: 0.8,
+            min_length: 3,
+            timeout_minutes: 10,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct InfractionsConfig {
+    // Lower the user's trust level for infractions (and restore it on pardon)
+    pub affect_trust: bool,
+    // Only warnings from the last this many days count towards escalation, 0 counts all
+    pub escalation_window_days: u64,
+    // What happens automatically when a warning brings a user to exactly a number of active warnings
+    pub escalation: Vec<EscalationRule>,
+}
+
+impl Default for InfractionsConfig {
+    fn default() -> Self {
+        InfractionsConfig {
+            affect_trust: false,
+            escalation_window_days: 30,
+            escalation: vec![
+                EscalationRule {
+                    warnings: 3,
+                    action: "timeout".to_string(),
+                    duration_minutes: 60,
+                },
+                EscalationRule {
+                    warnings: 5,
+                    action: "timeout".to_string(),
+                    duration_minutes: 24 * 60,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct EscalationRule {
+    // Number of active warnings that triggers the rule
+    pub warnings: u32,
+    // "timeout", "kick" or "ban"
+    pub action: String,
+    // Length of a timeout
+    pub duration_minutes: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DadConfig {
+    // Answer "I'm X" with "Hi X, I'm Moonbot", servers can turn it off with /dad
+    pub enabled: bool,
+    // Chance to answer a match
+    pub chance: f64,
+    // Seconds between jokes in a server
+    pub cooldown: u64,
+    // Rename the author to X: "off", "temporary" or "permanent"
+    pub nickname_mode: String,
+    // How long a temporary nickname lasts, in minutes
+    pub nickname_minutes: u64,
+}
+
+impl Default for DadConfig {
+    fn default() -> Self {
+        DadConfig {
+            enabled: true,
+            chance: 0.8,
+            cooldown: 300,
+            nickname_mode: "temporary".to_string(),
+            nickname_minutes: 10,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ResponderConfig {
+    // Name shown in /responders
+    pub name: String,
+    // What it does, shown in /responders
+    pub description: String,
+    // Words that trigger it (case-insensitive)
+    pub keywords: Vec<String>,
+    // Regular expressions that trigger it (case-insensitive)
+    pub patterns: Vec<String>,
+    // One is picked at random. `{user}` mentions the author, `$1` or `${name}` insert a capture group
+    pub replies: Vec<String>,
+    // Emoji to react with, unicode or `<:name:id>`
+    pub reactions: Vec<String>,
+    // Chance to respond to a match
+    pub chance: f64,
+    // Seconds between responses in a server
+    pub cooldown: u64,
+    // Whether it runs in servers that didn't turn it on or off with /responders
+    pub enabled: bool,
+}
+
+impl Default for ResponderConfig {
+    fn default() -> Self {
+        ResponderConfig {
+            name: String::new(),
+            description: String::new(),
+            keywords: Vec::new(),
+            patterns: Vec::new(),
+            replies: Vec::new(),
+            reactions: Vec::new(),
+            chance: 1.0,
+            cooldown: 0,
+            enabled: true,
         }
     }
 }
@@ -56,29 +434,138 @@ impl Default for DatabaseConfig {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 #[serde(default)]
 pub struct OpenAIConfig {
     // The OpenAI API Key
     pub api_key: String,
     // Optional: Override the API base URL (e.g., http://localhost:11434/v1 for Ollama)
     pub api_base: String,
+    // Which backend answers: "openai" (also any OpenAI-compatible API) or "mock", which
+    // answers offline as set up in [openai.mock]
+    pub backend: String,
+    // Replies of the mock backend
+    pub mock: OpenAIMock,
     // Configuration for the /askgpt command
     pub askgpt: OpenAIAskgpt,
     // Configuration for the /genimage command
     pub genimage: OpenAIGenImage,
     // Configuration for the automatic replies
     pub auto: OpenAIAuto,
+    // How replies to mentions are routed to intents
+    pub intents: OpenAIIntents,
+    // Default prompt templates, servers can override them with /prompt-template
+    pub prompts: OpenAIPrompts,
+    // Dry runs of prompts against test conversations (/prompt-eval and `moonbot eval`)
+    pub eval: OpenAIEval,
 }
 
-impl Default for OpenAIConfig {
+// The system prompt built for auto replies and /askgpt
+pub const DEFAULT_SYSTEM_TEMPLATE: &str = r#"{% if persona %}
+{{ persona }}
+
+{% endif %}
+{% if global_context %}
+### Global context
+{{ global_context | join("\n") }}
+
+{% endif %}
+{% if mood %}
+### Bot disposition
+mood='{{ mood.mood }}' level={{ mood.level }} notes={{ mood.notes }}
+
+{% endif %}
+{% if profile %}
+### User profile
+user_id={{ user.id }} name={{ user.name }}
+traits={{ profile.traits | join(", ") }}
+trust_level={{ profile.trust_level }}
+preferences={{ profile.preferences }}
+summary={{ summary }}
+
+{% endif %}
+### Conversation
+{% if guild %}Server: {{ guild }}, channel: #{{ channel }}. {% endif %}It is {{ time }}.
+
+### Operating rules
+- Be helpful, concise, and accurate.
+- If unsure, ask a brief clarifying question.
+- Avoid unsolicited replies and refrain from profanity or slurs.
+{% if context %}
+
+### Retrieved context
+{{ context }}
+{% endif %}
+"#;
+
+// The roleplay part of the system prompt, rendered as `persona` there
+pub const DEFAULT_PERSONA_TEMPLATE: &str = r#"### Roleplay persona{% if persona.name %}: {{ persona.name }}{% endif %}
+
+{% if persona.system %}
+{{ persona.system }}
+
+{% endif %}
+{% if persona.personality %}
+### Personality
+{{ persona.personality }}
+
+{% endif %}
+{% if persona.scenario %}
+### Scenario
+{{ persona.scenario }}
+
+{% endif %}
+{% if persona.examples %}
+### Example dialogues
+These show how the persona talks, don't repeat them.
+{{ persona.examples }}
+
+{% endif %}
+{% if persona.first_message %}
+### First message
+{{ persona.name }} opened the conversation with:
+{{ persona.first_message }}
+
+{% endif %}
+### Instruction
+Stay in the above persona for this conversation. Reflect its style and diction consistently. Avoid generic chatbot greetings.
+"#;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OpenAIPrompts {
+    // MiniJinja template for the system prompt
+    pub system: String,
+    // MiniJinja template for the roleplay persona
+    pub persona: String,
+}
+
+impl Default for OpenAIPrompts {
+    fn default() -> Self {
+        OpenAIPrompts {
+            system: DEFAULT_SYSTEM_TEMPLATE.to_string(),
+            persona: DEFAULT_PERSONA_TEMPLATE.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OpenAIEval {
+    // Directory with the YAML/JSON test conversations
+    pub fixtures: String,
+    // Model that scores replies when judging, empty uses openai.auto.model
+    pub judge_model: String,
+    // Most conversations replayed in one run
+    pub max_conversations: usize,
+}
+
+impl Default for OpenAIEval {
     fn default() -> Self {
-        OpenAIConfig {
-            api_key: String::from(""),
-            api_base: String::from(""),
-            askgpt: OpenAIAskgpt::default(),
-            genimage: OpenAIGenImage::default(),
-            auto: OpenAIAuto::default(),
+        OpenAIEval {
+            fixtures: String::from("evals"),
+            judge_model: String::from("gpt-4o-mini"),
+            max_conversations: 20,
         }
     }
 }
@@ -174,6 +661,8 @@ pub struct OpenAIAutoRandom {
     pub min_length: u32,
     pub cooldown: u64,
     pub trigger_chance: f64,
+    pub cue_words: Vec<String>,
+    pub threads: bool,
 }
 
 impl Default for OpenAIAutoRandom {
@@ -181,14 +670,108 @@ impl Default for OpenAIAutoRandom {
         OpenAIAutoRandom {
             // The minimum length of the message to trigger a random response
             min_length: 10,
-            // The cooldown in seconds between random responses
+            // The cooldown in seconds between random responses in a channel
             cooldown: 1800,
             // The chance of triggering a random response
             trigger_chance: 0.05,
+            // Only questions or messages with one of these words get random responses (empty allows any)
+            cue_words: ["how", "what", "why", "fix", "explain", "help", "debug", "solve", "idea", "show", "code"]
+                .map(String::from)
+                .to_vec(),
+            // Whether random responses may fire in threads
+            threads: true,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OpenAIMock {
+    // Repeat the last user message back when no scripted reply matches
+    pub echo: bool,
+    // Reply when no scripted reply matches and echo is off
+    pub default_reply: String,
+    // Scripted replies, the first whose `contains` is in the last user message is used
+    pub replies: Vec<MockReply>,
+    // Wait this long before every response, in milliseconds
+    pub latency_ms: u64,
+    // Fail every nth request, 0 never fails
+    pub fail_every: u32,
+    // Fail requests whose last user message contains this, empty never fails
+    pub fail_on: String,
+    // Length of mock embeddings
+    pub dimensions: u32,
+    // Moderation flags text containing this as harassment, empty flags nothing
+    pub flag_on: String,
+}
+
+impl Default for OpenAIMock {
+    fn default() -> Self {
+        OpenAIMock {
+            echo: true,
+            default_reply: String::from("This is a mock reply."),
+            replies: vec![],
+            latency_ms: 0,
+            fail_every: 0,
+            fail_on: String::new(),
+            dimensions: 256,
+            flag_on: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct MockReply {
+    // Text to look for in the last user message, empty matches anything
+    pub contains: String,
+    // What the mock answers
+    pub reply: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OpenAIIntents {
+    // Model asked to pick an intent when no rule matched, empty disables it
+    pub classifier_model: String,
+    // Intents answered with a fixed reply
+    pub simple: Vec<SimpleIntent>,
+}
+
+impl Default for OpenAIIntents {
+    fn default() -> Self {
+        OpenAIIntents {
+            classifier_model: String::new(),
+            simple: vec![SimpleIntent {
+                name: String::from("insult"),
+                priority: 100,
+                patterns: vec![String::from("stupid|idiot|dumb|moron|oaf")],
+                max_words: 3,
+                ..Default::default()
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct SimpleIntent {
+    // Name used in logs and by the classifier
+    pub name: String,
+    // Higher priorities are tried first, the built-in intents use 10 to 50
+    pub priority: i32,
+    // Words that trigger the intent (case-insensitive)
+    pub keywords: Vec<String>,
+    // Regular expressions that trigger the intent (case-insensitive)
+    pub patterns: Vec<String>,
+    // Only match messages of at most this many words (0 is any length)
+    pub max_words: usize,
+    // What the intent is for, lets the classifier pick it when no rule matched
+    pub description: String,
+    // The reply, `{user}` mentions the author. Empty stays quiet
+    pub reply: String,
+}
+
 #[derive(Debug, Deserialize, Default)]
 #[serde(default)]
 pub struct SentryConfig {
@@ -0,0 +1,24 @@
+//! `SeaORM` Entity for per-guild and per-channel auto_reply_settings
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "auto_reply_settings")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	/// 0 for the whole server
+	pub channel_id: i64,
+	/// Chance of a random reply (0.0-1.0), None inherits
+	pub chance: Option<f64>,
+	/// Seconds between random replies in a channel, None inherits
+	pub cooldown_secs: Option<i64>,
+	/// Whether random replies may fire in threads, None inherits
+	pub threads: Option<bool>,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,24 @@
+//! `SeaORM` Entity for per-guild and per-channel auto_reply_triggers
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "auto_reply_triggers")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	/// 0 for the whole server
+	pub channel_id: i64,
+	/// "force" (always reply) or "suppress" (never reply)
+	pub effect: String,
+	/// "keyword" (whole word, any case) or "regex"
+	pub kind: String,
+	pub value: String,
+	pub added_by: Option<i64>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
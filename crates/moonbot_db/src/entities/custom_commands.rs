@@ -0,0 +1,26 @@
+//! `SeaORM` Entity for per-guild custom_commands
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "custom_commands")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub name: String,
+	/// Template of the response
+	pub content: String,
+	/// Whether the response is sent as an embed
+	pub embed: bool,
+	/// JSON array of other names
+	pub aliases: String,
+	pub uses: i64,
+	pub created_by: Option<i64>,
+	pub created_at: DateTimeUtc,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
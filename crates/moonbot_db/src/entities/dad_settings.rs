@@ -0,0 +1,22 @@
+//! `SeaORM` Entity for per-guild dad_settings
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "dad_settings")]
+pub struct Model {
+	#[sea_orm(primary_key, auto_increment = false)]
+	pub guild_id: i64,
+	/// Overrides of the [dad] config, None inherits
+	pub enabled: Option<bool>,
+	pub chance: Option<f64>,
+	pub cooldown_secs: Option<i64>,
+	/// "off", "temporary" or "permanent"
+	pub nickname_mode: Option<String>,
+	pub nickname_minutes: Option<i64>,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,28 @@
+//! `SeaORM` Entity for per-guild user infractions
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "infractions")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub user_id: i64,
+	/// None for automatic escalations
+	pub moderator_id: Option<i64>,
+	/// "warn", "timeout", "kick" or "ban"
+	pub kind: String,
+	pub reason: Option<String>,
+	/// Length of a timeout
+	pub duration_secs: Option<i64>,
+	/// False once pardoned
+	pub active: bool,
+	pub pardoned_by: Option<i64>,
+	pub pardoned_at: Option<DateTimeUtc>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
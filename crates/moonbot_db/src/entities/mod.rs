@@ -10,3 +10,21 @@ pub mod user_insight;
 pub mod user_directory;
 pub mod channel_roleplay;
 pub mod guild_roleplay;
+pub mod play_history;
+pub mod music_settings;
+pub mod music_blocklist;
+pub mod sound_clips;
+pub mod scheduled_jobs;
+pub mod user_settings;
+pub mod auto_reply_settings;
+pub mod auto_reply_triggers;
+pub mod moderation_settings;
+pub mod moderation_actions;
+pub mod infractions;
+pub mod dad_settings;
+pub mod responder_settings;
+pub mod custom_commands;
+pub mod personas;
+pub mod persona_activations;
+pub mod prompt_templates;
+pub mod prompt_versions;
@@ -0,0 +1,26 @@
+//! `SeaORM` Entity for the moderation_actions audit log
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "moderation_actions")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub channel_id: Option<i64>,
+	pub message_id: Option<i64>,
+	pub target_user_id: i64,
+	/// None for the bot's own flags
+	pub moderator_id: Option<i64>,
+	/// "flag", "delete", "timeout", "warn" or "dismiss"
+	pub action: String,
+	pub reason: Option<String>,
+	/// JSON object of category -> score, for flags
+	pub scores: Option<String>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,19 @@
+//! `SeaORM` Entity for per-guild moderation_settings
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "moderation_settings")]
+pub struct Model {
+	#[sea_orm(primary_key, auto_increment = false)]
+	pub guild_id: i64,
+	pub enabled: bool,
+	pub log_channel_id: Option<i64>,
+	/// JSON object of category -> threshold (0.0-1.0)
+	pub thresholds: String,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,20 @@
+//! `SeaORM` Entity for per-guild music_blocklist
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "music_blocklist")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	/// "uri" (matches the start of a track URI) or "keyword" (matches title/author)
+	pub kind: String,
+	pub value: String,
+	pub added_by: Option<i64>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
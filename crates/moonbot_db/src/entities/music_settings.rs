@@ -0,0 +1,29 @@
+//! `SeaORM` Entity for per-guild music_settings
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "music_settings")]
+pub struct Model {
+	#[sea_orm(primary_key, auto_increment = false)]
+	pub guild_id: i64,
+	/// 24/7 mode: never auto-disconnect from voice
+	pub stay_connected: bool,
+	pub autoplay: bool,
+	/// "related" (YouTube mix) or "dj" (ask the chat model)
+	pub autoplay_mode: String,
+	pub dj_role_id: Option<i64>,
+	/// Fraction of listeners needed to vote-skip, None uses the config default
+	pub vote_skip_ratio: Option<f64>,
+	/// Queue policy overrides, None uses the config default and 0 means unlimited
+	pub max_queue_length: Option<i32>,
+	pub max_user_tracks: Option<i32>,
+	pub max_track_seconds: Option<i64>,
+	pub max_playlist_size: Option<i32>,
+	pub prevent_duplicates: Option<bool>,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,35 @@
+//! `SeaORM` Entity for the per-guild persona library
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "personas")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub name: String,
+	pub description: String,
+	/// Instructions for the model
+	pub system_text: String,
+	/// Example exchanges showing how the persona talks
+	pub example_dialogues: String,
+	/// Chat model, None uses openai.auto
+	pub model: Option<String>,
+	/// Temperature, None uses openai.auto
+	pub temperature: Option<f64>,
+	/// Character name the persona speaks as, empty uses the name
+	pub display_name: String,
+	/// Character card fields
+	pub personality: String,
+	pub scenario: String,
+	/// How the character opens a conversation
+	pub first_message: String,
+	pub created_by: Option<i64>,
+	pub created_at: DateTimeUtc,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,22 @@
+//! `SeaORM` Entity for play_history (one row per Lavalink track_start)
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "play_history")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub requester_id: Option<i64>,
+	pub title: String,
+	pub author: String,
+	pub uri: Option<String>,
+	pub length_ms: i64,
+	pub skipped: bool,
+	pub played_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -8,3 +8,21 @@ pub use super::user_insight::Entity as UserInsight;
 pub use super::user_directory::Entity as UserDirectory;
 pub use super::channel_roleplay::Entity as ChannelRoleplay;
 pub use super::guild_roleplay::Entity as GuildRoleplay;
+pub use super::play_history::Entity as PlayHistory;
+pub use super::music_settings::Entity as MusicSettings;
+pub use super::music_blocklist::Entity as MusicBlocklist;
+pub use super::sound_clips::Entity as SoundClips;
+pub use super::scheduled_jobs::Entity as ScheduledJobs;
+pub use super::user_settings::Entity as UserSettings;
+pub use super::auto_reply_settings::Entity as AutoReplySettings;
+pub use super::auto_reply_triggers::Entity as AutoReplyTriggers;
+pub use super::moderation_settings::Entity as ModerationSettings;
+pub use super::moderation_actions::Entity as ModerationActions;
+pub use super::infractions::Entity as Infractions;
+pub use super::dad_settings::Entity as DadSettings;
+pub use super::responder_settings::Entity as ResponderSettings;
+pub use super::custom_commands::Entity as CustomCommands;
+pub use super::personas::Entity as Personas;
+pub use super::persona_activations::Entity as PersonaActivations;
+pub use super::prompt_templates::Entity as PromptTemplates;
+pub use super::prompt_versions::Entity as PromptVersions;
@@ -0,0 +1,21 @@
+//! `SeaORM` Entity for per-guild prompt_templates
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "prompt_templates")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	/// Which prompt the template renders, "system" or "persona"
+	pub name: String,
+	/// MiniJinja source
+	pub source: String,
+	pub updated_by: Option<i64>,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
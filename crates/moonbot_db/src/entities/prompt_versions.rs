@@ -0,0 +1,22 @@
+//! `SeaORM` Entity for prompt_versions, the history of prompt changes
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "prompt_versions")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	/// 0 for the global prompt
+	pub guild_id: i64,
+	/// "global", "channel", "persona" or "template"
+	pub kind: String,
+	pub target: String,
+	pub content: String,
+	pub author: Option<i64>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
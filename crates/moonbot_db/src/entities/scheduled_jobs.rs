@@ -0,0 +1,28 @@
+//! `SeaORM` Entity for reminders and recurring scheduled messages
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "scheduled_jobs")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	/// "reminder" (one-off, for `user_id`) or "schedule" (recurring, posted to `channel_id`)
+	pub kind: String,
+	/// None for reminders set in DMs
+	pub guild_id: Option<i64>,
+	pub channel_id: i64,
+	pub user_id: i64,
+	pub message: String,
+	/// Cron expression, only for schedules
+	pub cron: Option<String>,
+	/// IANA timezone the job's times are in
+	pub timezone: String,
+	pub next_run: DateTimeUtc,
+	pub last_run: Option<DateTimeUtc>,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,23 @@
+//! `SeaORM` Entity for per-guild soundboard clips
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "sound_clips")]
+pub struct Model {
+	#[sea_orm(primary_key)]
+	pub id: i64,
+	pub guild_id: i64,
+	pub name: String,
+	/// File name inside the guild's directory under `soundboard.dir`
+	pub file_name: String,
+	pub size_bytes: i64,
+	pub duration_ms: i64,
+	pub added_by: Option<i64>,
+	pub plays: i64,
+	pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,19 @@
+//! `SeaORM` Entity for per-user preferences
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "user_settings")]
+pub struct Model {
+	#[sea_orm(primary_key, auto_increment = false)]
+	pub user_id: i64,
+	/// IANA timezone name, e.g. "Europe/Berlin"
+	pub timezone: Option<String>,
+	/// Never make dad jokes about this user
+	pub dad_opt_out: bool,
+	pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -6,7 +6,6 @@ use crate::entities::prelude::*;
 use sea_orm::*;
 use sea_query::OnConflict;
 use chrono::Utc;
-use serde_json;
 // use sea_orm::QueryOrder; // not used currently
 
 static DB_CLIENT: OnceCell<DatabaseConnection> = OnceCell::const_new();
@@ -543,3 +542,1492 @@ pub async fn clear_guild_roleplay(db: &DatabaseConnection, guild_id: i64) -> Res
     let res = crate::entities::guild_roleplay::Entity::delete_by_id(guild_id).exec(db).await?;
     Ok(res.rows_affected)
 }
+
+// --- Music play history ---
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct PlayHistoryEntry {
+    pub id: i64,
+    pub guild_id: i64,
+    pub requester_id: Option<i64>,
+    pub title: String,
+    pub author: String,
+    pub uri: Option<String>,
+    pub length_ms: i64,
+    pub skipped: bool,
+    pub played_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<crate::entities::play_history::Model> for PlayHistoryEntry {
+    fn from(m: crate::entities::play_history::Model) -> Self {
+        PlayHistoryEntry {
+            id: m.id,
+            guild_id: m.guild_id,
+            requester_id: m.requester_id,
+            title: m.title,
+            author: m.author,
+            uri: m.uri,
+            length_ms: m.length_ms,
+            skipped: m.skipped,
+            played_at: m.played_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TrackPlayCount {
+    pub title: String,
+    pub author: String,
+    pub uri: Option<String>,
+    pub plays: i64,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct MusicStats {
+    pub total_plays: u64,
+    pub skipped_plays: u64,
+    /// Sum of track lengths for plays that were not skipped.
+    pub listened_ms: i64,
+    pub top_tracks: Vec<TrackPlayCount>,
+    /// (requester_id, plays)
+    pub top_requesters: Vec<(i64, i64)>,
+    /// (hour of day in UTC, plays), busiest first
+    pub busiest_hours: Vec<(u32, u64)>,
+}
+
+/// Record a track starting to play. Returns the new row id.
+pub async fn record_play(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    requester_id: Option<i64>,
+    title: &str,
+    author: &str,
+    uri: Option<&str>,
+    length_ms: i64,
+) -> Result<i64, DbErr> {
+    let am = crate::entities::play_history::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        requester_id: ActiveValue::set(requester_id),
+        title: ActiveValue::set(title.to_string()),
+        author: ActiveValue::set(author.to_string()),
+        uri: ActiveValue::set(uri.map(|u| u.to_string())),
+        length_ms: ActiveValue::set(length_ms),
+        skipped: ActiveValue::set(false),
+        played_at: ActiveValue::set(chrono::Utc::now()),
+    };
+    let res = PlayHistory::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+/// Flag the most recent play in a guild as skipped.
+pub async fn mark_last_play_skipped(db: &DatabaseConnection, guild_id: i64) -> Result<(), DbErr> {
+    use crate::entities::play_history::Column;
+    let last = PlayHistory::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_desc(Column::PlayedAt)
+        .order_by_desc(Column::Id)
+        .one(db)
+        .await?;
+    if let Some(m) = last {
+        let mut am: crate::entities::play_history::ActiveModel = m.into();
+        am.skipped = ActiveValue::set(true);
+        am.update(db).await?;
+    }
+    Ok(())
+}
+
+/// Most recent plays in a guild, newest first.
+pub async fn recent_plays(db: &DatabaseConnection, guild_id: i64, limit: u64) -> Result<Vec<PlayHistoryEntry>, DbErr> {
+    use crate::entities::play_history::Column;
+    let rows = PlayHistory::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_desc(Column::PlayedAt)
+        .order_by_desc(Column::Id)
+        .limit(limit)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(PlayHistoryEntry::from).collect())
+}
+
+/// Aggregate listening statistics for a guild.
+pub async fn music_stats(db: &DatabaseConnection, guild_id: i64, limit: u64) -> Result<MusicStats, DbErr> {
+    use crate::entities::play_history::Column;
+    let total_plays = PlayHistory::find().filter(Column::GuildId.eq(guild_id)).count(db).await?;
+    let skipped_plays = PlayHistory::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Skipped.eq(true))
+        .count(db)
+        .await?;
+
+    // SUM(bigint) is NUMERIC on Postgres, cast it back so it decodes as i64 everywhere
+    let listened_ms: Option<i64> = PlayHistory::find()
+        .select_only()
+        .column_as(Expr::cust("CAST(SUM(length_ms) AS BIGINT)"), "listened")
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Skipped.eq(false))
+        .into_tuple()
+        .one(db)
+        .await?
+        .flatten();
+
+    let top_tracks: Vec<(String, String, Option<String>, i64)> = PlayHistory::find()
+        .select_only()
+        .column(Column::Title)
+        .column(Column::Author)
+        .column(Column::Uri)
+        .column_as(Column::Id.count(), "plays")
+        .filter(Column::GuildId.eq(guild_id))
+        .group_by(Column::Title)
+        .group_by(Column::Author)
+        .group_by(Column::Uri)
+        .order_by_desc(Expr::cust("plays"))
+        .limit(limit)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let top_requesters: Vec<(i64, i64)> = PlayHistory::find()
+        .select_only()
+        .column(Column::RequesterId)
+        .column_as(Column::Id.count(), "plays")
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::RequesterId.is_not_null())
+        .group_by(Column::RequesterId)
+        .order_by_desc(Expr::cust("plays"))
+        .limit(limit)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    // Every backend spells "hour of a timestamp" differently. Plays are stored in UTC, and
+    // SQLite keeps them as RFC 3339 text which strftime reads.
+    let hour = match db.get_database_backend() {
+        DbBackend::Postgres => "CAST(EXTRACT(HOUR FROM played_at) AS BIGINT)",
+        DbBackend::MySql => "CAST(HOUR(played_at) AS SIGNED)",
+        DbBackend::Sqlite => "CAST(strftime('%H', played_at) AS INTEGER)",
+    };
+    let busiest_hours: Vec<(i64, i64)> = PlayHistory::find()
+        .select_only()
+        .column_as(Expr::cust(hour), "hour")
+        .column_as(Column::Id.count(), "plays")
+        .filter(Column::GuildId.eq(guild_id))
+        .group_by(Expr::cust(hour))
+        .order_by_desc(Expr::cust("plays"))
+        .order_by_asc(Expr::cust("hour"))
+        .limit(limit)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(MusicStats {
+        total_plays,
+        skipped_plays,
+        listened_ms: listened_ms.unwrap_or(0),
+        top_tracks: top_tracks
+            .into_iter()
+            .map(|(title, author, uri, plays)| TrackPlayCount { title, author, uri, plays })
+            .collect(),
+        top_requesters,
+        busiest_hours: busiest_hours.into_iter().map(|(h, n)| (h as u32, n as u64)).collect(),
+    })
+}
+
+// --- Per-guild music settings ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GuildMusicSettings {
+    pub stay_connected: bool, // 24/7 mode
+    pub autoplay: bool,
+    pub autoplay_mode: String, // "related" | "dj"
+    pub dj_role_id: Option<i64>,
+    pub vote_skip_ratio: Option<f64>,
+    // Queue policy overrides, None falls back to the config and 0 means unlimited
+    pub max_queue_length: Option<i32>,
+    pub max_user_tracks: Option<i32>,
+    pub max_track_seconds: Option<i64>,
+    pub max_playlist_size: Option<i32>,
+    pub prevent_duplicates: Option<bool>,
+}
+
+impl Default for GuildMusicSettings {
+    fn default() -> Self {
+        GuildMusicSettings {
+            stay_connected: false,
+            autoplay: false,
+            autoplay_mode: "related".into(),
+            dj_role_id: None,
+            vote_skip_ratio: None,
+            max_queue_length: None,
+            max_user_tracks: None,
+            max_track_seconds: None,
+            max_playlist_size: None,
+            prevent_duplicates: None,
+        }
+    }
+}
+
+pub async fn get_music_settings(db: &DatabaseConnection, guild_id: i64) -> GuildMusicSettings {
+    if let Ok(Some(m)) = MusicSettings::find_by_id(guild_id).one(db).await {
+        GuildMusicSettings {
+            stay_connected: m.stay_connected,
+            autoplay: m.autoplay,
+            autoplay_mode: m.autoplay_mode,
+            dj_role_id: m.dj_role_id,
+            vote_skip_ratio: m.vote_skip_ratio,
+            max_queue_length: m.max_queue_length,
+            max_user_tracks: m.max_user_tracks,
+            max_track_seconds: m.max_track_seconds,
+            max_playlist_size: m.max_playlist_size,
+            prevent_duplicates: m.prevent_duplicates,
+        }
+    } else {
+        GuildMusicSettings::default()
+    }
+}
+
+pub async fn set_music_settings(db: &DatabaseConnection, guild_id: i64, settings: GuildMusicSettings) -> Result<(), DbErr> {
+    let am = crate::entities::music_settings::ActiveModel {
+        guild_id: ActiveValue::set(guild_id),
+        stay_connected: ActiveValue::set(settings.stay_connected),
+        autoplay: ActiveValue::set(settings.autoplay),
+        autoplay_mode: ActiveValue::set(settings.autoplay_mode),
+        dj_role_id: ActiveValue::set(settings.dj_role_id),
+        vote_skip_ratio: ActiveValue::set(settings.vote_skip_ratio),
+        max_queue_length: ActiveValue::set(settings.max_queue_length),
+        max_user_tracks: ActiveValue::set(settings.max_user_tracks),
+        max_track_seconds: ActiveValue::set(settings.max_track_seconds),
+        max_playlist_size: ActiveValue::set(settings.max_playlist_size),
+        prevent_duplicates: ActiveValue::set(settings.prevent_duplicates),
+        updated_at: ActiveValue::set(chrono::Utc::now()),
+    };
+    MusicSettings::insert(am)
+        .on_conflict(
+            OnConflict::column(crate::entities::music_settings::Column::GuildId)
+                .update_columns([
+                    crate::entities::music_settings::Column::StayConnected,
+                    crate::entities::music_settings::Column::Autoplay,
+                    crate::entities::music_settings::Column::AutoplayMode,
+                    crate::entities::music_settings::Column::DjRoleId,
+                    crate::entities::music_settings::Column::VoteSkipRatio,
+                    crate::entities::music_settings::Column::MaxQueueLength,
+                    crate::entities::music_settings::Column::MaxUserTracks,
+                    crate::entities::music_settings::Column::MaxTrackSeconds,
+                    crate::entities::music_settings::Column::MaxPlaylistSize,
+                    crate::entities::music_settings::Column::PreventDuplicates,
+                    crate::entities::music_settings::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+// --- Music blocklist ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MusicBlock {
+    pub kind: String, // "uri" | "keyword"
+    pub value: String,
+    pub added_by: Option<i64>,
+}
+
+/// Block a URI prefix or keyword in a guild. Returns false if it was already blocked.
+pub async fn add_music_block(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    kind: &str,
+    value: &str,
+    added_by: Option<i64>,
+) -> Result<bool, DbErr> {
+    use crate::entities::music_blocklist::Column;
+    let exists = MusicBlocklist::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::Value.eq(value))
+        .count(db)
+        .await?
+        > 0;
+    if exists {
+        return Ok(false);
+    }
+    let am = crate::entities::music_blocklist::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        kind: ActiveValue::set(kind.to_string()),
+        value: ActiveValue::set(value.to_string()),
+        added_by: ActiveValue::set(added_by),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    MusicBlocklist::insert(am).exec(db).await?;
+    Ok(true)
+}
+
+/// Unblock a URI prefix or keyword. Returns false if it wasn't blocked.
+pub async fn remove_music_block(db: &DatabaseConnection, guild_id: i64, kind: &str, value: &str) -> Result<bool, DbErr> {
+    use crate::entities::music_blocklist::Column;
+    let res = MusicBlocklist::delete_many()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::Value.eq(value))
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn music_blocklist(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<MusicBlock>, DbErr> {
+    use crate::entities::music_blocklist::Column;
+    let rows = MusicBlocklist::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::Kind)
+        .order_by_asc(Column::Value)
+        .all(db)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|m| MusicBlock {
+            kind: m.kind,
+            value: m.value,
+            added_by: m.added_by,
+        })
+        .collect())
+}
+
+// --- Soundboard ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SoundClip {
+    pub name: String,
+    pub file_name: String,
+    pub size_bytes: i64,
+    pub duration_ms: i64,
+    pub added_by: Option<i64>,
+    pub plays: i64,
+}
+
+impl From<crate::entities::sound_clips::Model> for SoundClip {
+    fn from(m: crate::entities::sound_clips::Model) -> Self {
+        SoundClip {
+            name: m.name,
+            file_name: m.file_name,
+            size_bytes: m.size_bytes,
+            duration_ms: m.duration_ms,
+            added_by: m.added_by,
+            plays: m.plays,
+        }
+    }
+}
+
+/// Add a clip to a guild's soundboard. Returns false if the name is taken.
+pub async fn add_sound_clip(db: &DatabaseConnection, guild_id: i64, clip: &SoundClip) -> Result<bool, DbErr> {
+    if get_sound_clip(db, guild_id, &clip.name).await?.is_some() {
+        return Ok(false);
+    }
+    let am = crate::entities::sound_clips::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        name: ActiveValue::set(clip.name.clone()),
+        file_name: ActiveValue::set(clip.file_name.clone()),
+        size_bytes: ActiveValue::set(clip.size_bytes),
+        duration_ms: ActiveValue::set(clip.duration_ms),
+        added_by: ActiveValue::set(clip.added_by),
+        plays: ActiveValue::set(0),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    SoundClips::insert(am).exec(db).await?;
+    Ok(true)
+}
+
+pub async fn get_sound_clip(db: &DatabaseConnection, guild_id: i64, name: &str) -> Result<Option<SoundClip>, DbErr> {
+    use crate::entities::sound_clips::Column;
+    Ok(SoundClips::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .one(db)
+        .await?
+        .map(SoundClip::from))
+}
+
+/// Remove a clip, returning it so its file can be deleted.
+pub async fn remove_sound_clip(db: &DatabaseConnection, guild_id: i64, name: &str) -> Result<Option<SoundClip>, DbErr> {
+    use crate::entities::sound_clips::Column;
+    let Some(clip) = get_sound_clip(db, guild_id, name).await? else {
+        return Ok(None);
+    };
+    SoundClips::delete_many()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .exec(db)
+        .await?;
+    Ok(Some(clip))
+}
+
+pub async fn sound_clips(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<SoundClip>, DbErr> {
+    use crate::entities::sound_clips::Column;
+    let rows = SoundClips::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::Name)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(SoundClip::from).collect())
+}
+
+pub async fn record_sound_play(db: &DatabaseConnection, guild_id: i64, name: &str) -> Result<(), DbErr> {
+    use crate::entities::sound_clips::Column;
+    SoundClips::update_many()
+        .col_expr(Column::Plays, Expr::col(Column::Plays).add(1))
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+// --- Scheduled jobs ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledJob {
+    pub id: i64,
+    pub kind: String, // "reminder" | "schedule" | "nick_revert"
+    pub guild_id: Option<i64>,
+    pub channel_id: i64,
+    pub user_id: i64,
+    pub message: String,
+    pub cron: Option<String>,
+    pub timezone: String,
+    pub next_run: chrono::DateTime<Utc>,
+    pub last_run: Option<chrono::DateTime<Utc>>,
+}
+
+impl From<crate::entities::scheduled_jobs::Model> for ScheduledJob {
+    fn from(m: crate::entities::scheduled_jobs::Model) -> Self {
+        ScheduledJob {
+            id: m.id,
+            kind: m.kind,
+            guild_id: m.guild_id,
+            channel_id: m.channel_id,
+            user_id: m.user_id,
+            message: m.message,
+            cron: m.cron,
+            timezone: m.timezone,
+            next_run: m.next_run,
+            last_run: m.last_run,
+        }
+    }
+}
+
+/// Store a new job, ignoring `job.id`. Returns the new id.
+pub async fn add_scheduled_job(db: &DatabaseConnection, job: &ScheduledJob) -> Result<i64, DbErr> {
+    let am = crate::entities::scheduled_jobs::ActiveModel {
+        id: ActiveValue::not_set(),
+        kind: ActiveValue::set(job.kind.clone()),
+        guild_id: ActiveValue::set(job.guild_id),
+        channel_id: ActiveValue::set(job.channel_id),
+        user_id: ActiveValue::set(job.user_id),
+        message: ActiveValue::set(job.message.clone()),
+        cron: ActiveValue::set(job.cron.clone()),
+        timezone: ActiveValue::set(job.timezone.clone()),
+        next_run: ActiveValue::set(job.next_run),
+        last_run: ActiveValue::set(job.last_run),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    let res = ScheduledJobs::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+pub async fn get_scheduled_job(db: &DatabaseConnection, id: i64) -> Result<Option<ScheduledJob>, DbErr> {
+    Ok(ScheduledJobs::find_by_id(id).one(db).await?.map(ScheduledJob::from))
+}
+
+/// Jobs whose next run is at or before `now`, oldest first.
+pub async fn due_scheduled_jobs(db: &DatabaseConnection, now: chrono::DateTime<Utc>) -> Result<Vec<ScheduledJob>, DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    let rows = ScheduledJobs::find()
+        .filter(Column::NextRun.lte(now))
+        .order_by_asc(Column::NextRun)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(ScheduledJob::from).collect())
+}
+
+/// When the next job is due, if there are any.
+pub async fn next_scheduled_run(db: &DatabaseConnection) -> Result<Option<chrono::DateTime<Utc>>, DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    Ok(ScheduledJobs::find()
+        .order_by_asc(Column::NextRun)
+        .one(db)
+        .await?
+        .map(|m| m.next_run))
+}
+
+pub async fn reschedule_job(
+    db: &DatabaseConnection,
+    id: i64,
+    next_run: chrono::DateTime<Utc>,
+    last_run: chrono::DateTime<Utc>,
+) -> Result<(), DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    ScheduledJobs::update_many()
+        .col_expr(Column::NextRun, Expr::value(next_run))
+        .col_expr(Column::LastRun, Expr::value(last_run))
+        .filter(Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_scheduled_job(db: &DatabaseConnection, id: i64) -> Result<bool, DbErr> {
+    let res = ScheduledJobs::delete_by_id(id).exec(db).await?;
+    Ok(res.rows_affected > 0)
+}
+
+/// A user's pending reminders, soonest first.
+pub async fn user_reminders(db: &DatabaseConnection, user_id: i64) -> Result<Vec<ScheduledJob>, DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    let rows = ScheduledJobs::find()
+        .filter(Column::Kind.eq("reminder"))
+        .filter(Column::UserId.eq(user_id))
+        .order_by_asc(Column::NextRun)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(ScheduledJob::from).collect())
+}
+
+/// A guild's recurring schedules, soonest first.
+pub async fn guild_schedules(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<ScheduledJob>, DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    let rows = ScheduledJobs::find()
+        .filter(Column::Kind.eq("schedule"))
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::NextRun)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(ScheduledJob::from).collect())
+}
+
+/// The pending job of a kind for a user in a guild, if any.
+pub async fn find_user_job(
+    db: &DatabaseConnection,
+    kind: &str,
+    guild_id: i64,
+    user_id: i64,
+) -> Result<Option<ScheduledJob>, DbErr> {
+    use crate::entities::scheduled_jobs::Column;
+    Ok(ScheduledJobs::find()
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::UserId.eq(user_id))
+        .one(db)
+        .await?
+        .map(ScheduledJob::from))
+}
+
+// --- User settings ---
+pub async fn get_user_timezone(db: &DatabaseConnection, user_id: i64) -> Option<String> {
+    UserSettings::find_by_id(user_id)
+        .one(db)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|m| m.timezone)
+}
+
+pub async fn set_user_timezone(db: &DatabaseConnection, user_id: i64, timezone: Option<String>) -> Result<(), DbErr> {
+    let am = crate::entities::user_settings::ActiveModel {
+        user_id: ActiveValue::set(user_id),
+        timezone: ActiveValue::set(timezone),
+        dad_opt_out: ActiveValue::not_set(),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    UserSettings::insert(am)
+        .on_conflict(
+            OnConflict::column(crate::entities::user_settings::Column::UserId)
+                .update_columns([
+                    crate::entities::user_settings::Column::Timezone,
+                    crate::entities::user_settings::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_dad_opt_out(db: &DatabaseConnection, user_id: i64) -> bool {
+    UserSettings::find_by_id(user_id)
+        .one(db)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|m| m.dad_opt_out)
+}
+
+pub async fn set_dad_opt_out(db: &DatabaseConnection, user_id: i64, opt_out: bool) -> Result<(), DbErr> {
+    let am = crate::entities::user_settings::ActiveModel {
+        user_id: ActiveValue::set(user_id),
+        timezone: ActiveValue::not_set(),
+        dad_opt_out: ActiveValue::set(opt_out),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    UserSettings::insert(am)
+        .on_conflict(
+            OnConflict::column(crate::entities::user_settings::Column::UserId)
+                .update_columns([
+                    crate::entities::user_settings::Column::DadOptOut,
+                    crate::entities::user_settings::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+// --- Auto-reply rules ---
+/// Random reply overrides for a guild (channel 0) or one channel, None inherits.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct AutoReplyOverrides {
+    pub chance: Option<f64>,
+    pub cooldown_secs: Option<i64>,
+    pub threads: Option<bool>,
+}
+
+pub async fn get_auto_reply_settings(db: &DatabaseConnection, guild_id: i64, channel_id: i64) -> AutoReplyOverrides {
+    use crate::entities::auto_reply_settings::Column;
+    let row = AutoReplySettings::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::ChannelId.eq(channel_id))
+        .one(db)
+        .await;
+    match row {
+        Ok(Some(m)) => AutoReplyOverrides {
+            chance: m.chance,
+            cooldown_secs: m.cooldown_secs,
+            threads: m.threads,
+        },
+        _ => AutoReplyOverrides::default(),
+    }
+}
+
+pub async fn set_auto_reply_settings(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    channel_id: i64,
+    settings: AutoReplyOverrides,
+) -> Result<(), DbErr> {
+    use crate::entities::auto_reply_settings::Column;
+    let am = crate::entities::auto_reply_settings::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        channel_id: ActiveValue::set(channel_id),
+        chance: ActiveValue::set(settings.chance),
+        cooldown_secs: ActiveValue::set(settings.cooldown_secs),
+        threads: ActiveValue::set(settings.threads),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    AutoReplySettings::insert(am)
+        .on_conflict(
+            OnConflict::columns([Column::GuildId, Column::ChannelId])
+                .update_columns([Column::Chance, Column::CooldownSecs, Column::Threads, Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AutoReplyTrigger {
+    pub channel_id: i64, // 0 for the whole server
+    pub effect: String,  // "force" | "suppress"
+    pub kind: String,    // "keyword" | "regex"
+    pub value: String,
+    pub added_by: Option<i64>,
+}
+
+/// Add a trigger. Returns false if it already exists.
+pub async fn add_auto_reply_trigger(db: &DatabaseConnection, guild_id: i64, trigger: &AutoReplyTrigger) -> Result<bool, DbErr> {
+    use crate::entities::auto_reply_triggers::Column;
+    let exists = AutoReplyTriggers::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::ChannelId.eq(trigger.channel_id))
+        .filter(Column::Effect.eq(trigger.effect.as_str()))
+        .filter(Column::Kind.eq(trigger.kind.as_str()))
+        .filter(Column::Value.eq(trigger.value.as_str()))
+        .count(db)
+        .await?
+        > 0;
+    if exists {
+        return Ok(false);
+    }
+    let am = crate::entities::auto_reply_triggers::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        channel_id: ActiveValue::set(trigger.channel_id),
+        effect: ActiveValue::set(trigger.effect.clone()),
+        kind: ActiveValue::set(trigger.kind.clone()),
+        value: ActiveValue::set(trigger.value.clone()),
+        added_by: ActiveValue::set(trigger.added_by),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    AutoReplyTriggers::insert(am).exec(db).await?;
+    Ok(true)
+}
+
+/// Remove a trigger. Returns false if it didn't exist.
+pub async fn remove_auto_reply_trigger(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    channel_id: i64,
+    effect: &str,
+    kind: &str,
+    value: &str,
+) -> Result<bool, DbErr> {
+    use crate::entities::auto_reply_triggers::Column;
+    let res = AutoReplyTriggers::delete_many()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::ChannelId.eq(channel_id))
+        .filter(Column::Effect.eq(effect))
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::Value.eq(value))
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+/// Every trigger in a guild, server-wide ones first.
+pub async fn auto_reply_triggers(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<AutoReplyTrigger>, DbErr> {
+    use crate::entities::auto_reply_triggers::Column;
+    let rows = AutoReplyTriggers::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::ChannelId)
+        .order_by_asc(Column::Effect)
+        .order_by_asc(Column::Value)
+        .all(db)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|m| AutoReplyTrigger {
+            channel_id: m.channel_id,
+            effect: m.effect,
+            kind: m.kind,
+            value: m.value,
+            added_by: m.added_by,
+        })
+        .collect())
+}
+
+// --- Moderation ---
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GuildModerationSettings {
+    pub enabled: bool,
+    pub log_channel_id: Option<i64>,
+    /// Category -> threshold overrides, "default" applies to the rest
+    pub thresholds: std::collections::BTreeMap<String, f64>,
+}
+
+pub async fn get_moderation_settings(db: &DatabaseConnection, guild_id: i64) -> GuildModerationSettings {
+    match ModerationSettings::find_by_id(guild_id).one(db).await {
+        Ok(Some(m)) => GuildModerationSettings {
+            enabled: m.enabled,
+            log_channel_id: m.log_channel_id,
+            thresholds: serde_json::from_str(&m.thresholds).unwrap_or_default(),
+        },
+        _ => GuildModerationSettings::default(),
+    }
+}
+
+pub async fn set_moderation_settings(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    settings: &GuildModerationSettings,
+) -> Result<(), DbErr> {
+    use crate::entities::moderation_settings::Column;
+    let am = crate::entities::moderation_settings::ActiveModel {
+        guild_id: ActiveValue::set(guild_id),
+        enabled: ActiveValue::set(settings.enabled),
+        log_channel_id: ActiveValue::set(settings.log_channel_id),
+        thresholds: ActiveValue::set(serde_json::to_string(&settings.thresholds).unwrap_or("{}".to_string())),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    ModerationSettings::insert(am)
+        .on_conflict(
+            OnConflict::column(Column::GuildId)
+                .update_columns([Column::Enabled, Column::LogChannelId, Column::Thresholds, Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ModerationAction {
+    pub id: i64,
+    pub guild_id: i64,
+    pub channel_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub target_user_id: i64,
+    pub moderator_id: Option<i64>, // None for the bot's own flags
+    pub action: String,            // "flag" | "delete" | "timeout" | "warn" | "dismiss"
+    pub reason: Option<String>,
+    pub scores: Option<String>, // JSON object of category -> score
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl From<crate::entities::moderation_actions::Model> for ModerationAction {
+    fn from(m: crate::entities::moderation_actions::Model) -> Self {
+        ModerationAction {
+            id: m.id,
+            guild_id: m.guild_id,
+            channel_id: m.channel_id,
+            message_id: m.message_id,
+            target_user_id: m.target_user_id,
+            moderator_id: m.moderator_id,
+            action: m.action,
+            reason: m.reason,
+            scores: m.scores,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// Record a moderation action. Returns its id.
+pub async fn add_moderation_action(db: &DatabaseConnection, action: &ModerationAction) -> Result<i64, DbErr> {
+    let am = crate::entities::moderation_actions::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(action.guild_id),
+        channel_id: ActiveValue::set(action.channel_id),
+        message_id: ActiveValue::set(action.message_id),
+        target_user_id: ActiveValue::set(action.target_user_id),
+        moderator_id: ActiveValue::set(action.moderator_id),
+        action: ActiveValue::set(action.action.clone()),
+        reason: ActiveValue::set(action.reason.clone()),
+        scores: ActiveValue::set(action.scores.clone()),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    let res = ModerationActions::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+pub async fn get_moderation_action(db: &DatabaseConnection, id: i64) -> Result<Option<ModerationAction>, DbErr> {
+    Ok(ModerationActions::find_by_id(id).one(db).await?.map(Into::into))
+}
+
+/// The latest actions in a guild, optionally only those against one user.
+pub async fn moderation_actions(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    target_user_id: Option<i64>,
+    limit: u64,
+) -> Result<Vec<ModerationAction>, DbErr> {
+    use crate::entities::moderation_actions::Column;
+    let mut query = ModerationActions::find().filter(Column::GuildId.eq(guild_id));
+    if let Some(user) = target_user_id {
+        query = query.filter(Column::TargetUserId.eq(user));
+    }
+    let rows = query.order_by_desc(Column::Id).limit(limit).all(db).await?;
+    Ok(rows.into_iter().map(Into::into).collect())
+}
+
+// --- Infractions ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Infraction {
+    pub id: i64,
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub moderator_id: Option<i64>, // None for automatic escalations
+    pub kind: String,              // "warn" | "timeout" | "kick" | "ban"
+    pub reason: Option<String>,
+    pub duration_secs: Option<i64>,
+    pub active: bool,
+    pub pardoned_by: Option<i64>,
+    pub pardoned_at: Option<chrono::DateTime<Utc>>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl From<crate::entities::infractions::Model> for Infraction {
+    fn from(m: crate::entities::infractions::Model) -> Self {
+        Infraction {
+            id: m.id,
+            guild_id: m.guild_id,
+            user_id: m.user_id,
+            moderator_id: m.moderator_id,
+            kind: m.kind,
+            reason: m.reason,
+            duration_secs: m.duration_secs,
+            active: m.active,
+            pardoned_by: m.pardoned_by,
+            pardoned_at: m.pardoned_at,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// Record an infraction. Returns its id.
+pub async fn add_infraction(db: &DatabaseConnection, infraction: &Infraction) -> Result<i64, DbErr> {
+    let am = crate::entities::infractions::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(infraction.guild_id),
+        user_id: ActiveValue::set(infraction.user_id),
+        moderator_id: ActiveValue::set(infraction.moderator_id),
+        kind: ActiveValue::set(infraction.kind.clone()),
+        reason: ActiveValue::set(infraction.reason.clone()),
+        duration_secs: ActiveValue::set(infraction.duration_secs),
+        active: ActiveValue::set(true),
+        pardoned_by: ActiveValue::set(None),
+        pardoned_at: ActiveValue::set(None),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    let res = Infractions::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+pub async fn get_infraction(db: &DatabaseConnection, id: i64) -> Result<Option<Infraction>, DbErr> {
+    Ok(Infractions::find_by_id(id).one(db).await?.map(Into::into))
+}
+
+/// A user's infractions in a guild, newest first.
+pub async fn user_infractions(db: &DatabaseConnection, guild_id: i64, user_id: i64) -> Result<Vec<Infraction>, DbErr> {
+    use crate::entities::infractions::Column;
+    let rows = Infractions::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::UserId.eq(user_id))
+        .order_by_desc(Column::Id)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(Into::into).collect())
+}
+
+/// Active warnings of a user in a guild, optionally only those since a time.
+pub async fn count_active_warnings(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    user_id: i64,
+    since: Option<chrono::DateTime<Utc>>,
+) -> Result<u64, DbErr> {
+    use crate::entities::infractions::Column;
+    let mut query = Infractions::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::Kind.eq("warn"))
+        .filter(Column::Active.eq(true));
+    if let Some(since) = since {
+        query = query.filter(Column::CreatedAt.gte(since));
+    }
+    query.count(db).await
+}
+
+/// Mark an infraction as pardoned. Returns false if it was already.
+pub async fn pardon_infraction(db: &DatabaseConnection, id: i64, pardoned_by: i64) -> Result<bool, DbErr> {
+    use crate::entities::infractions::Column;
+    let res = Infractions::update_many()
+        .col_expr(Column::Active, Expr::value(false))
+        .col_expr(Column::PardonedBy, Expr::value(pardoned_by))
+        .col_expr(Column::PardonedAt, Expr::value(Utc::now()))
+        .filter(Column::Id.eq(id))
+        .filter(Column::Active.eq(true))
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+// --- Dad jokes ---
+/// Per-guild overrides of the [dad] config, None inherits.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GuildDadSettings {
+    pub enabled: Option<bool>,
+    pub chance: Option<f64>,
+    pub cooldown_secs: Option<i64>,
+    pub nickname_mode: Option<String>, // "off" | "temporary" | "permanent"
+    pub nickname_minutes: Option<i64>,
+}
+
+pub async fn get_dad_settings(db: &DatabaseConnection, guild_id: i64) -> GuildDadSettings {
+    match DadSettings::find_by_id(guild_id).one(db).await {
+        Ok(Some(m)) => GuildDadSettings {
+            enabled: m.enabled,
+            chance: m.chance,
+            cooldown_secs: m.cooldown_secs,
+            nickname_mode: m.nickname_mode,
+            nickname_minutes: m.nickname_minutes,
+        },
+        _ => GuildDadSettings::default(),
+    }
+}
+
+pub async fn set_dad_settings(db: &DatabaseConnection, guild_id: i64, settings: GuildDadSettings) -> Result<(), DbErr> {
+    use crate::entities::dad_settings::Column;
+    let am = crate::entities::dad_settings::ActiveModel {
+        guild_id: ActiveValue::set(guild_id),
+        enabled: ActiveValue::set(settings.enabled),
+        chance: ActiveValue::set(settings.chance),
+        cooldown_secs: ActiveValue::set(settings.cooldown_secs),
+        nickname_mode: ActiveValue::set(settings.nickname_mode),
+        nickname_minutes: ActiveValue::set(settings.nickname_minutes),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    DadSettings::insert(am)
+        .on_conflict(
+            OnConflict::column(Column::GuildId)
+                .update_columns([
+                    Column::Enabled,
+                    Column::Chance,
+                    Column::CooldownSecs,
+                    Column::NicknameMode,
+                    Column::NicknameMinutes,
+                    Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+// --- Message responders ---
+/// Whether a guild turned a responder on or off, None if it uses the default.
+pub async fn get_responder_enabled(db: &DatabaseConnection, guild_id: i64, name: &str) -> Option<bool> {
+    use crate::entities::responder_settings::Column;
+    ResponderSettings::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .one(db)
+        .await
+        .ok()
+        .flatten()
+        .map(|m| m.enabled)
+}
+
+/// Turn a responder on or off in a guild, None goes back to its default.
+pub async fn set_responder_enabled(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    name: &str,
+    enabled: Option<bool>,
+) -> Result<(), DbErr> {
+    use crate::entities::responder_settings::Column;
+    let Some(enabled) = enabled else {
+        ResponderSettings::delete_many()
+            .filter(Column::GuildId.eq(guild_id))
+            .filter(Column::Name.eq(name))
+            .exec(db)
+            .await?;
+        return Ok(());
+    };
+    let am = crate::entities::responder_settings::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        name: ActiveValue::set(name.to_string()),
+        enabled: ActiveValue::set(enabled),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    ResponderSettings::insert(am)
+        .on_conflict(
+            OnConflict::columns([Column::GuildId, Column::Name])
+                .update_columns([Column::Enabled, Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+// --- Custom commands ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CustomCommand {
+    pub id: i64,
+    pub guild_id: i64,
+    pub name: String,
+    pub content: String,
+    pub embed: bool,
+    pub aliases: Vec<String>,
+    pub uses: i64,
+    pub created_by: Option<i64>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl From<crate::entities::custom_commands::Model> for CustomCommand {
+    fn from(m: crate::entities::custom_commands::Model) -> Self {
+        CustomCommand {
+            id: m.id,
+            guild_id: m.guild_id,
+            name: m.name,
+            content: m.content,
+            embed: m.embed,
+            aliases: serde_json::from_str(&m.aliases).unwrap_or_default(),
+            uses: m.uses,
+            created_by: m.created_by,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// Add a custom command, returning its id.
+pub async fn add_custom_command(db: &DatabaseConnection, command: &CustomCommand) -> Result<i64, DbErr> {
+    let am = crate::entities::custom_commands::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(command.guild_id),
+        name: ActiveValue::set(command.name.clone()),
+        content: ActiveValue::set(command.content.clone()),
+        embed: ActiveValue::set(command.embed),
+        aliases: ActiveValue::set(serde_json::to_string(&command.aliases).unwrap_or("[]".to_string())),
+        uses: ActiveValue::set(0),
+        created_by: ActiveValue::set(command.created_by),
+        created_at: ActiveValue::set(Utc::now()),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    let res = CustomCommands::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+/// Save the content, embed flag and aliases of a custom command.
+pub async fn update_custom_command(db: &DatabaseConnection, command: &CustomCommand) -> Result<(), DbErr> {
+    let am = crate::entities::custom_commands::ActiveModel {
+        id: ActiveValue::unchanged(command.id),
+        content: ActiveValue::set(command.content.clone()),
+        embed: ActiveValue::set(command.embed),
+        aliases: ActiveValue::set(serde_json::to_string(&command.aliases).unwrap_or("[]".to_string())),
+        updated_at: ActiveValue::set(Utc::now()),
+        ..Default::default()
+    };
+    am.update(db).await.map(|_| ())
+}
+
+pub async fn custom_commands(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<CustomCommand>, DbErr> {
+    use crate::entities::custom_commands::Column;
+    let rows = CustomCommands::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::Name)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(CustomCommand::from).collect())
+}
+
+/// Find a custom command by its name or one of its aliases.
+pub async fn find_custom_command(db: &DatabaseConnection, guild_id: i64, name: &str) -> Result<Option<CustomCommand>, DbErr> {
+    Ok(custom_commands(db, guild_id)
+        .await?
+        .into_iter()
+        .find(|c| c.name == name || c.aliases.iter().any(|a| a == name)))
+}
+
+pub async fn delete_custom_command(db: &DatabaseConnection, id: i64) -> Result<bool, DbErr> {
+    let res = CustomCommands::delete_by_id(id).exec(db).await?;
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn record_custom_command_use(db: &DatabaseConnection, id: i64) -> Result<(), DbErr> {
+    use crate::entities::custom_commands::Column;
+    CustomCommands::update_many()
+        .col_expr(Column::Uses, Expr::col(Column::Uses).add(1))
+        .filter(Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+// --- Personas ---
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Persona {
+    pub id: i64,
+    pub guild_id: i64,
+    pub name: String,
+    pub description: String,
+    pub system_text: String,
+    pub example_dialogues: String,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub display_name: String,
+    pub personality: String,
+    pub scenario: String,
+    pub first_message: String,
+    pub created_by: Option<i64>,
+}
+
+impl From<crate::entities::personas::Model> for Persona {
+    fn from(m: crate::entities::personas::Model) -> Self {
+        Persona {
+            id: m.id,
+            guild_id: m.guild_id,
+            name: m.name,
+            description: m.description,
+            system_text: m.system_text,
+            example_dialogues: m.example_dialogues,
+            model: m.model,
+            temperature: m.temperature,
+            display_name: m.display_name,
+            personality: m.personality,
+            scenario: m.scenario,
+            first_message: m.first_message,
+            created_by: m.created_by,
+        }
+    }
+}
+
+/// Add a persona to a guild's library, returning its id.
+pub async fn add_persona(db: &DatabaseConnection, persona: &Persona) -> Result<i64, DbErr> {
+    let am = crate::entities::personas::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(persona.guild_id),
+        name: ActiveValue::set(persona.name.clone()),
+        description: ActiveValue::set(persona.description.clone()),
+        system_text: ActiveValue::set(persona.system_text.clone()),
+        example_dialogues: ActiveValue::set(persona.example_dialogues.clone()),
+        model: ActiveValue::set(persona.model.clone()),
+        temperature: ActiveValue::set(persona.temperature),
+        display_name: ActiveValue::set(persona.display_name.clone()),
+        personality: ActiveValue::set(persona.personality.clone()),
+        scenario: ActiveValue::set(persona.scenario.clone()),
+        first_message: ActiveValue::set(persona.first_message.clone()),
+        created_by: ActiveValue::set(persona.created_by),
+        created_at: ActiveValue::set(Utc::now()),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    let res = Personas::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+/// Save everything but the owner and creator of a persona.
+pub async fn update_persona(db: &DatabaseConnection, persona: &Persona) -> Result<(), DbErr> {
+    let am = crate::entities::personas::ActiveModel {
+        id: ActiveValue::unchanged(persona.id),
+        name: ActiveValue::set(persona.name.clone()),
+        description: ActiveValue::set(persona.description.clone()),
+        system_text: ActiveValue::set(persona.system_text.clone()),
+        example_dialogues: ActiveValue::set(persona.example_dialogues.clone()),
+        model: ActiveValue::set(persona.model.clone()),
+        temperature: ActiveValue::set(persona.temperature),
+        display_name: ActiveValue::set(persona.display_name.clone()),
+        personality: ActiveValue::set(persona.personality.clone()),
+        scenario: ActiveValue::set(persona.scenario.clone()),
+        first_message: ActiveValue::set(persona.first_message.clone()),
+        updated_at: ActiveValue::set(Utc::now()),
+        ..Default::default()
+    };
+    am.update(db).await.map(|_| ())
+}
+
+pub async fn get_persona(db: &DatabaseConnection, guild_id: i64, name: &str) -> Result<Option<Persona>, DbErr> {
+    use crate::entities::personas::Column;
+    Ok(Personas::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .one(db)
+        .await?
+        .map(Persona::from))
+}
+
+pub async fn personas(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<Persona>, DbErr> {
+    use crate::entities::personas::Column;
+    let rows = Personas::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::Name)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(Persona::from).collect())
+}
+
+/// Delete a persona and wherever it was active.
+pub async fn delete_persona(db: &DatabaseConnection, id: i64) -> Result<bool, DbErr> {
+    use crate::entities::persona_activations::Column;
+    PersonaActivations::delete_many()
+        .filter(Column::PersonaId.eq(id))
+        .exec(db)
+        .await?;
+    let res = Personas::delete_by_id(id).exec(db).await?;
+    Ok(res.rows_affected > 0)
+}
+
+/// Make a persona active in a guild (channel 0) or a channel, None deactivates.
+pub async fn set_active_persona(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    channel_id: i64,
+    persona_id: Option<i64>,
+) -> Result<(), DbErr> {
+    use crate::entities::persona_activations::Column;
+    let Some(persona_id) = persona_id else {
+        PersonaActivations::delete_many()
+            .filter(Column::GuildId.eq(guild_id))
+            .filter(Column::ChannelId.eq(channel_id))
+            .exec(db)
+            .await?;
+        return Ok(());
+    };
+    let am = crate::entities::persona_activations::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        channel_id: ActiveValue::set(channel_id),
+        persona_id: ActiveValue::set(persona_id),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    PersonaActivations::insert(am)
+        .on_conflict(
+            OnConflict::columns([Column::GuildId, Column::ChannelId])
+                .update_columns([Column::PersonaId, Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+/// The persona active in exactly this scope (channel 0 for the guild), if any.
+pub async fn get_active_persona(db: &DatabaseConnection, guild_id: i64, channel_id: i64) -> Option<Persona> {
+    use crate::entities::persona_activations::Column;
+    let activation = PersonaActivations::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::ChannelId.eq(channel_id))
+        .one(db)
+        .await
+        .ok()??;
+    Personas::find_by_id(activation.persona_id)
+        .one(db)
+        .await
+        .ok()?
+        .map(Persona::from)
+}
+
+/// Where personas are active in a guild, as (channel id or 0, persona id).
+pub async fn persona_activations(db: &DatabaseConnection, guild_id: i64) -> Result<Vec<(i64, i64)>, DbErr> {
+    use crate::entities::persona_activations::Column;
+    let rows = PersonaActivations::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .order_by_asc(Column::ChannelId)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(|m| (m.channel_id, m.persona_id)).collect())
+}
+
+// --- Prompt templates ---
+/// A guild's own source for a prompt template, None if it uses the config's.
+pub async fn get_prompt_template(db: &DatabaseConnection, guild_id: i64, name: &str) -> Option<String> {
+    use crate::entities::prompt_templates::Column;
+    PromptTemplates::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Name.eq(name))
+        .one(db)
+        .await
+        .ok()
+        .flatten()
+        .map(|m| m.source)
+}
+
+/// Save a guild's prompt template, None goes back to the config's.
+pub async fn set_prompt_template(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    name: &str,
+    source: Option<String>,
+    updated_by: Option<i64>,
+) -> Result<(), DbErr> {
+    use crate::entities::prompt_templates::Column;
+    let Some(source) = source else {
+        PromptTemplates::delete_many()
+            .filter(Column::GuildId.eq(guild_id))
+            .filter(Column::Name.eq(name))
+            .exec(db)
+            .await?;
+        return Ok(());
+    };
+    let am = crate::entities::prompt_templates::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        name: ActiveValue::set(name.to_string()),
+        source: ActiveValue::set(source),
+        updated_by: ActiveValue::set(updated_by),
+        updated_at: ActiveValue::set(Utc::now()),
+    };
+    PromptTemplates::insert(am)
+        .on_conflict(
+            OnConflict::columns([Column::GuildId, Column::Name])
+                .update_columns([Column::Source, Column::UpdatedBy, Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+// --- Prompt history ---
+#[derive(Debug, Clone)]
+pub struct PromptVersion {
+    pub id: i64,
+    pub guild_id: i64,
+    pub kind: String,
+    pub target: String,
+    pub content: String,
+    pub author: Option<i64>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl From<crate::entities::prompt_versions::Model> for PromptVersion {
+    fn from(m: crate::entities::prompt_versions::Model) -> Self {
+        PromptVersion {
+            id: m.id,
+            guild_id: m.guild_id,
+            kind: m.kind,
+            target: m.target,
+            content: m.content,
+            author: m.author,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// Store a version of a prompt, returning its number.
+pub async fn add_prompt_version(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    kind: &str,
+    target: &str,
+    content: String,
+    author: Option<i64>,
+) -> Result<i64, DbErr> {
+    let am = crate::entities::prompt_versions::ActiveModel {
+        id: ActiveValue::not_set(),
+        guild_id: ActiveValue::set(guild_id),
+        kind: ActiveValue::set(kind.to_string()),
+        target: ActiveValue::set(target.to_string()),
+        content: ActiveValue::set(content),
+        author: ActiveValue::set(author),
+        created_at: ActiveValue::set(Utc::now()),
+    };
+    let res = PromptVersions::insert(am).exec(db).await?;
+    Ok(res.last_insert_id)
+}
+
+pub async fn get_prompt_version(db: &DatabaseConnection, id: i64) -> Result<Option<PromptVersion>, DbErr> {
+    Ok(PromptVersions::find_by_id(id).one(db).await?.map(PromptVersion::from))
+}
+
+/// The newest version of one prompt.
+pub async fn latest_prompt_version(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    kind: &str,
+    target: &str,
+) -> Result<Option<PromptVersion>, DbErr> {
+    use crate::entities::prompt_versions::Column;
+    Ok(PromptVersions::find()
+        .filter(Column::GuildId.eq(guild_id))
+        .filter(Column::Kind.eq(kind))
+        .filter(Column::Target.eq(target))
+        .order_by_desc(Column::Id)
+        .one(db)
+        .await?
+        .map(PromptVersion::from))
+}
+
+/// A guild's prompt versions and the global prompt's, newest first.
+pub async fn prompt_versions(
+    db: &DatabaseConnection,
+    guild_id: i64,
+    kind: Option<&str>,
+    limit: u64,
+) -> Result<Vec<PromptVersion>, DbErr> {
+    use crate::entities::prompt_versions::Column;
+    let mut query = PromptVersions::find().filter(Column::GuildId.is_in([guild_id, 0]));
+    if let Some(kind) = kind {
+        query = query.filter(Column::Kind.eq(kind));
+    }
+    let rows = query.order_by_desc(Column::Id).limit(limit).all(db).await?;
+    Ok(rows.into_iter().map(PromptVersion::from).collect())
+}
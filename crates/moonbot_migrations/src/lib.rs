@@ -9,6 +9,28 @@ mod m20250905_000001_user_directory_rag;
 mod m20250905_000002_channel_roleplay_table;
 mod m20250905_000004_guild_roleplay_table;
 mod m20250905_000005_corpus_rag;
+mod m20250906_000001_play_history_table;
+mod m20250906_000002_music_settings_table;
+mod m20250906_000003_music_autoplay_columns;
+mod m20250906_000004_music_dj_columns;
+mod m20250906_000005_music_limits_columns;
+mod m20250906_000006_music_blocklist_table;
+mod m20250906_000007_sound_clips_table;
+mod m20250907_000001_scheduled_jobs_table;
+mod m20250907_000002_user_settings_table;
+mod m20250908_000001_auto_reply_settings_table;
+mod m20250908_000002_auto_reply_triggers_table;
+mod m20250908_000003_moderation_settings_table;
+mod m20250908_000004_moderation_actions_table;
+mod m20250908_000005_infractions_table;
+mod m20250908_000006_dad_settings_table;
+mod m20250908_000007_user_dad_opt_out_column;
+mod m20250908_000008_responder_settings_table;
+mod m20250908_000009_custom_commands_table;
+mod m20250908_000010_personas_tables;
+mod m20250908_000011_persona_card_columns;
+mod m20250908_000012_prompt_templates_table;
+mod m20250908_000013_prompt_versions_table;
 
 pub struct Migrator;
 
@@ -25,6 +47,28 @@ impl MigratorTrait for Migrator {
             Box::new(m20250905_000002_channel_roleplay_table::Migration),
             Box::new(m20250905_000004_guild_roleplay_table::Migration),
             Box::new(m20250905_000005_corpus_rag::Migration),
+            Box::new(m20250906_000001_play_history_table::Migration),
+            Box::new(m20250906_000002_music_settings_table::Migration),
+            Box::new(m20250906_000003_music_autoplay_columns::Migration),
+            Box::new(m20250906_000004_music_dj_columns::Migration),
+            Box::new(m20250906_000005_music_limits_columns::Migration),
+            Box::new(m20250906_000006_music_blocklist_table::Migration),
+            Box::new(m20250906_000007_sound_clips_table::Migration),
+            Box::new(m20250907_000001_scheduled_jobs_table::Migration),
+            Box::new(m20250907_000002_user_settings_table::Migration),
+            Box::new(m20250908_000001_auto_reply_settings_table::Migration),
+            Box::new(m20250908_000002_auto_reply_triggers_table::Migration),
+            Box::new(m20250908_000003_moderation_settings_table::Migration),
+            Box::new(m20250908_000004_moderation_actions_table::Migration),
+            Box::new(m20250908_000005_infractions_table::Migration),
+            Box::new(m20250908_000006_dad_settings_table::Migration),
+            Box::new(m20250908_000007_user_dad_opt_out_column::Migration),
+            Box::new(m20250908_000008_responder_settings_table::Migration),
+            Box::new(m20250908_000009_custom_commands_table::Migration),
+            Box::new(m20250908_000010_personas_tables::Migration),
+            Box::new(m20250908_000011_persona_card_columns::Migration),
+            Box::new(m20250908_000012_prompt_templates_table::Migration),
+            Box::new(m20250908_000013_prompt_versions_table::Migration),
         ]
     }
 }
@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(PlayHistory::Table)
+					.if_not_exists()
+					.col(big_integer(PlayHistory::Id).auto_increment().primary_key())
+					.col(big_integer(PlayHistory::GuildId))
+					.col(big_integer_null(PlayHistory::RequesterId))
+					.col(text(PlayHistory::Title))
+					.col(text(PlayHistory::Author))
+					.col(text_null(PlayHistory::Uri))
+					.col(big_integer(PlayHistory::LengthMs))
+					.col(boolean(PlayHistory::Skipped).default(false))
+					.col(timestamp(PlayHistory::PlayedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_play_history_guild_played_at")
+					.table(PlayHistory::Table)
+					.col(PlayHistory::GuildId)
+					.col(PlayHistory::PlayedAt)
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(PlayHistory::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum PlayHistory {
+	Table,
+	Id,
+	GuildId,
+	RequesterId,
+	Title,
+	Author,
+	Uri,
+	LengthMs,
+	Skipped,
+	PlayedAt,
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(MusicSettings::Table)
+					.if_not_exists()
+					.col(big_unsigned_uniq(MusicSettings::GuildId).primary_key())
+					.col(boolean(MusicSettings::StayConnected).default(false))
+					.col(timestamp(MusicSettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(MusicSettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum MusicSettings {
+	Table,
+	GuildId,
+	StayConnected,
+	UpdatedAt,
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// SQLite only supports one column per ALTER TABLE
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(boolean(MusicSettings::Autoplay).default(false))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(string_len(MusicSettings::AutoplayMode, 16).default("related"))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::AutoplayMode)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::Autoplay)
+					.to_owned(),
+			)
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum MusicSettings {
+	Table,
+	Autoplay,
+	AutoplayMode,
+}
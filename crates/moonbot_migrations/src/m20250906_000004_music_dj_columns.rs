@@ -0,0 +1,53 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// SQLite only supports one column per ALTER TABLE
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(big_integer_null(MusicSettings::DjRoleId))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(double_null(MusicSettings::VoteSkipRatio))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::VoteSkipRatio)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::DjRoleId)
+					.to_owned(),
+			)
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum MusicSettings {
+	Table,
+	DjRoleId,
+	VoteSkipRatio,
+}
@@ -0,0 +1,104 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// SQLite only supports one column per ALTER TABLE
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(integer_null(MusicSettings::MaxQueueLength))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(integer_null(MusicSettings::MaxUserTracks))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(big_integer_null(MusicSettings::MaxTrackSeconds))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(integer_null(MusicSettings::MaxPlaylistSize))
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.add_column(boolean_null(MusicSettings::PreventDuplicates))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::PreventDuplicates)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::MaxPlaylistSize)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::MaxTrackSeconds)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::MaxUserTracks)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(MusicSettings::Table)
+					.drop_column(MusicSettings::MaxQueueLength)
+					.to_owned(),
+			)
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum MusicSettings {
+	Table,
+	MaxQueueLength,
+	MaxUserTracks,
+	MaxTrackSeconds,
+	MaxPlaylistSize,
+	PreventDuplicates,
+}
@@ -0,0 +1,55 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(MusicBlocklist::Table)
+					.if_not_exists()
+					.col(big_integer(MusicBlocklist::Id).auto_increment().primary_key())
+					.col(big_integer(MusicBlocklist::GuildId))
+					.col(string_len(MusicBlocklist::Kind, 16))
+					.col(text(MusicBlocklist::Value))
+					.col(big_integer_null(MusicBlocklist::AddedBy))
+					.col(timestamp(MusicBlocklist::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_music_blocklist_guild_kind_value")
+					.table(MusicBlocklist::Table)
+					.col(MusicBlocklist::GuildId)
+					.col(MusicBlocklist::Kind)
+					.col(MusicBlocklist::Value)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(MusicBlocklist::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum MusicBlocklist {
+	Table,
+	Id,
+	GuildId,
+	Kind,
+	Value,
+	AddedBy,
+	CreatedAt,
+}
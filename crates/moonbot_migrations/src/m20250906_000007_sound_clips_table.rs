@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(SoundClips::Table)
+					.if_not_exists()
+					.col(big_integer(SoundClips::Id).auto_increment().primary_key())
+					.col(big_integer(SoundClips::GuildId))
+					.col(string_len(SoundClips::Name, 32))
+					.col(text(SoundClips::FileName))
+					.col(big_integer(SoundClips::SizeBytes))
+					.col(big_integer(SoundClips::DurationMs))
+					.col(big_integer_null(SoundClips::AddedBy))
+					.col(big_integer(SoundClips::Plays).default(0))
+					.col(timestamp(SoundClips::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_sound_clips_guild_name")
+					.table(SoundClips::Table)
+					.col(SoundClips::GuildId)
+					.col(SoundClips::Name)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(SoundClips::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum SoundClips {
+	Table,
+	Id,
+	GuildId,
+	Name,
+	FileName,
+	SizeBytes,
+	DurationMs,
+	AddedBy,
+	Plays,
+	CreatedAt,
+}
@@ -0,0 +1,62 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(ScheduledJobs::Table)
+					.if_not_exists()
+					.col(big_integer(ScheduledJobs::Id).auto_increment().primary_key())
+					.col(string_len(ScheduledJobs::Kind, 16))
+					.col(big_integer_null(ScheduledJobs::GuildId))
+					.col(big_integer(ScheduledJobs::ChannelId))
+					.col(big_integer(ScheduledJobs::UserId))
+					.col(text(ScheduledJobs::Message))
+					.col(text_null(ScheduledJobs::Cron))
+					.col(string_len(ScheduledJobs::Timezone, 64))
+					.col(timestamp(ScheduledJobs::NextRun))
+					.col(timestamp_null(ScheduledJobs::LastRun))
+					.col(timestamp(ScheduledJobs::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_scheduled_jobs_next_run")
+					.table(ScheduledJobs::Table)
+					.col(ScheduledJobs::NextRun)
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(ScheduledJobs::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum ScheduledJobs {
+	Table,
+	Id,
+	Kind,
+	GuildId,
+	ChannelId,
+	UserId,
+	Message,
+	Cron,
+	Timezone,
+	NextRun,
+	LastRun,
+	CreatedAt,
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(UserSettings::Table)
+					.if_not_exists()
+					.col(big_integer(UserSettings::UserId).primary_key())
+					.col(string_len_null(UserSettings::Timezone, 64))
+					.col(timestamp(UserSettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(UserSettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum UserSettings {
+	Table,
+	UserId,
+	Timezone,
+	UpdatedAt,
+}
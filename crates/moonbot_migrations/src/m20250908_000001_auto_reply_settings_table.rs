@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(AutoReplySettings::Table)
+					.if_not_exists()
+					.col(big_integer(AutoReplySettings::Id).auto_increment().primary_key())
+					.col(big_integer(AutoReplySettings::GuildId))
+					// 0 for the whole server
+					.col(big_integer(AutoReplySettings::ChannelId))
+					.col(double_null(AutoReplySettings::Chance))
+					.col(big_integer_null(AutoReplySettings::CooldownSecs))
+					.col(boolean_null(AutoReplySettings::Threads))
+					.col(timestamp(AutoReplySettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_auto_reply_settings_guild_channel")
+					.table(AutoReplySettings::Table)
+					.col(AutoReplySettings::GuildId)
+					.col(AutoReplySettings::ChannelId)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(AutoReplySettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum AutoReplySettings {
+	Table,
+	Id,
+	GuildId,
+	ChannelId,
+	Chance,
+	CooldownSecs,
+	Threads,
+	UpdatedAt,
+}
@@ -0,0 +1,62 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(AutoReplyTriggers::Table)
+					.if_not_exists()
+					.col(big_integer(AutoReplyTriggers::Id).auto_increment().primary_key())
+					.col(big_integer(AutoReplyTriggers::GuildId))
+					// 0 for the whole server
+					.col(big_integer(AutoReplyTriggers::ChannelId))
+					.col(string_len(AutoReplyTriggers::Effect, 16))
+					.col(string_len(AutoReplyTriggers::Kind, 16))
+					.col(text(AutoReplyTriggers::Value))
+					.col(big_integer_null(AutoReplyTriggers::AddedBy))
+					.col(timestamp(AutoReplyTriggers::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_auto_reply_triggers_unique")
+					.table(AutoReplyTriggers::Table)
+					.col(AutoReplyTriggers::GuildId)
+					.col(AutoReplyTriggers::ChannelId)
+					.col(AutoReplyTriggers::Effect)
+					.col(AutoReplyTriggers::Kind)
+					.col(AutoReplyTriggers::Value)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(AutoReplyTriggers::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum AutoReplyTriggers {
+	Table,
+	Id,
+	GuildId,
+	ChannelId,
+	Effect,
+	Kind,
+	Value,
+	AddedBy,
+	CreatedAt,
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(ModerationSettings::Table)
+					.if_not_exists()
+					.col(big_integer(ModerationSettings::GuildId).primary_key())
+					.col(boolean(ModerationSettings::Enabled).default(false))
+					.col(big_integer_null(ModerationSettings::LogChannelId))
+					// JSON object of category -> threshold
+					.col(text(ModerationSettings::Thresholds).default("{}"))
+					.col(timestamp(ModerationSettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(ModerationSettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum ModerationSettings {
+	Table,
+	GuildId,
+	Enabled,
+	LogChannelId,
+	Thresholds,
+	UpdatedAt,
+}
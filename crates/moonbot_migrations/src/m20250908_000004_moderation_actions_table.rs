@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(ModerationActions::Table)
+					.if_not_exists()
+					.col(big_integer(ModerationActions::Id).auto_increment().primary_key())
+					.col(big_integer(ModerationActions::GuildId))
+					.col(big_integer_null(ModerationActions::ChannelId))
+					.col(big_integer_null(ModerationActions::MessageId))
+					.col(big_integer(ModerationActions::TargetUserId))
+					// Null for the bot's own flags
+					.col(big_integer_null(ModerationActions::ModeratorId))
+					.col(string_len(ModerationActions::Action, 16))
+					.col(text_null(ModerationActions::Reason))
+					// JSON object of category -> score, for flags
+					.col(text_null(ModerationActions::Scores))
+					.col(timestamp(ModerationActions::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_moderation_actions_guild_target")
+					.table(ModerationActions::Table)
+					.col(ModerationActions::GuildId)
+					.col(ModerationActions::TargetUserId)
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(ModerationActions::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum ModerationActions {
+	Table,
+	Id,
+	GuildId,
+	ChannelId,
+	MessageId,
+	TargetUserId,
+	ModeratorId,
+	Action,
+	Reason,
+	Scores,
+	CreatedAt,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(Infractions::Table)
+					.if_not_exists()
+					.col(big_integer(Infractions::Id).auto_increment().primary_key())
+					.col(big_integer(Infractions::GuildId))
+					.col(big_integer(Infractions::UserId))
+					// Null for automatic escalations
+					.col(big_integer_null(Infractions::ModeratorId))
+					.col(string_len(Infractions::Kind, 16))
+					.col(text_null(Infractions::Reason))
+					.col(big_integer_null(Infractions::DurationSecs))
+					.col(boolean(Infractions::Active).default(true))
+					.col(big_integer_null(Infractions::PardonedBy))
+					.col(timestamp_null(Infractions::PardonedAt))
+					.col(timestamp(Infractions::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_infractions_guild_user")
+					.table(Infractions::Table)
+					.col(Infractions::GuildId)
+					.col(Infractions::UserId)
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(Infractions::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum Infractions {
+	Table,
+	Id,
+	GuildId,
+	UserId,
+	ModeratorId,
+	Kind,
+	Reason,
+	DurationSecs,
+	Active,
+	PardonedBy,
+	PardonedAt,
+	CreatedAt,
+}
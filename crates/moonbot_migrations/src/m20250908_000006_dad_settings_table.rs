@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(DadSettings::Table)
+					.if_not_exists()
+					.col(big_integer(DadSettings::GuildId).primary_key())
+					// Overrides of the [dad] config, null inherits
+					.col(boolean_null(DadSettings::Enabled))
+					.col(double_null(DadSettings::Chance))
+					.col(big_integer_null(DadSettings::CooldownSecs))
+					.col(string_len_null(DadSettings::NicknameMode, 16))
+					.col(big_integer_null(DadSettings::NicknameMinutes))
+					.col(timestamp(DadSettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(DadSettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum DadSettings {
+	Table,
+	GuildId,
+	Enabled,
+	Chance,
+	CooldownSecs,
+	NicknameMode,
+	NicknameMinutes,
+	UpdatedAt,
+}
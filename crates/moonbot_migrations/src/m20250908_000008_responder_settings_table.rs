@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(ResponderSettings::Table)
+					.if_not_exists()
+					.col(big_integer(ResponderSettings::Id).auto_increment().primary_key())
+					.col(big_integer(ResponderSettings::GuildId))
+					.col(string(ResponderSettings::Name))
+					.col(boolean(ResponderSettings::Enabled))
+					.col(timestamp(ResponderSettings::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_responder_settings_guild_name")
+					.table(ResponderSettings::Table)
+					.col(ResponderSettings::GuildId)
+					.col(ResponderSettings::Name)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(ResponderSettings::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum ResponderSettings {
+	Table,
+	Id,
+	GuildId,
+	Name,
+	Enabled,
+	UpdatedAt,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(CustomCommands::Table)
+					.if_not_exists()
+					.col(big_integer(CustomCommands::Id).auto_increment().primary_key())
+					.col(big_integer(CustomCommands::GuildId))
+					.col(string_len(CustomCommands::Name, 32))
+					.col(text(CustomCommands::Content))
+					.col(boolean(CustomCommands::Embed).default(false))
+					// JSON array of other names
+					.col(text(CustomCommands::Aliases).default("[]"))
+					.col(big_integer(CustomCommands::Uses).default(0))
+					.col(big_integer_null(CustomCommands::CreatedBy))
+					.col(timestamp(CustomCommands::CreatedAt).default(Expr::current_timestamp()))
+					.col(timestamp(CustomCommands::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_custom_commands_guild_name")
+					.table(CustomCommands::Table)
+					.col(CustomCommands::GuildId)
+					.col(CustomCommands::Name)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(CustomCommands::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum CustomCommands {
+	Table,
+	Id,
+	GuildId,
+	Name,
+	Content,
+	Embed,
+	Aliases,
+	Uses,
+	CreatedBy,
+	CreatedAt,
+	UpdatedAt,
+}
@@ -0,0 +1,146 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(Personas::Table)
+					.if_not_exists()
+					.col(big_integer(Personas::Id).auto_increment().primary_key())
+					.col(big_integer(Personas::GuildId))
+					.col(string_len(Personas::Name, 32))
+					.col(text(Personas::Description).default(""))
+					.col(text(Personas::SystemText))
+					.col(text(Personas::ExampleDialogues).default(""))
+					// Null uses openai.auto
+					.col(string_null(Personas::Model))
+					.col(double_null(Personas::Temperature))
+					.col(big_integer_null(Personas::CreatedBy))
+					.col(timestamp(Personas::CreatedAt).default(Expr::current_timestamp()))
+					.col(timestamp(Personas::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_personas_guild_name")
+					.table(Personas::Table)
+					.col(Personas::GuildId)
+					.col(Personas::Name)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_table(
+				Table::create()
+					.table(PersonaActivations::Table)
+					.if_not_exists()
+					.col(big_integer(PersonaActivations::Id).auto_increment().primary_key())
+					.col(big_integer(PersonaActivations::GuildId))
+					// 0 for the whole server
+					.col(big_integer(PersonaActivations::ChannelId))
+					.col(big_integer(PersonaActivations::PersonaId))
+					.col(timestamp(PersonaActivations::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_persona_activations_guild_channel")
+					.table(PersonaActivations::Table)
+					.col(PersonaActivations::GuildId)
+					.col(PersonaActivations::ChannelId)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+
+		// Server personas set with /roleplay become a persona called "roleplay", still active
+		let copy = Query::insert()
+			.into_table(Personas::Table)
+			.columns([Personas::GuildId, Personas::Name, Personas::Description, Personas::SystemText])
+			.select_from(
+				Query::select()
+					.column(GuildRoleplay::GuildId)
+					.expr(Expr::val("roleplay"))
+					.expr(Expr::val("Imported from /roleplay"))
+					.column(GuildRoleplay::Persona)
+					.from(GuildRoleplay::Table)
+					.to_owned(),
+			)
+			.map_err(|e| DbErr::Migration(e.to_string()))?
+			.to_owned();
+		manager.exec_stmt(copy).await?;
+
+		let activate = Query::insert()
+			.into_table(PersonaActivations::Table)
+			.columns([PersonaActivations::GuildId, PersonaActivations::ChannelId, PersonaActivations::PersonaId])
+			.select_from(
+				Query::select()
+					.column(Personas::GuildId)
+					.expr(Expr::val(0i64))
+					.column(Personas::Id)
+					.from(Personas::Table)
+					.and_where(Expr::col(Personas::Name).eq("roleplay"))
+					.to_owned(),
+			)
+			.map_err(|e| DbErr::Migration(e.to_string()))?
+			.to_owned();
+		manager.exec_stmt(activate).await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(PersonaActivations::Table).to_owned())
+			.await?;
+		manager
+			.drop_table(Table::drop().table(Personas::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum Personas {
+	Table,
+	Id,
+	GuildId,
+	Name,
+	Description,
+	SystemText,
+	ExampleDialogues,
+	Model,
+	Temperature,
+	CreatedBy,
+	CreatedAt,
+	UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum PersonaActivations {
+	Table,
+	Id,
+	GuildId,
+	ChannelId,
+	PersonaId,
+	UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum GuildRoleplay {
+	Table,
+	GuildId,
+	Persona,
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// SQLite only supports one column per ALTER TABLE
+		for column in [
+			string(Personas::DisplayName).default("").to_owned(),
+			text(Personas::Personality).default("").to_owned(),
+			text(Personas::Scenario).default("").to_owned(),
+			text(Personas::FirstMessage).default("").to_owned(),
+		] {
+			manager
+				.alter_table(
+					Table::alter()
+						.table(Personas::Table)
+						.add_column(column)
+						.to_owned(),
+				)
+				.await?;
+		}
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		for column in [
+			Personas::FirstMessage,
+			Personas::Scenario,
+			Personas::Personality,
+			Personas::DisplayName,
+		] {
+			manager
+				.alter_table(
+					Table::alter()
+						.table(Personas::Table)
+						.drop_column(column)
+						.to_owned(),
+				)
+				.await?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum Personas {
+	Table,
+	// Character name the persona speaks as, empty uses the persona name
+	DisplayName,
+	Personality,
+	Scenario,
+	FirstMessage,
+}
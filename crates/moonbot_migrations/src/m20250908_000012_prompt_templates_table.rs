@@ -0,0 +1,55 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(PromptTemplates::Table)
+					.if_not_exists()
+					.col(big_integer(PromptTemplates::Id).auto_increment().primary_key())
+					.col(big_integer(PromptTemplates::GuildId))
+					// "system" or "persona"
+					.col(string_len(PromptTemplates::Name, 32))
+					.col(text(PromptTemplates::Source))
+					.col(big_integer_null(PromptTemplates::UpdatedBy))
+					.col(timestamp(PromptTemplates::UpdatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_prompt_templates_guild_name")
+					.table(PromptTemplates::Table)
+					.col(PromptTemplates::GuildId)
+					.col(PromptTemplates::Name)
+					.unique()
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(PromptTemplates::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum PromptTemplates {
+	Table,
+	Id,
+	GuildId,
+	Name,
+	Source,
+	UpdatedBy,
+	UpdatedAt,
+}
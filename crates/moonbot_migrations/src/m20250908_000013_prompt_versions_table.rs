@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(PromptVersions::Table)
+					.if_not_exists()
+					// Doubles as the version number
+					.col(big_integer(PromptVersions::Id).auto_increment().primary_key())
+					// 0 for the global prompt
+					.col(big_integer(PromptVersions::GuildId))
+					// "global", "channel", "persona" or "template"
+					.col(string_len(PromptVersions::Kind, 16))
+					// Channel id, persona or template name, empty for the global prompt
+					.col(string(PromptVersions::Target))
+					// The whole prompt after the change, empty when it was cleared
+					.col(text(PromptVersions::Content))
+					.col(big_integer_null(PromptVersions::Author))
+					.col(timestamp(PromptVersions::CreatedAt).default(Expr::current_timestamp()))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.if_not_exists()
+					.name("idx_prompt_versions_target")
+					.table(PromptVersions::Table)
+					.col(PromptVersions::GuildId)
+					.col(PromptVersions::Kind)
+					.col(PromptVersions::Target)
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(PromptVersions::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum PromptVersions {
+	Table,
+	Id,
+	GuildId,
+	Kind,
+	Target,
+	Content,
+	Author,
+	CreatedAt,
+}